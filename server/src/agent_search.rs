@@ -1,37 +1,76 @@
-use crate::llm::default_completion;
+use crate::checkpoint::{checkpoint_root, sub_search_id, CheckpointError, Checkpointer};
 use crate::llm::LLMError;
+use crate::llm::{default_completion, race_completion};
 use crate::prompts::{
     build_analyze_result_system_prompt, build_sufficient_information_check_prompt, Prompt,
-    AGGREGATE_WEB_SEARCH_FINDINGS_PROMPT, WEB_SEARCH_USE_SAME_WEB_SEARCH_FINDINGS_DOCUMENT,
+    AGGREGATE_WEB_SEARCH_FINDINGS_PROMPT, DEDUP_ANALYSIS_SYSTEM_PROMPT,
+    WEB_SEARCH_USE_SAME_WEB_SEARCH_FINDINGS_DOCUMENT,
 };
-use crate::query::QueryStrategy;
+use crate::query::{QueryStrategy, QueryTranslation, StrategySelection};
+use crate::request_context::current_request_id;
 use crate::result_format::{
-    format_result, AnalysisDocument, ResultFormat, ResultFormatError, ResultFormatResponse,
+    apply_post_processors, format_result, AnalysisDocument, DryRunResult, PostProcessor,
+    ResultFormat, ResultFormatError, ResultFormatResponse, SkippedResult,
+};
+use crate::search::{
+    self, dedup_search_results, is_url_allowed, merge_domain_lists, rerank_search_results, search,
+    search_page, SearchError, SearchResult, SearxSafeSearch, MAX_RESULTS_TO_VISIT,
+};
+use crate::server::ServerState;
+use crate::session::{
+    current_search_session, is_url_skipped, report_progress, seeded_search_results,
+    with_search_session,
+};
+use crate::usage::{
+    count_query_llm_calls, track_usage, with_current_search_context, with_stage_temperatures,
+    LLMCallCounter, LLMCallStage, StageTemperatures, UsageSummary, UsageTracker,
 };
-use crate::search::SearchResult;
 use crate::utils::ParseJsonError;
-use crate::utils::{display_search_results_with_indices, parse_json_response};
-use crate::webpage_parse::{visit_and_parse_webpage, WebpageParseError};
+use crate::utils::{
+    display_search_results_with_indices, filter_results_by_patterns, new_uuid_v4, normalize_url,
+    parse_json_response_with_retry, source_credibility_score, truncate_at_sentence,
+    DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
+};
+use crate::webpage_parse::{
+    extract_links, is_stale_date, latest_metadata_date, validate_user_agent,
+    visit_and_parse_webpage, DomParseConfig, ParsedWebpage, WebpageParseError,
+};
 use rocket::{FromForm, FromFormField};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
 
-use futures::future::join_all;
+use futures::stream::{self, StreamExt};
 use tokio::task;
 use tokio::task::JoinError;
 
+pub mod adaptive_parallel;
+pub mod best_first;
 pub mod human;
+pub mod multi_query_parallel_tree;
 pub mod parallel;
 pub mod parallel_tree;
 pub mod sequential;
 
+pub use adaptive_parallel::{adaptive_parallel_agent_search, AdaptiveParallelAgentSearchError};
+pub use best_first::{best_first_agent_search, BestFirstAgentSearchError};
 pub use human::{human_agent_search, HumanAgentSearchError};
+pub use multi_query_parallel_tree::{
+    multi_query_parallel_tree_agent_search, MultiQueryParallelTreeAgentSearchError,
+};
 pub use parallel::{parallel_agent_search, ParallelAgentSearchError};
 pub use parallel_tree::{parallel_tree_agent_search, ParallelTreeAgentSearchError};
 pub use sequential::{sequential_agent_search, SequentialAgentSearchError};
 
-use crate::query::{synthesize_queries, QuerySynthesisError};
+use crate::pii::{redact_analysis_document, redact_result_format_response};
+use crate::query::{
+    correct_query_spelling, generate_follow_up_queries, synthesize_comparison_queries,
+    synthesize_queries, translate_to_english, QuerySynthesisError,
+};
 
 #[derive(Deserialize, Debug, Clone, FromForm)]
 pub struct AgentSearchInput {
@@ -51,6 +90,78 @@ pub struct AgentSearchInput {
     pub whitelisted_base_urls: Option<Vec<String>>,
     #[serde(default)]
     pub blacklisted_base_urls: Option<Vec<String>>,
+    #[serde(default)]
+    pub fallback_to_snippet: Option<bool>,
+    #[serde(default)]
+    pub adaptive_batch_size: Option<usize>,
+    #[serde(default)]
+    pub dry_run: Option<bool>,
+    #[serde(default)]
+    pub safe_search: Option<SearxSafeSearch>,
+    #[serde(default)]
+    pub max_parallel_page_visits: Option<usize>,
+    #[serde(default)]
+    pub html_strip_tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub html_keep_attributes: Option<Vec<String>>,
+    #[serde(default)]
+    pub result_formats: Option<Vec<ResultFormat>>,
+    #[serde(default)]
+    pub auto_translate_query_to_english: Option<bool>,
+    #[serde(default)]
+    pub follow_links_depth: Option<u32>,
+    #[serde(default)]
+    pub max_page_content_chars: Option<usize>,
+    #[serde(default)]
+    pub enable_multi_hop: Option<bool>,
+    #[serde(default)]
+    pub temperatures: Option<StageTemperatures>,
+    #[serde(default)]
+    pub max_llm_calls: Option<u32>,
+    #[serde(default)]
+    pub resume_from_checkpoint: Option<String>,
+    #[serde(default)]
+    pub rerank_results: Option<bool>,
+    #[serde(default)]
+    pub suggest_follow_up_queries: Option<bool>,
+    #[serde(default)]
+    pub skip_bot_blocked_urls: Option<bool>,
+    #[serde(default)]
+    pub post_processors: Option<Vec<PostProcessor>>,
+    #[serde(default)]
+    pub merge_strategy: Option<AnalysisMergeStrategy>,
+    #[serde(default)]
+    pub max_dependency_tree_levels: Option<usize>,
+    #[serde(default)]
+    pub sort_by_source_credibility: Option<bool>,
+    #[serde(default)]
+    pub source_credibility_category: Option<String>,
+    #[serde(default)]
+    pub spell_check_query: Option<bool>,
+    #[serde(default)]
+    pub redact_pii: Option<bool>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub qa_pair_count: Option<usize>,
+    #[serde(default)]
+    pub dynamic_parallel: Option<bool>,
+    #[serde(default)]
+    pub max_extra_pages: Option<usize>,
+    #[serde(default)]
+    pub inter_level_parallelism: Option<bool>,
+    #[serde(default)]
+    pub max_analysis_growth_ratio: Option<f32>,
+    #[serde(default)]
+    pub vision_enabled: Option<bool>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub max_content_age_days: Option<u32>,
+    #[serde(default)]
+    pub whitelisted_url_patterns: Option<Vec<String>>,
+    #[serde(default)]
+    pub min_page_content_chars: Option<usize>,
 }
 
 impl Default for AgentSearchInput {
@@ -65,6 +176,42 @@ impl Default for AgentSearchInput {
             custom_result_format_description: None,
             whitelisted_base_urls: None,
             blacklisted_base_urls: None,
+            fallback_to_snippet: Some(false),
+            adaptive_batch_size: Some(adaptive_parallel::DEFAULT_ADAPTIVE_BATCH_SIZE),
+            dry_run: Some(false),
+            safe_search: None,
+            max_parallel_page_visits: Some(DEFAULT_MAX_PARALLEL_PAGE_VISITS),
+            html_strip_tags: None,
+            html_keep_attributes: None,
+            result_formats: None,
+            auto_translate_query_to_english: Some(false),
+            follow_links_depth: Some(0),
+            max_page_content_chars: Some(DEFAULT_MAX_PAGE_CONTENT_CHARS),
+            enable_multi_hop: Some(false),
+            temperatures: None,
+            max_llm_calls: None,
+            resume_from_checkpoint: None,
+            rerank_results: None,
+            suggest_follow_up_queries: None,
+            skip_bot_blocked_urls: None,
+            post_processors: None,
+            merge_strategy: None,
+            max_dependency_tree_levels: None,
+            sort_by_source_credibility: None,
+            source_credibility_category: None,
+            spell_check_query: Some(false),
+            redact_pii: Some(false),
+            language: None,
+            qa_pair_count: None,
+            dynamic_parallel: Some(false),
+            max_extra_pages: Some(parallel::DEFAULT_MAX_EXTRA_PAGES),
+            inter_level_parallelism: Some(false),
+            max_analysis_growth_ratio: None,
+            vision_enabled: None,
+            user_agent: None,
+            max_content_age_days: None,
+            whitelisted_url_patterns: None,
+            min_page_content_chars: None,
         }
     }
 }
@@ -77,9 +224,163 @@ impl AgentSearchInput {
             self.blacklisted_base_urls.as_ref(),
         )
     }
+
+    pub fn requested_result_formats(&self) -> Vec<ResultFormat> {
+        match &self.result_formats {
+            Some(result_formats) if !result_formats.is_empty() => result_formats.clone(),
+            _ => vec![self.result_format.clone().unwrap_or_default()],
+        }
+    }
+
+    pub fn follow_links_depth(&self) -> u32 {
+        self.follow_links_depth
+            .unwrap_or(0)
+            .min(MAX_FOLLOW_LINKS_DEPTH)
+    }
+
+    pub fn should_follow_links(&self, result: &SearchResult) -> bool {
+        result.link_depth.unwrap_or(0) < self.follow_links_depth()
+    }
+
+    pub fn filter_discovered_results<'a>(
+        &self,
+        discovered_results: Vec<SearchResult>,
+        known_results: impl IntoIterator<Item = &'a SearchResult>,
+    ) -> Vec<SearchResult> {
+        let mut known_urls = known_results
+            .into_iter()
+            .map(|result| normalize_url(&result.url))
+            .collect::<HashSet<_>>();
+        let discovered_results = match &self.whitelisted_url_patterns {
+            Some(patterns) => filter_results_by_patterns(discovered_results, patterns),
+            None => discovered_results,
+        };
+        discovered_results
+            .into_iter()
+            .filter(|result| {
+                is_url_allowed(
+                    &result.url,
+                    self.whitelisted_base_urls.as_ref(),
+                    self.blacklisted_base_urls.as_ref(),
+                )
+            })
+            .filter(|result| known_urls.insert(normalize_url(&result.url)))
+            .take(MAX_DISCOVERED_LINKS_PER_PAGE)
+            .collect()
+    }
+
+    pub fn skip_bot_blocked_urls(&self) -> bool {
+        self.skip_bot_blocked_urls.unwrap_or(true)
+    }
+
+    pub fn sort_by_source_credibility(&self, results: &mut [SearchResult]) {
+        if !self.sort_by_source_credibility.unwrap_or(false) {
+            return;
+        }
+        let category = self.source_credibility_category.as_deref();
+        results.sort_by(|a, b| {
+            source_credibility_score(&b.url, category)
+                .total_cmp(&source_credibility_score(&a.url, category))
+        });
+    }
+
+    pub fn freshness_filtered_dom_parse_config(&self) -> DomParseConfig {
+        DomParseConfig {
+            max_content_age_days: self.max_content_age_days,
+            ..self.dom_parse_config()
+        }
+    }
+
+    pub fn dom_parse_config(&self) -> DomParseConfig {
+        DomParseConfig {
+            max_content_chars: Some(
+                self.max_page_content_chars
+                    .unwrap_or(DEFAULT_MAX_PAGE_CONTENT_CHARS),
+            ),
+            vision_enabled: self.vision_enabled.unwrap_or(false),
+            user_agent: self.user_agent.clone(),
+            min_content_chars: Some(
+                self.min_page_content_chars
+                    .unwrap_or(DEFAULT_MIN_PAGE_CONTENT_CHARS),
+            ),
+            ..DomParseConfig::from_overrides(
+                self.html_strip_tags.as_ref(),
+                self.html_keep_attributes.as_ref(),
+            )
+        }
+    }
+
+    pub fn checkpoint_search_id(&self) -> Option<String> {
+        match &self.resume_from_checkpoint {
+            Some(search_id) => Some(search_id.clone()),
+            None => {
+                checkpoint_root()?;
+                Some(new_uuid_v4())
+            }
+        }
+    }
+
+    pub fn sub_query_checkpoint_search_id(&self) -> Option<String> {
+        let search_id = self.checkpoint_search_id()?;
+        tracing::info!(
+            request_id = %current_request_id(),
+            "Saving sub-query checkpoints for query \"{}\" under search ID {}",
+            self.query,
+            search_id
+        );
+        Some(search_id)
+    }
+
+    pub fn checkpointer(&self) -> Option<Checkpointer> {
+        let search_id = self.checkpoint_search_id()?;
+        match Checkpointer::new(&search_id) {
+            Ok(checkpointer) => {
+                tracing::info!(
+                    request_id = %current_request_id(),
+                    "Saving checkpoints for query \"{}\" under search ID {}",
+                    self.query,
+                    search_id
+                );
+                Some(checkpointer)
+            }
+            Err(e) => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Checkpoints disabled for query \"{}\": {}",
+                    self.query,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    pub fn initial_analysis(&self) -> Result<AnalysisDocument, CheckpointError> {
+        let empty_analysis = AnalysisDocument {
+            content: String::new(),
+            visited_results: Vec::new(),
+            unvisited_results: Vec::new(),
+            skipped_results: Vec::new(),
+        };
+        let search_id = match &self.resume_from_checkpoint {
+            Some(search_id) => search_id,
+            None => return Ok(empty_analysis),
+        };
+        match Checkpointer::new(search_id)?.load()? {
+            Some(analysis) => Ok(analysis),
+            None => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "No checkpoint found for search ID {}, starting from scratch",
+                    search_id
+                );
+                Ok(empty_analysis)
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, FromFormField)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromFormField)]
 pub enum AgentSearchStrategy {
     #[serde(rename = "human")]
     Human,
@@ -89,6 +390,12 @@ pub enum AgentSearchStrategy {
     Sequential,
     #[serde(rename = "parallel_tree")]
     ParallelTree,
+    #[serde(rename = "adaptive_parallel")]
+    AdaptiveParallel,
+    #[serde(rename = "multi_query_parallel_tree")]
+    MultiQueryParallelTree,
+    #[serde(rename = "best_first")]
+    BestFirst,
 }
 
 impl Default for AgentSearchStrategy {
@@ -97,17 +404,92 @@ impl Default for AgentSearchStrategy {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromFormField)]
+pub enum AnalysisMergeStrategy {
+    #[default]
+    #[serde(rename = "append")]
+    Append,
+    #[serde(rename = "replace")]
+    Replace,
+    #[serde(rename = "summarize")]
+    Summarize,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AgentSearchResult {
     pub raw_analysis: AnalysisDocument,
-    pub queries_executed: Vec<String>,
+    pub queries_executed: Vec<QueryExecutionRecord>,
     pub response: ResultFormatResponse,
+    pub auto_selected_strategy: Option<StrategySelection>,
+    pub query_translation: Option<QueryTranslation>,
+    pub usage: UsageSummary,
+    pub rate_limit_delays_ms: u64,
+    pub truncated_due_to_llm_budget: bool,
+    pub suggested_follow_up_queries: Option<Vec<String>>,
+    #[serde(default)]
+    pub query_was_corrected: bool,
+    #[serde(default)]
+    pub original_query: Option<String>,
+    #[serde(default)]
+    pub served_from_cache: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AgentSearchMultiFormatResult {
+    pub raw_analysis: AnalysisDocument,
+    pub queries_executed: Vec<QueryExecutionRecord>,
+    pub responses: Vec<ResultFormatResponse>,
+    pub auto_selected_strategy: Option<StrategySelection>,
+    pub query_translation: Option<QueryTranslation>,
+    pub usage: UsageSummary,
+    pub rate_limit_delays_ms: u64,
+    pub truncated_due_to_llm_budget: bool,
+    pub suggested_follow_up_queries: Option<Vec<String>>,
+    #[serde(default)]
+    pub query_was_corrected: bool,
+    #[serde(default)]
+    pub original_query: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PreFormattedAgentSearchResult {
     pub raw_analysis: AnalysisDocument,
-    pub queries_executed: Vec<String>,
+    pub queries_executed: Vec<QueryExecutionRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryExecutionRecord {
+    pub query: String,
+    pub duration_ms: u64,
+    pub pages_visited: usize,
+    pub llm_calls: usize,
+    pub strategy: AgentSearchStrategy,
+    #[serde(skip)]
+    started_at: Option<Instant>,
+}
+
+pub async fn record_query_execution<F, E>(
+    query: &str,
+    strategy: AgentSearchStrategy,
+    search: F,
+) -> Result<PreFormattedAgentSearchResult, E>
+where
+    F: Future<Output = Result<PreFormattedAgentSearchResult, E>>,
+{
+    let started_at = Instant::now();
+    let llm_calls = LLMCallCounter::new(None);
+    let mut result = count_query_llm_calls(llm_calls.clone(), search).await?;
+    if result.queries_executed.is_empty() {
+        result.queries_executed.push(QueryExecutionRecord {
+            query: query.to_string(),
+            duration_ms: started_at.elapsed().as_millis() as u64,
+            pages_visited: result.raw_analysis.visited_results.len(),
+            llm_calls: llm_calls.calls() as usize,
+            strategy,
+            started_at: Some(started_at),
+        });
+    }
+    Ok(result)
 }
 
 #[derive(Error, Debug)]
@@ -118,6 +500,23 @@ pub enum VisitAndExtractRelevantInfoError {
     WebpageParseError(#[from] WebpageParseError),
     #[error("Join error: {0}")]
     JoinError(#[from] JoinError),
+    #[error("Stale content at {url} (last updated {date})")]
+    StaleContent { url: String, date: String },
+    #[error("Skipped {}: {}", .0.url, .0.reason)]
+    Skipped(SkippedResult),
+}
+
+impl VisitAndExtractRelevantInfoError {
+    pub fn is_bot_challenge(&self) -> bool {
+        matches!(
+            self,
+            VisitAndExtractRelevantInfoError::WebpageParseError(WebpageParseError::BotChallenge(_))
+        )
+    }
+
+    pub fn is_stale_content(&self) -> bool {
+        matches!(self, VisitAndExtractRelevantInfoError::StaleContent { .. })
+    }
 }
 
 #[derive(Error, Debug)]
@@ -139,6 +538,12 @@ pub enum AgentSingleSearchError {
     SequentialAgentSearchError(#[from] SequentialAgentSearchError),
     #[error("Parallel tree agent search failed: {0}")]
     ParallelTreeAgentSearchError(#[from] ParallelTreeAgentSearchError),
+    #[error("Adaptive parallel agent search failed: {0}")]
+    AdaptiveParallelAgentSearchError(#[from] AdaptiveParallelAgentSearchError),
+    #[error("Multi-query parallel tree agent search failed: {0}")]
+    MultiQueryParallelTreeAgentSearchError(#[from] MultiQueryParallelTreeAgentSearchError),
+    #[error("Best-first agent search failed: {0}")]
+    BestFirstAgentSearchError(#[from] BestFirstAgentSearchError),
 }
 
 #[derive(Error, Debug)]
@@ -147,51 +552,295 @@ pub enum AgentSearchError {
     QuerySynthesisError(#[from] QuerySynthesisError),
     #[error("Agent single search failed: {0}")]
     SingleSearchError(#[from] AgentSingleSearchError),
+    #[error("Analysis deduplication failed: {0}")]
+    DeduplicationError(LLMError),
+    #[error("Analysis merge failed: {0}")]
+    AnalysisMergeError(#[from] AggregationPassError),
+    #[error("Follow-up query generation failed: {0}")]
+    FollowUpQueriesError(LLMError),
+    #[error("Query spell check failed: {0}")]
+    QueryCorrectionError(LLMError),
     #[error("Result format failed: {0}")]
     ResultFormatError(#[from] ResultFormatError),
+    #[error("Result format task failed: {0}")]
+    ResultFormatJoinError(#[from] JoinError),
+    #[error("No result format response was produced")]
+    MissingResponse,
+    #[error("{0}")]
+    InvalidUserAgent(WebpageParseError),
+    #[error("Identical in-flight search failed: {0}")]
+    InFlightSearchError(String),
 }
 
 pub async fn agent_search_with_query(
     search_input: &AgentSearchInput,
-    searx_host: &str,
-    searx_port: &str,
+    state: &Arc<ServerState>,
 ) -> Result<PreFormattedAgentSearchResult, AgentSingleSearchError> {
+    let search_input = &AgentSearchInput {
+        whitelisted_base_urls: merge_domain_lists(
+            search_input.whitelisted_base_urls.as_ref(),
+            &state.domain_lists.allowlist(),
+        ),
+        blacklisted_base_urls: merge_domain_lists(
+            search_input.blacklisted_base_urls.as_ref(),
+            &state.domain_lists.blocklist(),
+        ),
+        ..search_input.clone()
+    };
     let search_strategy = search_input.search_strategy.clone().unwrap_or_default();
+    record_query_execution(
+        &search_input.query,
+        search_strategy.clone(),
+        run_search_strategy(search_input, &search_strategy, state),
+    )
+    .await
+}
+
+async fn run_search_strategy(
+    search_input: &AgentSearchInput,
+    search_strategy: &AgentSearchStrategy,
+    state: &Arc<ServerState>,
+) -> Result<PreFormattedAgentSearchResult, AgentSingleSearchError> {
     match search_strategy {
-        AgentSearchStrategy::Human => human_agent_search(&search_input, searx_host, searx_port)
+        AgentSearchStrategy::Human => human_agent_search(search_input, state)
             .await
             .map_err(AgentSingleSearchError::HumanAgentSearchError),
-        AgentSearchStrategy::Parallel => {
-            parallel_agent_search(&search_input, searx_host, searx_port)
-                .await
-                .map_err(AgentSingleSearchError::ParallelAgentSearchError)
-        }
-        AgentSearchStrategy::Sequential => {
-            sequential_agent_search(&search_input, searx_host, searx_port)
+        AgentSearchStrategy::Parallel => parallel_agent_search(search_input, state)
+            .await
+            .map_err(AgentSingleSearchError::ParallelAgentSearchError),
+        AgentSearchStrategy::Sequential => sequential_agent_search(search_input, state)
+            .await
+            .map_err(AgentSingleSearchError::SequentialAgentSearchError),
+        AgentSearchStrategy::ParallelTree => parallel_tree_agent_search(search_input, state)
+            .await
+            .map_err(AgentSingleSearchError::ParallelTreeAgentSearchError),
+        AgentSearchStrategy::AdaptiveParallel => {
+            adaptive_parallel_agent_search(search_input, state)
                 .await
-                .map_err(AgentSingleSearchError::SequentialAgentSearchError)
+                .map_err(AgentSingleSearchError::AdaptiveParallelAgentSearchError)
         }
-        AgentSearchStrategy::ParallelTree => {
-            parallel_tree_agent_search(&search_input, searx_host, searx_port)
+        AgentSearchStrategy::MultiQueryParallelTree => {
+            multi_query_parallel_tree_agent_search(search_input, state)
                 .await
-                .map_err(AgentSingleSearchError::ParallelTreeAgentSearchError)
+                .map_err(AgentSingleSearchError::MultiQueryParallelTreeAgentSearchError)
         }
+        AgentSearchStrategy::BestFirst => best_first_agent_search(search_input, state)
+            .await
+            .map_err(AgentSingleSearchError::BestFirstAgentSearchError),
     }
 }
 
 pub async fn agent_search(
     search_input: &AgentSearchInput,
-    searx_host: &str,
-    searx_port: &str,
+    state: &Arc<ServerState>,
 ) -> Result<AgentSearchResult, AgentSearchError> {
+    let result = agent_search_multi_format(search_input, state).await?;
+    let response = match result.responses.into_iter().next() {
+        Some(response) => response,
+        None => return Err(AgentSearchError::MissingResponse),
+    };
+    Ok(AgentSearchResult {
+        raw_analysis: result.raw_analysis,
+        queries_executed: result.queries_executed,
+        response,
+        auto_selected_strategy: result.auto_selected_strategy,
+        query_translation: result.query_translation,
+        usage: result.usage,
+        rate_limit_delays_ms: result.rate_limit_delays_ms,
+        truncated_due_to_llm_budget: result.truncated_due_to_llm_budget,
+        suggested_follow_up_queries: result.suggested_follow_up_queries,
+        query_was_corrected: result.query_was_corrected,
+        original_query: result.original_query,
+        served_from_cache: false,
+    })
+}
+
+pub async fn agent_search_from_pages(
+    search_input: &AgentSearchInput,
+    pages: Vec<SearchResult>,
+    state: &Arc<ServerState>,
+) -> Result<AgentSearchResult, AgentSearchError> {
+    let session = current_search_session()
+        .unwrap_or_default()
+        .with_seeded_results(pages);
+    with_search_session(session, agent_search(search_input, state)).await
+}
+
+pub async fn agent_search_multi_format(
+    search_input: &AgentSearchInput,
+    state: &Arc<ServerState>,
+) -> Result<AgentSearchMultiFormatResult, AgentSearchError> {
+    let usage_tracker = UsageTracker::with_max_llm_calls(search_input.max_llm_calls);
+    let result = with_stage_temperatures(
+        search_input.temperatures.clone().unwrap_or_default(),
+        track_usage(
+            usage_tracker.clone(),
+            agent_search_untracked(search_input, state),
+        ),
+    )
+    .await?;
+    Ok(AgentSearchMultiFormatResult {
+        usage: usage_tracker.summary(),
+        rate_limit_delays_ms: usage_tracker.rate_limit_delays_ms(),
+        truncated_due_to_llm_budget: usage_tracker.truncated_due_to_llm_budget(),
+        ..result
+    })
+}
+
+async fn format_results_in_parallel(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+    result_formats: Vec<ResultFormat>,
+    custom_format_description: Option<&str>,
+    qa_pair_count: Option<usize>,
+    post_processors: &[PostProcessor],
+) -> Result<Vec<ResultFormatResponse>, AgentSearchError> {
+    let tasks = result_formats.into_iter().map(|result_format| {
+        let query = query.to_string();
+        let analysis_document = analysis_document.clone();
+        let custom_format_description = custom_format_description.map(|d| d.to_string());
+        let post_processors = post_processors.to_vec();
+        tokio::spawn(with_current_search_context(async move {
+            let response = format_result(
+                &query,
+                &analysis_document,
+                &result_format,
+                custom_format_description.as_deref(),
+                qa_pair_count,
+            )
+            .await?;
+            apply_post_processors(response, &post_processors).await
+        }))
+    });
+    let join_results = futures::future::join_all(tasks).await;
+    let mut responses = Vec::new();
+    for join_result in join_results {
+        match join_result {
+            Ok(Ok(response)) => responses.push(response),
+            Ok(Err(e)) => return Err(AgentSearchError::ResultFormatError(e)),
+            Err(e) => return Err(AgentSearchError::ResultFormatJoinError(e)),
+        }
+    }
+    Ok(responses)
+}
+
+async fn agent_search_untracked(
+    search_input: &AgentSearchInput,
+    state: &Arc<ServerState>,
+) -> Result<AgentSearchMultiFormatResult, AgentSearchError> {
+    if let Some(user_agent) = &search_input.user_agent {
+        if let Err(e) = validate_user_agent(user_agent) {
+            return Err(AgentSearchError::InvalidUserAgent(e));
+        }
+    }
+    let query_correction = if search_input.spell_check_query.unwrap_or(false) {
+        match correct_query_spelling(&search_input.query).await {
+            Ok(correction) if correction.was_changed => Some(correction),
+            Ok(_) => None,
+            Err(e) => return Err(AgentSearchError::QueryCorrectionError(e)),
+        }
+    } else {
+        None
+    };
+    let corrected_input;
+    let search_input = match &query_correction {
+        Some(correction) => {
+            corrected_input = AgentSearchInput {
+                query: correction.corrected.clone(),
+                ..search_input.clone()
+            };
+            &corrected_input
+        }
+        None => search_input,
+    };
+    let query_was_corrected = query_correction.is_some();
+    let original_query = query_correction.map(|correction| correction.original);
+    let query_translation = if search_input
+        .auto_translate_query_to_english
+        .unwrap_or(false)
+    {
+        match translate_to_english(&search_input.query).await {
+            Ok(translation) => Some(translation),
+            Err(e) => return Err(AgentSearchError::QuerySynthesisError(e)),
+        }
+    } else {
+        None
+    };
+    let translated_input;
+    let search_input = match &query_translation {
+        Some(translation) => {
+            translated_input = AgentSearchInput {
+                query: translation.translated.clone(),
+                ..search_input.clone()
+            };
+            &translated_input
+        }
+        None => search_input,
+    };
     let query_strategy = search_input.query_strategy.clone().unwrap_or_default();
     let search_strategy = search_input.search_strategy.clone().unwrap_or_default();
-    let synthesized_queries = synthesize_queries(&search_input.query, &query_strategy)
-        .await
-        .map_err(|e| AgentSearchError::QuerySynthesisError(e))?;
+    let result_formats = search_input.requested_result_formats();
+    let is_comparison = result_formats
+        .iter()
+        .any(|result_format| matches!(result_format, ResultFormat::ProsConsComparison));
+    let (query_strategy, synthesized_queries) = if is_comparison {
+        (
+            QueryStrategy::Parallel,
+            synthesize_comparison_queries(&search_input.query).await,
+        )
+    } else {
+        (
+            query_strategy.clone(),
+            synthesize_queries(&search_input.query, &query_strategy).await,
+        )
+    };
+    let synthesized_queries =
+        synthesized_queries.map_err(|e| AgentSearchError::QuerySynthesisError(e))?;
+    let auto_selected_strategy = synthesized_queries.strategy_selection.clone();
+    let (query_strategy, search_strategy) = match &auto_selected_strategy {
+        Some(selection) => (
+            selection.query_strategy.clone(),
+            selection.search_strategy.clone(),
+        ),
+        None => (
+            synthesized_queries
+                .resolved_query_strategy
+                .clone()
+                .unwrap_or(query_strategy),
+            search_strategy,
+        ),
+    };
+    if search_input.dry_run.unwrap_or(false) {
+        return Ok(AgentSearchMultiFormatResult {
+            raw_analysis: AnalysisDocument {
+                content: String::new(),
+                visited_results: Vec::new(),
+                unvisited_results: Vec::new(),
+                skipped_results: Vec::new(),
+            },
+            queries_executed: Vec::new(),
+            responses: vec![ResultFormatResponse::DryRun(DryRunResult {
+                queries: synthesized_queries.queries,
+                strategy: search_strategy,
+                reasoning: synthesized_queries.reasoning,
+            })],
+            auto_selected_strategy,
+            query_translation,
+            usage: UsageSummary::default(),
+            rate_limit_delays_ms: 0,
+            truncated_due_to_llm_budget: false,
+            suggested_follow_up_queries: None,
+            query_was_corrected,
+            original_query,
+        });
+    }
     let current_search_result: Option<SearchResult> = search_input.current_search_result.clone();
-    let pre_formatted_result: PreFormattedAgentSearchResult = match query_strategy {
-        QueryStrategy::Verbatim | QueryStrategy::Single => {
+    let merge_strategy = search_input.merge_strategy.clone().unwrap_or_default();
+    let mut pre_formatted_result: PreFormattedAgentSearchResult = match query_strategy {
+        QueryStrategy::Verbatim
+        | QueryStrategy::Single
+        | QueryStrategy::Auto
+        | QueryStrategy::Adaptive => {
             let query = synthesized_queries.queries.first().unwrap();
             let modified_input = AgentSearchInput {
                 query: query.clone(),
@@ -205,12 +854,47 @@ pub async fn agent_search(
                     .clone(),
                 whitelisted_base_urls: search_input.whitelisted_base_urls.clone(),
                 blacklisted_base_urls: search_input.blacklisted_base_urls.clone(),
+                fallback_to_snippet: search_input.fallback_to_snippet,
+                adaptive_batch_size: search_input.adaptive_batch_size,
+                dry_run: None,
+                safe_search: search_input.safe_search,
+                max_parallel_page_visits: search_input.max_parallel_page_visits,
+                html_strip_tags: search_input.html_strip_tags.clone(),
+                html_keep_attributes: search_input.html_keep_attributes.clone(),
+                result_formats: search_input.result_formats.clone(),
+                auto_translate_query_to_english: None,
+                follow_links_depth: search_input.follow_links_depth,
+                max_page_content_chars: search_input.max_page_content_chars,
+                enable_multi_hop: search_input.enable_multi_hop,
+                temperatures: search_input.temperatures.clone(),
+                max_llm_calls: search_input.max_llm_calls,
+                resume_from_checkpoint: search_input.resume_from_checkpoint.clone(),
+                rerank_results: search_input.rerank_results,
+                suggest_follow_up_queries: None,
+                skip_bot_blocked_urls: search_input.skip_bot_blocked_urls,
+                post_processors: search_input.post_processors.clone(),
+                merge_strategy: search_input.merge_strategy.clone(),
+                max_dependency_tree_levels: search_input.max_dependency_tree_levels,
+                sort_by_source_credibility: search_input.sort_by_source_credibility,
+                source_credibility_category: search_input.source_credibility_category.clone(),
+                spell_check_query: None,
+                redact_pii: None,
+                language: search_input.language.clone(),
+                qa_pair_count: search_input.qa_pair_count,
+                dynamic_parallel: search_input.dynamic_parallel,
+                max_extra_pages: search_input.max_extra_pages,
+                inter_level_parallelism: search_input.inter_level_parallelism,
+                max_analysis_growth_ratio: search_input.max_analysis_growth_ratio,
+                vision_enabled: search_input.vision_enabled,
+                user_agent: search_input.user_agent.clone(),
+                max_content_age_days: search_input.max_content_age_days,
+                whitelisted_url_patterns: search_input.whitelisted_url_patterns.clone(),
+                min_page_content_chars: search_input.min_page_content_chars,
+            };
+            let pre_formatted_result = match agent_search_with_query(&modified_input, state).await {
+                Ok(result) => result,
+                Err(e) => return Err(AgentSearchError::SingleSearchError(e)),
             };
-            let pre_formatted_result =
-                match agent_search_with_query(&modified_input, searx_host, searx_port).await {
-                    Ok(result) => result,
-                    Err(e) => return Err(AgentSearchError::SingleSearchError(e)),
-                };
             pre_formatted_result
         }
         QueryStrategy::Sequential => {
@@ -218,10 +902,12 @@ pub async fn agent_search(
                 content: String::new(),
                 visited_results: Vec::new(),
                 unvisited_results: Vec::new(),
+                skipped_results: Vec::new(),
             };
             let mut queries_executed = Vec::new();
+            let checkpoint_search_id = search_input.sub_query_checkpoint_search_id();
 
-            for query in synthesized_queries.queries {
+            for (index, query) in synthesized_queries.queries.into_iter().enumerate() {
                 let modified_input = AgentSearchInput {
                     query: query.clone(),
                     current_search_result: current_search_result.clone(),
@@ -234,33 +920,61 @@ pub async fn agent_search(
                         .clone(),
                     whitelisted_base_urls: search_input.whitelisted_base_urls.clone(),
                     blacklisted_base_urls: search_input.blacklisted_base_urls.clone(),
+                    fallback_to_snippet: search_input.fallback_to_snippet,
+                    adaptive_batch_size: search_input.adaptive_batch_size,
+                    dry_run: None,
+                    safe_search: search_input.safe_search,
+                    max_parallel_page_visits: search_input.max_parallel_page_visits,
+                    html_strip_tags: search_input.html_strip_tags.clone(),
+                    html_keep_attributes: search_input.html_keep_attributes.clone(),
+                    result_formats: search_input.result_formats.clone(),
+                    auto_translate_query_to_english: None,
+                    follow_links_depth: search_input.follow_links_depth,
+                    max_page_content_chars: search_input.max_page_content_chars,
+                    enable_multi_hop: search_input.enable_multi_hop,
+                    temperatures: search_input.temperatures.clone(),
+                    max_llm_calls: search_input.max_llm_calls,
+                    resume_from_checkpoint: checkpoint_search_id
+                        .as_deref()
+                        .map(|search_id| sub_search_id(search_id, index)),
+                    rerank_results: search_input.rerank_results,
+                    suggest_follow_up_queries: None,
+                    skip_bot_blocked_urls: search_input.skip_bot_blocked_urls,
+                    post_processors: search_input.post_processors.clone(),
+                    merge_strategy: search_input.merge_strategy.clone(),
+                    max_dependency_tree_levels: search_input.max_dependency_tree_levels,
+                    sort_by_source_credibility: search_input.sort_by_source_credibility,
+                    source_credibility_category: search_input.source_credibility_category.clone(),
+                    spell_check_query: None,
+                    redact_pii: None,
+                    language: search_input.language.clone(),
+                    qa_pair_count: search_input.qa_pair_count,
+                    dynamic_parallel: search_input.dynamic_parallel,
+                    max_extra_pages: search_input.max_extra_pages,
+                    inter_level_parallelism: search_input.inter_level_parallelism,
+                    max_analysis_growth_ratio: search_input.max_analysis_growth_ratio,
+                    vision_enabled: search_input.vision_enabled,
+                    user_agent: search_input.user_agent.clone(),
+                    max_content_age_days: search_input.max_content_age_days,
+                    whitelisted_url_patterns: search_input.whitelisted_url_patterns.clone(),
+                    min_page_content_chars: search_input.min_page_content_chars,
+                };
+                let iter_result = match agent_search_with_query(&modified_input, state).await {
+                    Ok(result) => result,
+                    Err(e) => return Err(AgentSearchError::SingleSearchError(e)),
                 };
-                let iter_result =
-                    match agent_search_with_query(&modified_input, searx_host, searx_port).await {
-                        Ok(result) => result,
-                        Err(e) => return Err(AgentSearchError::SingleSearchError(e)),
-                    };
 
-                if cur_analysis.content.is_empty() {
-                    cur_analysis = iter_result.raw_analysis;
-                } else {
-                    cur_analysis = AnalysisDocument {
-                        content: format!(
-                            "{}\n\n{}",
-                            cur_analysis.content, iter_result.raw_analysis.content
-                        ),
-                        visited_results: cur_analysis
-                            .visited_results
-                            .into_iter()
-                            .chain(iter_result.raw_analysis.visited_results.into_iter())
-                            .collect(),
-                        unvisited_results: cur_analysis
-                            .unvisited_results
-                            .into_iter()
-                            .chain(iter_result.raw_analysis.unvisited_results.into_iter())
-                            .collect(),
-                    };
-                }
+                cur_analysis = match merge_analysis_documents(
+                    &search_input.query,
+                    cur_analysis,
+                    iter_result.raw_analysis,
+                    &merge_strategy,
+                )
+                .await
+                {
+                    Ok(analysis) => analysis,
+                    Err(e) => return Err(AgentSearchError::AnalysisMergeError(e)),
+                };
                 queries_executed.extend(iter_result.queries_executed);
             }
             PreFormattedAgentSearchResult {
@@ -269,19 +983,53 @@ pub async fn agent_search(
             }
         }
         QueryStrategy::Parallel => {
-            let tasks = synthesized_queries.queries.iter().map(|query| {
+            let checkpoint_search_id = search_input.sub_query_checkpoint_search_id();
+            let queries = synthesized_queries.queries.iter().enumerate();
+            let tasks = queries.map(|(index, query)| {
                 let query = query.clone();
                 let current_search_result = current_search_result.clone();
                 let search_strategy = search_strategy.clone();
                 let max_results_to_visit = search_input.max_results_to_visit;
                 let result_format = search_input.result_format.clone();
-                let searx_host = searx_host.to_string();
-                let searx_port = searx_port.to_string();
+                let state = state.clone();
                 let custom_result_format_description =
                     search_input.custom_result_format_description.clone();
                 let whitelisted_base_urls = search_input.whitelisted_base_urls.clone();
                 let blacklisted_base_urls = search_input.blacklisted_base_urls.clone();
-                tokio::spawn(async move {
+                let fallback_to_snippet = search_input.fallback_to_snippet;
+                let adaptive_batch_size = search_input.adaptive_batch_size;
+                let safe_search = search_input.safe_search;
+                let max_parallel_page_visits = search_input.max_parallel_page_visits;
+                let html_strip_tags = search_input.html_strip_tags.clone();
+                let html_keep_attributes = search_input.html_keep_attributes.clone();
+                let result_formats = search_input.result_formats.clone();
+                let follow_links_depth = search_input.follow_links_depth;
+                let max_page_content_chars = search_input.max_page_content_chars;
+                let enable_multi_hop = search_input.enable_multi_hop;
+                let temperatures = search_input.temperatures.clone();
+                let max_llm_calls = search_input.max_llm_calls;
+                let resume_from_checkpoint = checkpoint_search_id
+                    .as_deref()
+                    .map(|search_id| sub_search_id(search_id, index));
+                let rerank_results = search_input.rerank_results;
+                let skip_bot_blocked_urls = search_input.skip_bot_blocked_urls;
+                let post_processors = search_input.post_processors.clone();
+                let merge_strategy = search_input.merge_strategy.clone();
+                let max_dependency_tree_levels = search_input.max_dependency_tree_levels;
+                let sort_by_source_credibility = search_input.sort_by_source_credibility;
+                let source_credibility_category = search_input.source_credibility_category.clone();
+                let language = search_input.language.clone();
+                let qa_pair_count = search_input.qa_pair_count;
+                let dynamic_parallel = search_input.dynamic_parallel;
+                let max_extra_pages = search_input.max_extra_pages;
+                let inter_level_parallelism = search_input.inter_level_parallelism;
+                let max_analysis_growth_ratio = search_input.max_analysis_growth_ratio;
+                let vision_enabled = search_input.vision_enabled;
+                let user_agent = search_input.user_agent.clone();
+                let max_content_age_days = search_input.max_content_age_days;
+                let whitelisted_url_patterns = search_input.whitelisted_url_patterns.clone();
+                let min_page_content_chars = search_input.min_page_content_chars;
+                tokio::spawn(with_current_search_context(async move {
                     let modified_input = AgentSearchInput {
                         query,
                         current_search_result,
@@ -292,9 +1040,45 @@ pub async fn agent_search(
                         custom_result_format_description,
                         whitelisted_base_urls,
                         blacklisted_base_urls,
+                        fallback_to_snippet,
+                        adaptive_batch_size,
+                        dry_run: None,
+                        safe_search,
+                        max_parallel_page_visits,
+                        html_strip_tags,
+                        html_keep_attributes,
+                        result_formats,
+                        auto_translate_query_to_english: None,
+                        follow_links_depth,
+                        max_page_content_chars,
+                        enable_multi_hop,
+                        temperatures,
+                        max_llm_calls,
+                        resume_from_checkpoint,
+                        rerank_results,
+                        suggest_follow_up_queries: None,
+                        skip_bot_blocked_urls,
+                        post_processors,
+                        merge_strategy,
+                        max_dependency_tree_levels,
+                        sort_by_source_credibility,
+                        source_credibility_category,
+                        spell_check_query: None,
+                        redact_pii: None,
+                        language,
+                        qa_pair_count,
+                        dynamic_parallel,
+                        max_extra_pages,
+                        inter_level_parallelism,
+                        max_analysis_growth_ratio,
+                        vision_enabled,
+                        user_agent,
+                        max_content_age_days,
+                        whitelisted_url_patterns,
+                        min_page_content_chars,
                     };
-                    agent_search_with_query(&modified_input, &searx_host, &searx_port).await
-                })
+                    agent_search_with_query(&modified_input, &state).await
+                }))
             });
             let join_results = futures::future::join_all(tasks).await;
             let mut results = Vec::new();
@@ -317,80 +1101,458 @@ pub async fn agent_search(
                 content: String::new(),
                 visited_results: Vec::new(),
                 unvisited_results: Vec::new(),
+                skipped_results: Vec::new(),
             };
             let mut queries_executed = Vec::new();
             for res in results {
-                if cur_analysis.content.is_empty() {
-                    cur_analysis = res.raw_analysis;
-                } else {
-                    cur_analysis = AnalysisDocument {
-                        content: format!(
-                            "{}\n\n{}",
-                            cur_analysis.content, res.raw_analysis.content
-                        ),
-                        visited_results: cur_analysis
-                            .visited_results
-                            .clone()
-                            .into_iter()
-                            .chain(res.raw_analysis.visited_results.clone().into_iter())
-                            .collect(),
-                        unvisited_results: cur_analysis
-                            .unvisited_results
-                            .clone()
-                            .into_iter()
-                            .chain(res.raw_analysis.unvisited_results.clone().into_iter())
-                            .collect(),
-                    };
-                }
+                cur_analysis = match merge_analysis_documents(
+                    &search_input.query,
+                    cur_analysis,
+                    res.raw_analysis,
+                    &merge_strategy,
+                )
+                .await
+                {
+                    Ok(analysis) => analysis,
+                    Err(e) => return Err(AgentSearchError::AnalysisMergeError(e)),
+                };
                 queries_executed.extend(res.queries_executed);
             }
+            queries_executed.sort_by_key(|record| record.started_at);
+            if queries_executed.len() > 1 && matches!(merge_strategy, AnalysisMergeStrategy::Append)
+            {
+                cur_analysis.content = match deduplicate_analysis(&cur_analysis.content).await {
+                    Ok(content) => content,
+                    Err(e) => return Err(AgentSearchError::DeduplicationError(e)),
+                };
+            }
             PreFormattedAgentSearchResult {
                 raw_analysis: cur_analysis,
                 queries_executed,
             }
         }
     };
-    let result_format = search_input.result_format.clone().unwrap_or_default();
-    let response = match format_result(
+    let redact = search_input.redact_pii.unwrap_or(false);
+    if redact {
+        redact_analysis_document(&mut pre_formatted_result.raw_analysis);
+    }
+    let responses = format_results_in_parallel(
         &search_input.query,
         &pre_formatted_result.raw_analysis,
-        &result_format,
+        result_formats,
         search_input.custom_result_format_description.as_deref(),
+        search_input.qa_pair_count,
+        search_input.post_processors.as_deref().unwrap_or_default(),
     )
-    .await
-    {
-        Ok(response) => response,
-        Err(e) => return Err(AgentSearchError::ResultFormatError(e)),
+    .await?;
+    let responses = if redact {
+        responses
+            .into_iter()
+            .map(redact_result_format_response)
+            .collect()
+    } else {
+        responses
     };
-    Ok(AgentSearchResult {
+    let suggested_follow_up_queries = if search_input.suggest_follow_up_queries.unwrap_or(false) {
+        match generate_follow_up_queries(&search_input.query, &pre_formatted_result.raw_analysis)
+            .await
+        {
+            Ok(queries) => Some(queries),
+            Err(e) => return Err(AgentSearchError::FollowUpQueriesError(e)),
+        }
+    } else {
+        None
+    };
+    Ok(AgentSearchMultiFormatResult {
         raw_analysis: pre_formatted_result.raw_analysis,
         queries_executed: pre_formatted_result.queries_executed,
-        response,
+        responses,
+        auto_selected_strategy,
+        query_translation,
+        usage: UsageSummary::default(),
+        rate_limit_delays_ms: 0,
+        truncated_due_to_llm_budget: false,
+        suggested_follow_up_queries,
+        query_was_corrected,
+        original_query,
     })
 }
 
+async fn merge_analysis_documents(
+    query: &str,
+    cur_analysis: AnalysisDocument,
+    new_analysis: AnalysisDocument,
+    merge_strategy: &AnalysisMergeStrategy,
+) -> Result<AnalysisDocument, AggregationPassError> {
+    if cur_analysis.content.is_empty() {
+        return Ok(new_analysis);
+    }
+    let content = match merge_strategy {
+        AnalysisMergeStrategy::Append => {
+            format!("{}\n\n{}", cur_analysis.content, new_analysis.content)
+        }
+        AnalysisMergeStrategy::Replace => return Ok(new_analysis),
+        AnalysisMergeStrategy::Summarize => {
+            let extraction_results = vec![
+                ExtractionResult {
+                    search_result: SearchResult {
+                        title: "Previous analysis".to_string(),
+                        url: String::new(),
+                        content: String::new(),
+                        published_at: None,
+                        modified_at: None,
+                        link_depth: None,
+                        infobox: None,
+                        anchor_text: None,
+                        score: None,
+                    },
+                    content: cur_analysis.content,
+                },
+                ExtractionResult {
+                    search_result: SearchResult {
+                        title: "New analysis".to_string(),
+                        url: String::new(),
+                        content: String::new(),
+                        published_at: None,
+                        modified_at: None,
+                        link_depth: None,
+                        infobox: None,
+                        anchor_text: None,
+                        score: None,
+                    },
+                    content: new_analysis.content,
+                },
+            ];
+            match aggregate_results(query, extraction_results).await {
+                Ok(content) => content,
+                Err(e) => return Err(e),
+            }
+        }
+    };
+    Ok(AnalysisDocument {
+        content,
+        visited_results: dedup_search_results(
+            cur_analysis
+                .visited_results
+                .into_iter()
+                .chain(new_analysis.visited_results)
+                .collect(),
+        ),
+        unvisited_results: dedup_search_results(
+            cur_analysis
+                .unvisited_results
+                .into_iter()
+                .chain(new_analysis.unvisited_results)
+                .collect(),
+        ),
+        skipped_results: cur_analysis
+            .skipped_results
+            .into_iter()
+            .chain(new_analysis.skipped_results)
+            .collect(),
+    })
+}
+
+pub async fn deduplicate_analysis(content: &str) -> Result<String, LLMError> {
+    let prompt = Prompt::new(
+        DEDUP_ANALYSIS_SYSTEM_PROMPT.to_string(),
+        format!("# Findings document:\n{}", content),
+    );
+    default_completion(&prompt, LLMCallStage::Deduplication).await
+}
+
+const RERANK_CANDIDATES_MULTIPLIER: usize = 2;
+
+const LOCAL_INDEX_TOP_K: usize = 3;
+const MIN_LOCAL_INDEX_SCORE: f64 = 0.2;
+const LOCAL_INDEX_SNIPPET_CHARS: usize = 300;
+
+fn search_local_page_index(
+    search_input: &AgentSearchInput,
+    state: &ServerState,
+) -> Vec<SearchResult> {
+    let page_index = state.page_index.read().unwrap();
+    page_index
+        .query(&search_input.query, LOCAL_INDEX_TOP_K)
+        .into_iter()
+        .filter(|(_, score)| *score >= MIN_LOCAL_INDEX_SCORE)
+        .filter(|(url, _)| {
+            is_url_allowed(
+                url,
+                search_input.whitelisted_base_urls.as_ref(),
+                search_input.blacklisted_base_urls.as_ref(),
+            )
+        })
+        .filter_map(|(url, _)| {
+            let content = page_index.get(&url)?;
+            Some(SearchResult {
+                title: url.clone(),
+                content: truncate_at_sentence(content, LOCAL_INDEX_SNIPPET_CHARS).to_string(),
+                url,
+                published_at: None,
+                modified_at: None,
+                link_depth: None,
+                infobox: None,
+                anchor_text: None,
+                score: None,
+            })
+        })
+        .collect()
+}
+
+fn prepend_local_results(
+    local_results: Vec<SearchResult>,
+    search_results: Vec<SearchResult>,
+) -> Vec<SearchResult> {
+    if local_results.is_empty() {
+        return search_results;
+    }
+    let local_urls = local_results
+        .iter()
+        .map(|result| normalize_url(&result.url))
+        .collect::<HashSet<_>>();
+    local_results
+        .into_iter()
+        .chain(
+            search_results
+                .into_iter()
+                .filter(|result| !local_urls.contains(&normalize_url(&result.url))),
+        )
+        .collect()
+}
+
+pub async fn search_and_rerank(
+    search_input: &AgentSearchInput,
+    state: &ServerState,
+) -> Result<Vec<SearchResult>, SearchError> {
+    if let Some(seeded_results) = seeded_search_results() {
+        return Ok(seeded_results);
+    }
+    let local_results = search_local_page_index(search_input, state);
+    let rerank_results = search_input.rerank_results.unwrap_or(false);
+    let max_results = search_input
+        .max_results_to_visit
+        .unwrap_or(MAX_RESULTS_TO_VISIT);
+    let search_results = search(
+        &searx_search_input(search_input),
+        &state.searx.client,
+        &state.searx,
+    )
+    .await?;
+    if !rerank_results {
+        return Ok(prepend_local_results(local_results, search_results));
+    }
+    let search_results =
+        match rerank_search_results(&search_input.query, search_results.clone(), max_results).await
+        {
+            Ok(reranked_results) => reranked_results,
+            Err(e) => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Failed to rerank search results, keeping search engine order: {}",
+                    e
+                );
+                search_results.into_iter().take(max_results).collect()
+            }
+        };
+    Ok(prepend_local_results(local_results, search_results))
+}
+
+pub async fn search_extra_page(
+    search_input: &AgentSearchInput,
+    state: &ServerState,
+    extra_page: usize,
+) -> Result<Vec<SearchResult>, SearchError> {
+    if seeded_search_results().is_some() {
+        return Ok(Vec::new());
+    }
+    let searx_input = searx_search_input(search_input);
+    let max_results = searx_input
+        .max_results_to_visit
+        .unwrap_or(MAX_RESULTS_TO_VISIT);
+    let searched_pages = state.searx.num_pages(max_results);
+    search_page(
+        &searx_input,
+        &state.searx.client,
+        &state.searx,
+        searched_pages + extra_page,
+    )
+    .await
+}
+
+fn searx_search_input(search_input: &AgentSearchInput) -> search::SearchInput {
+    let max_results = search_input
+        .max_results_to_visit
+        .unwrap_or(MAX_RESULTS_TO_VISIT);
+    search::SearchInput {
+        query: search_input.build_google_search_query(),
+        max_results_to_visit: if search_input.rerank_results.unwrap_or(false) {
+            Some(max_results * RERANK_CANDIDATES_MULTIPLIER)
+        } else {
+            search_input.max_results_to_visit
+        },
+        whitelisted_base_urls: search_input.whitelisted_base_urls.clone(),
+        blacklisted_base_urls: search_input.blacklisted_base_urls.clone(),
+        safe_search: search_input.safe_search,
+        spell_check_query: None,
+        language: search_input.language.clone(),
+        whitelisted_url_patterns: search_input.whitelisted_url_patterns.clone(),
+    }
+}
+
+pub const MAX_FOLLOW_LINKS_DEPTH: u32 = 2;
+const MAX_DISCOVERED_LINKS_PER_PAGE: usize = 10;
+
+fn discover_links(parsed_webpage: &ParsedWebpage, result: &SearchResult) -> Vec<SearchResult> {
+    if parsed_webpage.is_snippet {
+        return Vec::new();
+    }
+    let link_depth = result.link_depth.unwrap_or(0) + 1;
+    extract_links(&parsed_webpage.original_content, &result.url)
+        .into_iter()
+        .map(|link| SearchResult {
+            title: if link.text.is_empty() {
+                link.url.clone()
+            } else {
+                link.text
+            },
+            url: link.url,
+            content: format!("[Linked from {}]", result.url),
+            published_at: None,
+            modified_at: None,
+            link_depth: Some(link_depth),
+            infobox: None,
+            anchor_text: None,
+            score: None,
+        })
+        .collect()
+}
+
 async fn visit_and_extract_relevant_info(
     query: &str,
     current_analysis: &str,
-    result: &SearchResult,
-) -> Result<String, VisitAndExtractRelevantInfoError> {
-    let parsed_webpage = match visit_and_parse_webpage(&result.url).await {
+    result: &mut SearchResult,
+    fallback_to_snippet: bool,
+    follow_links: bool,
+    dom_parse_config: &DomParseConfig,
+    state: &ServerState,
+) -> Result<(String, Vec<SearchResult>), VisitAndExtractRelevantInfoError> {
+    let fetched_webpage = match &result.infobox {
+        Some(infobox) => Ok(ParsedWebpage {
+            original_content: infobox.clone(),
+            content: infobox.clone(),
+            is_snippet: true,
+            published_at: None,
+            modified_at: None,
+            image_urls: Vec::new(),
+        }),
+        None => match state.page_cache.get(&result.url, dom_parse_config) {
+            Some(parsed_webpage) => Ok(parsed_webpage),
+            None => {
+                let fetched_webpage = visit_and_parse_webpage(
+                    &result.url,
+                    &state.http_client.0,
+                    &state.user_agents,
+                    dom_parse_config.user_agent.as_deref(),
+                    &state.domain_rate_limiter,
+                    dom_parse_config,
+                )
+                .await;
+                if let Ok(parsed_webpage) = &fetched_webpage {
+                    state
+                        .page_index
+                        .write()
+                        .unwrap()
+                        .index(&result.url, &parsed_webpage.content);
+                    state
+                        .page_cache
+                        .insert(&result.url, dom_parse_config, parsed_webpage);
+                }
+                fetched_webpage
+            }
+        },
+    };
+    let parsed_webpage = match fetched_webpage {
         Ok(parsed_webpage) => parsed_webpage,
+        Err(e) if fallback_to_snippet && !matches!(e, WebpageParseError::InvalidUserAgent(_)) => {
+            tracing::warn!(
+                request_id = %current_request_id(),
+                "{}",
+                WebpageParseError::FetchSkipped(format!("{} ({})", result.url, e))
+            );
+            ParsedWebpage {
+                original_content: result.content.clone(),
+                content: result.content.clone(),
+                is_snippet: true,
+                published_at: None,
+                modified_at: None,
+                image_urls: Vec::new(),
+            }
+        }
         Err(e) => return Err(VisitAndExtractRelevantInfoError::WebpageParseError(e)),
     };
-    let user_prompt = format!(
+    if parsed_webpage.published_at.is_some() {
+        result.published_at = parsed_webpage.published_at.clone();
+    }
+    if parsed_webpage.modified_at.is_some() {
+        result.modified_at = parsed_webpage.modified_at.clone();
+    }
+    if let Some(max_content_age_days) = dom_parse_config.max_content_age_days {
+        let latest_date = latest_metadata_date(
+            parsed_webpage.published_at.as_deref(),
+            parsed_webpage.modified_at.as_deref(),
+        );
+        if let Some(date) = latest_date.filter(|date| is_stale_date(*date, max_content_age_days)) {
+            return Err(VisitAndExtractRelevantInfoError::StaleContent {
+                url: result.url.clone(),
+                date: date.to_string(),
+            });
+        }
+    }
+    if let Some(min_content_chars) = dom_parse_config.min_content_chars {
+        let content_chars = parsed_webpage.content.chars().count();
+        if !parsed_webpage.is_snippet && content_chars < min_content_chars {
+            return Err(VisitAndExtractRelevantInfoError::Skipped(SkippedResult {
+                url: result.url.clone(),
+                title: result.title.clone(),
+                reason: format!("content too short: {} chars", content_chars),
+            }));
+        }
+    }
+    let discovered_results = if follow_links {
+        discover_links(&parsed_webpage, result)
+    } else {
+        Vec::new()
+    };
+    let mut user_prompt = format!(
         "# Query:\n{}\n\n# Search result:\n## {} ({})\n\n{}\n\n# Current findings document:\n{}",
         query, result.title, result.url, parsed_webpage.content, current_analysis
     );
-    let prompt = Prompt::new(build_analyze_result_system_prompt(), user_prompt);
-    let completion = match default_completion(&prompt).await {
+    if dom_parse_config.vision_enabled && !parsed_webpage.image_urls.is_empty() {
+        user_prompt.push_str(&format!(
+            "\n\n# Images on the page:\n{}",
+            parsed_webpage
+                .image_urls
+                .iter()
+                .map(|image_url| format!("- {}", image_url))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ));
+    }
+    let prompt = Prompt::new(
+        build_analyze_result_system_prompt(parsed_webpage.is_snippet),
+        user_prompt,
+    );
+    let completion = match default_completion(&prompt, LLMCallStage::Extraction).await {
         Ok(completion) => completion,
         Err(e) => return Err(VisitAndExtractRelevantInfoError::LLMError(e)),
     };
     if completion.contains(&WEB_SEARCH_USE_SAME_WEB_SEARCH_FINDINGS_DOCUMENT) {
-        return Ok(current_analysis.to_string());
+        report_progress(&result.url, current_analysis);
+        return Ok((current_analysis.to_string(), discovered_results));
     }
-    Ok(completion)
+    report_progress(&result.url, &completion);
+    Ok((completion, discovered_results))
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -420,67 +1582,151 @@ async fn check_sufficient_information(
 ) -> Result<SufficientInformationCheck, SufficientInformationCheckError> {
     let user_prompt = format!("# Query:\n{}\n\n# Current analysis:\n{}\n\n# Visited results:\n{}\n\n# Unvisited results:\n{}", query, current_analysis, display_search_results_with_indices(visited_results), display_search_results_with_indices(unvisited_results));
     let prompt = Prompt::new(build_sufficient_information_check_prompt(), user_prompt);
-    let completion = match default_completion(&prompt).await {
+    let completion = match race_completion(&prompt, LLMCallStage::SufficiencyCheck).await {
         Ok(completion) => completion,
         Err(e) => return Err(SufficientInformationCheckError::LLMError(e)),
     };
-    let decision: SufficientInformationCheck = match parse_json_response(&completion) {
-        Ok(decision) => decision,
-        Err(e) => return Err(SufficientInformationCheckError::ParseError(e)),
-    };
+    let decision: SufficientInformationCheck =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(decision) => decision,
+            Err(e) => return Err(SufficientInformationCheckError::ParseError(e)),
+        };
     Ok(decision)
 }
 
+pub const DEFAULT_MAX_PARALLEL_PAGE_VISITS: usize = 5;
+pub const DEFAULT_MAX_PAGE_CONTENT_CHARS: usize = 8000;
+pub const DEFAULT_MIN_PAGE_CONTENT_CHARS: usize = 200;
+
 pub async fn parallel_visit_and_extract_relevant_info(
-    query: &str,
+    search_input: &AgentSearchInput,
     search_results: &[SearchResult],
     current_analysis: &str,
+    state: &Arc<ServerState>,
 ) -> Result<PreFormattedAgentSearchResult, ParallelAgentSearchError> {
-    let extraction_tasks = search_results
+    let query = search_input.query.as_str();
+    let fallback_to_snippet = search_input.fallback_to_snippet.unwrap_or(false);
+    let skip_bot_blocked_urls = search_input.skip_bot_blocked_urls();
+    let max_parallel_page_visits = search_input
+        .max_parallel_page_visits
+        .unwrap_or(DEFAULT_MAX_PARALLEL_PAGE_VISITS);
+    let dom_parse_config = search_input.dom_parse_config();
+    let search_results = search_results
         .iter()
-        .map(|result| {
+        .filter(|result| !is_url_skipped(&result.url))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut skipped_results = Vec::new();
+    let mut extraction_results = stream::iter(search_results.iter().cloned().enumerate())
+        .map(|(idx, mut result)| {
             let query = query.to_string();
             let current_analysis = current_analysis.to_string();
-            let result = result.clone();
-            task::spawn(async move {
-                visit_and_extract_relevant_info(query.as_str(), &current_analysis, &result).await
-            })
+            let follow_links = search_input.should_follow_links(&result);
+            let dom_parse_config = dom_parse_config.clone();
+            let state = state.clone();
+            let task = task::spawn(with_current_search_context(async move {
+                visit_and_extract_relevant_info(
+                    query.as_str(),
+                    &current_analysis,
+                    &mut result,
+                    fallback_to_snippet,
+                    follow_links,
+                    &dom_parse_config,
+                    &state,
+                )
+                .await
+                .map(|(content, discovered_results)| {
+                    (
+                        ExtractionResult {
+                            search_result: result,
+                            content,
+                        },
+                        discovered_results,
+                    )
+                })
+            }));
+            async move { task.await.map(|result| (idx, result)) }
         })
-        .collect::<Vec<_>>();
-    let extraction_results: Vec<ExtractionResult> = join_all(extraction_tasks)
+        .buffer_unordered(max_parallel_page_visits.max(1))
+        .collect::<Vec<_>>()
         .await
         .into_iter()
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .enumerate()
-        .map(|(index, result)| {
-            result
-                .map(|content| ExtractionResult {
-                    search_result: search_results[index].clone(),
-                    content,
-                })
-                .map_err(ParallelAgentSearchError::VisitAndExtractRelevantInfoError)
-        })
         .collect::<Result<Vec<_>, _>>()?;
+    extraction_results.sort_by_key(|(idx, _)| *idx);
+    let (extraction_results, discovered_results): (Vec<ExtractionResult>, Vec<_>) =
+        extraction_results
+            .into_iter()
+            .filter_map(|(_, result)| match result {
+                Err(e) if skip_bot_blocked_urls && e.is_bot_challenge() => {
+                    tracing::warn!(
+                        request_id = %current_request_id(),
+                        "Skipping bot-blocked result: {}",
+                        e
+                    );
+                    None
+                }
+                Err(VisitAndExtractRelevantInfoError::Skipped(skipped_result)) => {
+                    tracing::info!(
+                        request_id = %current_request_id(),
+                        "Skipping {}: {}",
+                        skipped_result.url,
+                        skipped_result.reason
+                    );
+                    skipped_results.push(skipped_result);
+                    None
+                }
+                result => {
+                    Some(result.map_err(ParallelAgentSearchError::VisitAndExtractRelevantInfoError))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+    let visited_results = extraction_results
+        .iter()
+        .map(|result| result.search_result.clone())
+        .collect::<Vec<_>>();
+    let mut unvisited_results: Vec<SearchResult> = Vec::new();
+    for page_discovered_results in discovered_results {
+        let page_discovered_results = search_input.filter_discovered_results(
+            page_discovered_results,
+            search_results.iter().chain(unvisited_results.iter()),
+        );
+        unvisited_results.extend(page_discovered_results);
+    }
     let aggregated_result = match aggregate_results(query, extraction_results).await {
         Ok(result) => PreFormattedAgentSearchResult {
             raw_analysis: AnalysisDocument {
                 content: result,
-                visited_results: search_results.to_vec(),
-                unvisited_results: Vec::new(),
+                visited_results,
+                unvisited_results,
+                skipped_results,
             },
-            queries_executed: vec![query.to_string()],
+            queries_executed: Vec::new(),
         },
         Err(e) => return Err(ParallelAgentSearchError::AggregationPassError(e)),
     };
     Ok(aggregated_result)
 }
 
+pub const DEFAULT_AGGREGATE_BATCH_SIZE: usize = 5;
+
+pub fn aggregate_batch_size() -> usize {
+    std::env::var("AGGREGATE_BATCH_SIZE")
+        .ok()
+        .and_then(|batch_size| batch_size.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_AGGREGATE_BATCH_SIZE)
+        .max(2)
+}
+
 async fn aggregate_results(
     query: &str,
     extraction_results: Vec<ExtractionResult>,
 ) -> Result<String, AggregationPassError> {
-    let extraction_results_display = extraction_results
+    let batch_size = aggregate_batch_size();
+    let mut documents = extraction_results
         .iter()
         .map(|result| {
             format!(
@@ -488,8 +1734,28 @@ async fn aggregate_results(
                 result.search_result.title, result.search_result.url, result.content
             )
         })
-        .collect::<Vec<_>>()
-        .join("\n\n");
+        .collect::<Vec<_>>();
+    while documents.len() > batch_size {
+        let batch_summaries = documents.chunks(batch_size).map(|batch| async move {
+            match batch {
+                [document] => Ok(document.clone()),
+                _ => aggregate_documents(query, &batch.join("\n\n")).await,
+            }
+        });
+        documents = futures::future::try_join_all(batch_summaries)
+            .await?
+            .into_iter()
+            .enumerate()
+            .map(|(i, summary)| format!("## Partial summary {}\n\n{}", i + 1, summary))
+            .collect();
+    }
+    aggregate_documents(query, &documents.join("\n\n")).await
+}
+
+async fn aggregate_documents(
+    query: &str,
+    extraction_results_display: &str,
+) -> Result<String, AggregationPassError> {
     let user_prompt = format!(
         r#"# Search query
 {query}
@@ -501,7 +1767,7 @@ async fn aggregate_results(
         AGGREGATE_WEB_SEARCH_FINDINGS_PROMPT.to_string(),
         user_prompt,
     );
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::Aggregation).await {
         Ok(completion) => completion,
         Err(e) => return Err(AggregationPassError(e)),
     };
@@ -0,0 +1,104 @@
+use crate::agent_search::{
+    check_sufficient_information, parallel_visit_and_extract_relevant_info, search_and_rerank,
+    AgentSearchInput, AnalysisDocument, ParallelAgentSearchError, PreFormattedAgentSearchResult,
+    SufficientInformationCheckError,
+};
+use crate::search::SearchError;
+use crate::server::ServerState;
+use crate::session::search_cancelled;
+use crate::usage::stop_for_llm_budget;
+use std::sync::Arc;
+use thiserror::Error;
+
+pub const DEFAULT_ADAPTIVE_BATCH_SIZE: usize = 3;
+
+#[derive(Error, Debug)]
+pub enum AdaptiveParallelAgentSearchError {
+    #[error("Search failed: {0}")]
+    SearchError(#[from] SearchError),
+    #[error("Parallel agent search error: {0}")]
+    ParallelAgentSearchError(#[from] ParallelAgentSearchError),
+    #[error("Sufficient information check failed: {0}")]
+    SufficientInformationCheckError(#[from] SufficientInformationCheckError),
+}
+
+pub async fn adaptive_parallel_agent_search(
+    search_input: &AgentSearchInput,
+    state: &Arc<ServerState>,
+) -> Result<PreFormattedAgentSearchResult, AdaptiveParallelAgentSearchError> {
+    let search_results = match search_and_rerank(search_input, state).await {
+        Ok(results) => results,
+        Err(e) => return Err(AdaptiveParallelAgentSearchError::SearchError(e)),
+    };
+    let batch_size = search_input
+        .adaptive_batch_size
+        .unwrap_or(DEFAULT_ADAPTIVE_BATCH_SIZE)
+        .max(1);
+    let mut analysis = AnalysisDocument {
+        content: String::new(),
+        visited_results: Vec::new(),
+        unvisited_results: search_results,
+        skipped_results: Vec::new(),
+    };
+    while !analysis.unvisited_results.is_empty() {
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
+        let num_to_visit = batch_size.min(analysis.unvisited_results.len());
+        let batch: Vec<_> = analysis.unvisited_results.drain(..num_to_visit).collect();
+        let batch_result = match parallel_visit_and_extract_relevant_info(
+            search_input,
+            &batch,
+            &analysis.content,
+            state,
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                return Err(AdaptiveParallelAgentSearchError::ParallelAgentSearchError(
+                    e,
+                ))
+            }
+        };
+        analysis.content = batch_result.raw_analysis.content;
+        analysis
+            .visited_results
+            .extend(batch_result.raw_analysis.visited_results);
+        analysis
+            .skipped_results
+            .extend(batch_result.raw_analysis.skipped_results);
+        let discovered_results = search_input.filter_discovered_results(
+            batch_result.raw_analysis.unvisited_results,
+            analysis
+                .visited_results
+                .iter()
+                .chain(analysis.unvisited_results.iter()),
+        );
+        analysis.unvisited_results.extend(discovered_results);
+        if analysis.unvisited_results.is_empty() {
+            break;
+        }
+        match check_sufficient_information(
+            &search_input.query,
+            &analysis.content,
+            &analysis.visited_results,
+            &analysis.unvisited_results,
+        )
+        .await
+        {
+            Ok(decision) => {
+                if decision.sufficient {
+                    break;
+                }
+            }
+            Err(e) => {
+                return Err(AdaptiveParallelAgentSearchError::SufficientInformationCheckError(e))
+            }
+        }
+    }
+    Ok(PreFormattedAgentSearchResult {
+        raw_analysis: analysis,
+        queries_executed: Vec::new(),
+    })
+}
@@ -0,0 +1,141 @@
+use crate::agent_search::{
+    check_sufficient_information, search_and_rerank, visit_and_extract_relevant_info,
+    AgentSearchInput, PreFormattedAgentSearchResult, SufficientInformationCheckError,
+    VisitAndExtractRelevantInfoError,
+};
+use crate::checkpoint::CheckpointError;
+use crate::request_context::current_request_id;
+use crate::search::{SearchError, SearchResult, MAX_RESULTS_TO_VISIT};
+use crate::server::ServerState;
+use crate::session::{is_url_skipped, search_cancelled};
+use crate::usage::stop_for_llm_budget;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BestFirstAgentSearchError {
+    #[error("Search failed: {0}")]
+    SearchError(#[from] SearchError),
+    #[error("Visit and extract relevant info failed: {0}")]
+    VisitAndExtractRelevantInfoError(#[from] VisitAndExtractRelevantInfoError),
+    #[error("Sufficient information check failed: {0}")]
+    SufficientInformationCheckError(#[from] SufficientInformationCheckError),
+    #[error("Failed to resume from checkpoint: {0}")]
+    CheckpointError(#[from] CheckpointError),
+}
+
+fn sort_by_score(results: &mut [SearchResult]) {
+    results.sort_by(|a, b| {
+        let a_score = a.score.unwrap_or(f64::NEG_INFINITY);
+        let b_score = b.score.unwrap_or(f64::NEG_INFINITY);
+        b_score.total_cmp(&a_score)
+    });
+}
+
+pub async fn best_first_agent_search(
+    search_input: &AgentSearchInput,
+    state: &ServerState,
+) -> Result<PreFormattedAgentSearchResult, BestFirstAgentSearchError> {
+    let search_result = match search_and_rerank(search_input, state).await {
+        Ok(results) => results,
+        Err(e) => return Err(BestFirstAgentSearchError::SearchError(e)),
+    };
+    let dom_parse_config = search_input.dom_parse_config();
+    let mut analysis = match search_input.initial_analysis() {
+        Ok(analysis) => analysis,
+        Err(e) => return Err(BestFirstAgentSearchError::CheckpointError(e)),
+    };
+    let new_results = analysis.filter_new_results(search_result);
+    analysis.unvisited_results.extend(new_results);
+    sort_by_score(&mut analysis.unvisited_results);
+    let checkpointer = search_input.checkpointer();
+    let max_results_to_visit = search_input
+        .max_results_to_visit
+        .unwrap_or(MAX_RESULTS_TO_VISIT);
+    while !analysis.unvisited_results.is_empty()
+        && analysis.visited_results.len() < max_results_to_visit
+    {
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
+        let mut result = analysis.unvisited_results.remove(0);
+        if is_url_skipped(&result.url) {
+            continue;
+        }
+        let follow_links = search_input.should_follow_links(&result);
+        let (new_analysis, discovered_results) = match visit_and_extract_relevant_info(
+            &search_input.query,
+            &analysis.content,
+            &mut result,
+            search_input.fallback_to_snippet.unwrap_or(false),
+            follow_links,
+            &dom_parse_config,
+            state,
+        )
+        .await
+        {
+            Ok(extracted) => extracted,
+            Err(e) if search_input.skip_bot_blocked_urls() && e.is_bot_challenge() => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Skipping bot-blocked result: {}",
+                    e
+                );
+                continue;
+            }
+            Err(VisitAndExtractRelevantInfoError::Skipped(skipped_result)) => {
+                tracing::info!(
+                    request_id = %current_request_id(),
+                    "Skipping {}: {}",
+                    skipped_result.url,
+                    skipped_result.reason
+                );
+                analysis.skipped_results.push(skipped_result);
+                continue;
+            }
+            Err(e) => {
+                return Err(BestFirstAgentSearchError::VisitAndExtractRelevantInfoError(
+                    e,
+                ))
+            }
+        };
+        analysis.content = new_analysis;
+        analysis.visited_results.push(result);
+        let discovered_results = search_input.filter_discovered_results(
+            discovered_results,
+            analysis
+                .visited_results
+                .iter()
+                .chain(analysis.unvisited_results.iter()),
+        );
+        analysis.unvisited_results.extend(discovered_results);
+        if let Some(checkpointer) = &checkpointer {
+            checkpointer.save_or_log(&analysis);
+        }
+        match check_sufficient_information(
+            &search_input.query,
+            &analysis.content,
+            &analysis.visited_results,
+            &analysis.unvisited_results,
+        )
+        .await
+        {
+            Ok(decision) => {
+                if decision.sufficient {
+                    break;
+                }
+            }
+            Err(e) => {
+                return Err(BestFirstAgentSearchError::SufficientInformationCheckError(
+                    e,
+                ))
+            }
+        }
+    }
+    if let Some(checkpointer) = &checkpointer {
+        checkpointer.remove();
+    }
+    Ok(PreFormattedAgentSearchResult {
+        raw_analysis: analysis,
+        queries_executed: Vec::new(),
+    })
+}
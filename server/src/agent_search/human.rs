@@ -2,16 +2,23 @@ use serde::Deserialize;
 use thiserror::Error;
 
 use crate::agent_search::{
-    check_sufficient_information, visit_and_extract_relevant_info, AgentSearchInput,
-    AnalysisDocument, LLMError, PreFormattedAgentSearchResult, SearchResult,
+    check_sufficient_information, search_and_rerank, visit_and_extract_relevant_info,
+    AgentSearchInput, LLMError, PreFormattedAgentSearchResult, SearchResult,
     SufficientInformationCheckError, VisitAndExtractRelevantInfoError,
 };
+use crate::checkpoint::CheckpointError;
 use crate::llm::default_completion;
 use crate::prompts::{build_select_next_result_system_prompt, Prompt};
-use crate::search;
-use crate::search::{search, SearchError};
+use crate::request_context::current_request_id;
+use crate::search::SearchError;
+use crate::server::ServerState;
+use crate::session::{is_url_skipped, search_cancelled};
+use crate::usage::{stop_for_llm_budget, LLMCallStage};
 use crate::utils::ParseJsonError;
-use crate::utils::{display_search_results_with_indices, parse_json_response};
+use crate::utils::{
+    display_search_results_with_indices, parse_json_response_with_retry,
+    DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
+};
 
 #[derive(Error, Debug)]
 pub enum SelectNextResultError {
@@ -31,6 +38,8 @@ pub enum HumanAgentSearchError {
     SufficientInformationCheckError(#[from] SufficientInformationCheckError),
     #[error("Failed to select next result: {0}")]
     SelectNextResultError(#[from] SelectNextResultError),
+    #[error("Failed to resume from checkpoint: {0}")]
+    CheckpointError(#[from] CheckpointError),
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -46,45 +55,40 @@ async fn select_next_result(
 ) -> Result<usize, SelectNextResultError> {
     let user_prompt = format!("# Query:\n{}\n\n# Current analysis:\n{}\n\n# Visited results:\n{}\n\n# Unvisited results:\n{}", query, current_analysis, display_search_results_with_indices(visited_results), display_search_results_with_indices(unvisited_results));
     let prompt = Prompt::new(build_select_next_result_system_prompt(), user_prompt);
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::ResultSelection).await {
         Ok(completion) => completion,
         Err(e) => return Err(SelectNextResultError::LLMError(e)),
     };
 
-    let decision: NextResultToVisit = match parse_json_response(&completion) {
-        Ok(decision) => decision,
-        Err(e) => return Err(SelectNextResultError::ParseError(e)),
-    };
+    let decision: NextResultToVisit =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(decision) => decision,
+            Err(e) => return Err(SelectNextResultError::ParseError(e)),
+        };
     Ok(decision.index)
 }
 
 pub async fn human_agent_search(
     search_input: &AgentSearchInput,
-    searx_host: &str,
-    searx_port: &str,
+    state: &ServerState,
 ) -> Result<PreFormattedAgentSearchResult, HumanAgentSearchError> {
-    let search_result = match search(
-        &search::SearchInput {
-            query: search_input.build_google_search_query(),
-            max_results_to_visit: search_input.max_results_to_visit,
-            whitelisted_base_urls: search_input.whitelisted_base_urls.clone(),
-            blacklisted_base_urls: search_input.blacklisted_base_urls.clone(),
-        },
-        searx_host,
-        searx_port,
-    )
-    .await
-    {
+    let search_result = match search_and_rerank(search_input, state).await {
         Ok(results) => results,
         Err(e) => return Err(HumanAgentSearchError::SearchError(e)),
     };
-    let mut analysis = AnalysisDocument {
-        content: String::new(),
-        visited_results: Vec::new(),
-        unvisited_results: Vec::new(),
+    let mut analysis = match search_input.initial_analysis() {
+        Ok(analysis) => analysis,
+        Err(e) => return Err(HumanAgentSearchError::CheckpointError(e)),
     };
-    let mut unvisited_results = search_result.clone();
+    let mut unvisited_results = analysis.filter_new_results(search_result);
+    let checkpointer = search_input.checkpointer();
+    let dom_parse_config = search_input.freshness_filtered_dom_parse_config();
     while !unvisited_results.is_empty() {
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
         let next_index = match select_next_result(
             &search_input.query,
             &analysis.content,
@@ -96,12 +100,64 @@ pub async fn human_agent_search(
             Ok(idx) => idx,
             Err(e) => return Err(HumanAgentSearchError::SelectNextResultError(e)),
         };
-        let result = unvisited_results.remove(next_index);
-        match visit_and_extract_relevant_info(&search_input.query, &analysis.content, &result).await
+        let mut result = unvisited_results.remove(next_index);
+        if is_url_skipped(&result.url) {
+            continue;
+        }
+        let follow_links = search_input.should_follow_links(&result);
+        match visit_and_extract_relevant_info(
+            &search_input.query,
+            &analysis.content,
+            &mut result,
+            search_input.fallback_to_snippet.unwrap_or(false),
+            follow_links,
+            &dom_parse_config,
+            state,
+        )
+        .await
         {
-            Ok(new_analysis) => {
+            Ok((new_analysis, discovered_results)) => {
                 analysis.content = new_analysis;
                 analysis.unvisited_results.push(result);
+                let discovered_results = search_input.filter_discovered_results(
+                    discovered_results,
+                    analysis
+                        .visited_results
+                        .iter()
+                        .chain(analysis.unvisited_results.iter())
+                        .chain(unvisited_results.iter()),
+                );
+                unvisited_results.extend(discovered_results);
+                if let Some(checkpointer) = &checkpointer {
+                    checkpointer.save_or_log(&analysis);
+                }
+            }
+            Err(e) if search_input.skip_bot_blocked_urls() && e.is_bot_challenge() => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Skipping bot-blocked result: {}",
+                    e
+                );
+                continue;
+            }
+            Err(e) if e.is_stale_content() => {
+                tracing::info!(
+                    request_id = %current_request_id(),
+                    "Skipping stale result: {}",
+                    e
+                );
+                analysis.push_stale_result(result);
+                continue;
+            }
+            Err(VisitAndExtractRelevantInfoError::Skipped(skipped_result)) => {
+                tracing::info!(
+                    request_id = %current_request_id(),
+                    "Skipping {}: {}",
+                    skipped_result.url,
+                    skipped_result.reason
+                );
+                analysis.skipped_results.push(skipped_result);
+                continue;
             }
             Err(e) => return Err(HumanAgentSearchError::VisitAndExtractRelevantInfoError(e)),
         }
@@ -121,8 +177,11 @@ pub async fn human_agent_search(
             Err(e) => return Err(HumanAgentSearchError::SufficientInformationCheckError(e)),
         }
     }
+    if let Some(checkpointer) = &checkpointer {
+        checkpointer.remove();
+    }
     Ok(PreFormattedAgentSearchResult {
         raw_analysis: analysis,
-        queries_executed: vec![search_input.query.clone()],
+        queries_executed: Vec::new(),
     })
 }
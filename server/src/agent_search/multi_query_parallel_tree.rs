@@ -0,0 +1,116 @@
+use crate::agent_search::parallel_tree::TreeConstructionError;
+use crate::agent_search::{
+    aggregate_results, parallel_tree_agent_search, record_query_execution, AgentSearchInput,
+    AgentSearchStrategy, AggregationPassError, AnalysisDocument, ExtractionResult,
+    ParallelTreeAgentSearchError, PreFormattedAgentSearchResult, SearchResult,
+};
+use crate::query::{synthesize_queries, QueryStrategy, QuerySynthesisError};
+use crate::search::dedup_search_results;
+use crate::server::ServerState;
+use crate::usage::with_current_search_context;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::task::JoinError;
+
+#[derive(Error, Debug)]
+pub enum MultiQueryParallelTreeAgentSearchError {
+    #[error("Query synthesis failed: {0}")]
+    QuerySynthesisError(#[from] QuerySynthesisError),
+    #[error("Parallel tree agent search failed: {0}")]
+    ParallelTreeAgentSearchError(#[from] ParallelTreeAgentSearchError),
+    #[error("Tree construction failed: {0}")]
+    TreeConstructionError(#[from] TreeConstructionError),
+    #[error("Aggregation pass failed: {0}")]
+    AggregationPassError(#[from] AggregationPassError),
+    #[error("Join error: {0}")]
+    JoinError(#[from] JoinError),
+}
+
+pub async fn multi_query_parallel_tree_agent_search(
+    search_input: &AgentSearchInput,
+    state: &Arc<ServerState>,
+) -> Result<PreFormattedAgentSearchResult, MultiQueryParallelTreeAgentSearchError> {
+    let synthesized_queries =
+        match synthesize_queries(&search_input.query, &QueryStrategy::Parallel).await {
+            Ok(queries) => queries,
+            Err(e) => {
+                return Err(MultiQueryParallelTreeAgentSearchError::QuerySynthesisError(
+                    e,
+                ))
+            }
+        };
+    let tasks = synthesized_queries.queries.iter().map(|query| {
+        let modified_input = AgentSearchInput {
+            query: query.clone(),
+            ..search_input.clone()
+        };
+        let state = state.clone();
+        tokio::spawn(with_current_search_context(async move {
+            record_query_execution(
+                &modified_input.query,
+                AgentSearchStrategy::ParallelTree,
+                parallel_tree_agent_search(&modified_input, &state),
+            )
+            .await
+        }))
+    });
+    let join_results = futures::future::join_all(tasks).await;
+    let mut results = Vec::new();
+    for join_result in join_results {
+        match join_result {
+            Ok(Ok(result)) => results.push(result),
+            Ok(Err(ParallelTreeAgentSearchError::TreeConstructionError(e))) => {
+                return Err(MultiQueryParallelTreeAgentSearchError::TreeConstructionError(e))
+            }
+            Ok(Err(e)) => {
+                return Err(MultiQueryParallelTreeAgentSearchError::ParallelTreeAgentSearchError(e))
+            }
+            Err(e) => return Err(MultiQueryParallelTreeAgentSearchError::JoinError(e)),
+        }
+    }
+    if results.len() == 1 {
+        return Ok(results.remove(0));
+    }
+    let extraction_results = synthesized_queries
+        .queries
+        .iter()
+        .zip(results.iter())
+        .map(|(query, result)| ExtractionResult {
+            search_result: SearchResult {
+                title: format!("Analysis for query: {}", query),
+                url: String::new(),
+                content: String::new(),
+                published_at: None,
+                modified_at: None,
+                link_depth: None,
+                infobox: None,
+                anchor_text: None,
+                score: None,
+            },
+            content: result.raw_analysis.content.clone(),
+        })
+        .collect::<Vec<_>>();
+    let content = match aggregate_results(&search_input.query, extraction_results).await {
+        Ok(content) => content,
+        Err(e) => return Err(MultiQueryParallelTreeAgentSearchError::AggregationPassError(e)),
+    };
+    let mut visited_results = Vec::new();
+    let mut unvisited_results = Vec::new();
+    let mut skipped_results = Vec::new();
+    let mut queries_executed = Vec::new();
+    for result in results {
+        visited_results.extend(result.raw_analysis.visited_results);
+        unvisited_results.extend(result.raw_analysis.unvisited_results);
+        skipped_results.extend(result.raw_analysis.skipped_results);
+        queries_executed.extend(result.queries_executed);
+    }
+    Ok(PreFormattedAgentSearchResult {
+        raw_analysis: AnalysisDocument {
+            content,
+            visited_results: dedup_search_results(visited_results),
+            unvisited_results: dedup_search_results(unvisited_results),
+            skipped_results,
+        },
+        queries_executed,
+    })
+}
@@ -1,12 +1,19 @@
 use crate::agent_search::{
-    parallel_visit_and_extract_relevant_info, AgentSearchInput, AggregationPassError,
-    PreFormattedAgentSearchResult, VisitAndExtractRelevantInfoError,
+    check_sufficient_information, parallel_visit_and_extract_relevant_info, search_and_rerank,
+    search_extra_page, AgentSearchInput, AggregationPassError, AnalysisDocument,
+    PreFormattedAgentSearchResult, SufficientInformationCheckError,
+    VisitAndExtractRelevantInfoError,
 };
-use crate::search;
-use crate::search::{search, SearchError};
+use crate::search::{SearchError, SearchResult};
+use crate::server::ServerState;
+use crate::session::search_cancelled;
+use crate::usage::stop_for_llm_budget;
+use std::sync::Arc;
 use thiserror::Error;
 use tokio::task::JoinError;
 
+pub const DEFAULT_MAX_EXTRA_PAGES: usize = 2;
+
 #[derive(Error, Debug)]
 pub enum ParallelAgentSearchError {
     #[error("Search failed: {0}")]
@@ -17,27 +24,131 @@ pub enum ParallelAgentSearchError {
     AggregationPassError(#[from] AggregationPassError),
     #[error("Join error: {0}")]
     JoinError(#[from] JoinError),
+    #[error("Sufficient information check failed: {0}")]
+    SufficientInformationCheckError(#[from] SufficientInformationCheckError),
 }
 
 pub async fn parallel_agent_search(
     search_input: &AgentSearchInput,
-    searx_host: &str,
-    searx_port: &str,
+    state: &Arc<ServerState>,
 ) -> Result<PreFormattedAgentSearchResult, ParallelAgentSearchError> {
-    let search_results = match search(
-        &search::SearchInput {
-            query: search_input.build_google_search_query(),
-            max_results_to_visit: search_input.max_results_to_visit,
-            whitelisted_base_urls: search_input.whitelisted_base_urls.clone(),
-            blacklisted_base_urls: search_input.blacklisted_base_urls.clone(),
-        },
-        searx_host,
-        searx_port,
-    )
-    .await
-    {
+    let search_results = match search_and_rerank(search_input, state).await {
         Ok(results) => results,
         Err(e) => return Err(ParallelAgentSearchError::SearchError(e)),
     };
-    parallel_visit_and_extract_relevant_info(&search_input.query, &search_results, "").await
+    if search_input.dynamic_parallel.unwrap_or(false) {
+        return dynamic_parallel_agent_search(search_input, search_results, state).await;
+    }
+    let mut analysis =
+        parallel_visit_and_extract_relevant_info(search_input, &search_results, "", state)
+            .await?
+            .raw_analysis;
+    loop {
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
+        let discovered_results = search_input.filter_discovered_results(
+            std::mem::take(&mut analysis.unvisited_results),
+            analysis.visited_results.iter(),
+        );
+        if discovered_results.is_empty() {
+            break;
+        }
+        let batch_result = parallel_visit_and_extract_relevant_info(
+            search_input,
+            &discovered_results,
+            &analysis.content,
+            state,
+        )
+        .await?;
+        analysis.content = batch_result.raw_analysis.content;
+        analysis
+            .visited_results
+            .extend(batch_result.raw_analysis.visited_results);
+        analysis
+            .skipped_results
+            .extend(batch_result.raw_analysis.skipped_results);
+        analysis.unvisited_results = batch_result.raw_analysis.unvisited_results;
+    }
+    Ok(PreFormattedAgentSearchResult {
+        raw_analysis: analysis,
+        queries_executed: Vec::new(),
+    })
+}
+
+async fn dynamic_parallel_agent_search(
+    search_input: &AgentSearchInput,
+    search_results: Vec<SearchResult>,
+    state: &Arc<ServerState>,
+) -> Result<PreFormattedAgentSearchResult, ParallelAgentSearchError> {
+    let max_extra_pages = search_input
+        .max_extra_pages
+        .unwrap_or(DEFAULT_MAX_EXTRA_PAGES);
+    let first_batch_size = search_results.len().div_ceil(2);
+    let mut analysis = AnalysisDocument {
+        content: String::new(),
+        visited_results: Vec::new(),
+        unvisited_results: search_results,
+        skipped_results: Vec::new(),
+    };
+    let mut extra_pages = 0;
+    loop {
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
+        if analysis.unvisited_results.is_empty() {
+            if extra_pages >= max_extra_pages {
+                break;
+            }
+            extra_pages += 1;
+            let page_results = match search_extra_page(search_input, state, extra_pages).await {
+                Ok(results) => results,
+                Err(e) => return Err(ParallelAgentSearchError::SearchError(e)),
+            };
+            analysis.unvisited_results = analysis.filter_new_results(page_results);
+            continue;
+        }
+        let num_to_visit = if analysis.visited_results.is_empty() {
+            first_batch_size
+        } else {
+            analysis.unvisited_results.len()
+        };
+        let batch: Vec<_> = analysis.unvisited_results.drain(..num_to_visit).collect();
+        let batch_result = parallel_visit_and_extract_relevant_info(
+            search_input,
+            &batch,
+            &analysis.content,
+            state,
+        )
+        .await?;
+        analysis.content = batch_result.raw_analysis.content;
+        analysis
+            .visited_results
+            .extend(batch_result.raw_analysis.visited_results);
+        analysis
+            .skipped_results
+            .extend(batch_result.raw_analysis.skipped_results);
+        let discovered_results = search_input.filter_discovered_results(
+            batch_result.raw_analysis.unvisited_results,
+            analysis
+                .visited_results
+                .iter()
+                .chain(analysis.unvisited_results.iter()),
+        );
+        analysis.unvisited_results.extend(discovered_results);
+        let decision = check_sufficient_information(
+            &search_input.query,
+            &analysis.content,
+            &analysis.visited_results,
+            &analysis.unvisited_results,
+        )
+        .await?;
+        if decision.sufficient {
+            break;
+        }
+    }
+    Ok(PreFormattedAgentSearchResult {
+        raw_analysis: analysis,
+        queries_executed: Vec::new(),
+    })
 }
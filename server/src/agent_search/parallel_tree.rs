@@ -1,13 +1,21 @@
 use crate::agent_search::VisitAndExtractRelevantInfoError;
 use crate::agent_search::{
-    parallel_visit_and_extract_relevant_info, AgentSearchInput, AnalysisDocument,
+    aggregate_results, parallel_visit_and_extract_relevant_info, search_and_rerank,
+    AgentSearchInput, AggregationPassError, AnalysisDocument, ExtractionResult,
     PreFormattedAgentSearchResult, SearchResult,
 };
-use crate::llm::{default_completion, LLMError};
-use crate::prompts::{build_dependency_tree_system_prompt, Prompt};
-use crate::search;
-use crate::search::{search, SearchError};
+use crate::llm::{default_completion, CompletionResult, LLMError};
+use crate::prompts::{
+    build_dependency_tree_system_prompt, Prompt, DEPENDENCY_TREE_LEVEL_DEPENDENCIES_NOTE,
+};
+use crate::result_format::SkippedResult;
+use crate::search::{dedup_search_results, SearchError};
+use crate::server::ServerState;
+use crate::session::search_cancelled;
+use crate::usage::{stop_for_llm_budget, LLMCallStage};
+use crate::utils::parse_json_response;
 use serde::Deserialize;
+use std::sync::Arc;
 use thiserror::Error;
 use tokio::task::JoinError;
 
@@ -25,6 +33,8 @@ pub enum ParallelTreeAgentSearchError {
     ParallelAgentSearchError(#[from] ParallelAgentSearchError),
     #[error("Join error: {0}")]
     JoinError(#[from] JoinError),
+    #[error("Aggregation pass failed: {0}")]
+    AggregationPassError(#[from] AggregationPassError),
 }
 
 #[derive(Error, Debug)]
@@ -33,16 +43,85 @@ pub enum TreeConstructionError {
     LLMError(#[from] LLMError),
     #[error("Parse error: {0}")]
     ParseError(String),
+    #[error(
+        "Result index {index} in level {level} is out of range for {num_results} search results"
+    )]
+    ResultIndexOutOfRange {
+        index: usize,
+        level: usize,
+        num_results: usize,
+    },
 }
 
 #[derive(Deserialize, Debug)]
 struct DependencyTree {
     levels: Vec<Vec<usize>>,
+    #[serde(default)]
+    depends_on: Vec<Vec<usize>>,
+}
+
+impl DependencyTree {
+    fn fold_levels_beyond(&mut self, max_levels: usize) {
+        let max_levels = max_levels.max(1);
+        if self.levels.len() <= max_levels {
+            return;
+        }
+        let folded_levels = self.levels.split_off(max_levels);
+        if let Some(last_level) = self.levels.last_mut() {
+            last_level.extend(folded_levels.into_iter().flatten());
+        }
+        if self.depends_on.len() > max_levels {
+            let folded_dependencies = self.depends_on.split_off(max_levels);
+            if let Some(last_dependencies) = self.depends_on.last_mut() {
+                last_dependencies.extend(folded_dependencies.into_iter().flatten());
+            }
+        }
+    }
+
+    fn level_dependencies(&self, level: usize) -> Vec<usize> {
+        let mut dependencies = if self.depends_on.len() == self.levels.len() {
+            self.depends_on[level]
+                .iter()
+                .copied()
+                .filter(|&dependency| dependency < level)
+                .collect()
+        } else if level > 0 {
+            vec![level - 1]
+        } else {
+            Vec::new()
+        };
+        dependencies.extend((0..level).filter(|&earlier_level| {
+            self.levels[earlier_level]
+                .iter()
+                .any(|idx| self.levels[level].contains(idx))
+        }));
+        dependencies
+    }
+
+    fn execution_waves(&self) -> Vec<Vec<usize>> {
+        let mut level_waves: Vec<usize> = Vec::with_capacity(self.levels.len());
+        for level in 0..self.levels.len() {
+            let wave = self
+                .level_dependencies(level)
+                .into_iter()
+                .map(|dependency| level_waves[dependency] + 1)
+                .max()
+                .unwrap_or(0);
+            level_waves.push(wave);
+        }
+        let num_waves = level_waves.iter().max().map_or(0, |&wave| wave + 1);
+        let mut waves = vec![Vec::new(); num_waves];
+        for (level, wave) in level_waves.into_iter().enumerate() {
+            waves[wave].push(level);
+        }
+        waves
+    }
 }
 
 async fn construct_dependency_tree(
     query: &str,
     search_results: &[SearchResult],
+    with_level_dependencies: bool,
 ) -> Result<DependencyTree, TreeConstructionError> {
     let results_display = search_results
         .iter()
@@ -51,80 +130,123 @@ async fn construct_dependency_tree(
         .collect::<Vec<_>>()
         .join("\n");
 
+    let mut system_prompt = build_dependency_tree_system_prompt();
+    if with_level_dependencies {
+        system_prompt.push_str(DEPENDENCY_TREE_LEVEL_DEPENDENCIES_NOTE);
+    }
     let prompt = Prompt::new(
-        build_dependency_tree_system_prompt(),
+        system_prompt,
         format!(
             "# Query:\n{}\n\n# Search Results:\n{}",
             query, results_display
         ),
     );
 
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::DependencyTree).await {
         Ok(completion) => completion,
         Err(e) => return Err(TreeConstructionError::LLMError(e)),
     };
 
-    serde_json::from_str(&completion).map_err(|e| {
-        TreeConstructionError::ParseError(format!("Failed to parse dependency tree: {}", e))
-    })
+    let parsed = parse_json_response(&CompletionResult::Text(completion.clone()));
+    let dependency_tree: DependencyTree = match parsed {
+        Ok(dependency_tree) => dependency_tree,
+        Err(e) => match serde_json::from_str(completion.trim()) {
+            Ok(dependency_tree) => dependency_tree,
+            Err(_) => {
+                return Err(TreeConstructionError::ParseError(format!(
+                    "Failed to parse dependency tree: {}",
+                    e
+                )))
+            }
+        },
+    };
+    for (level, level_indices) in dependency_tree.levels.iter().enumerate() {
+        if let Some(&index) = level_indices
+            .iter()
+            .find(|&&index| index >= search_results.len())
+        {
+            return Err(TreeConstructionError::ResultIndexOutOfRange {
+                index,
+                level,
+                num_results: search_results.len(),
+            });
+        }
+    }
+    Ok(dependency_tree)
 }
 
 async fn process_level(
-    query: &str,
+    search_input: &AgentSearchInput,
     search_results: &[SearchResult],
     level_indices: &[usize],
     current_analysis: &str,
-) -> Result<String, ParallelTreeAgentSearchError> {
+    state: &Arc<ServerState>,
+) -> Result<(String, Vec<SkippedResult>), ParallelTreeAgentSearchError> {
     let level_results: Vec<SearchResult> = level_indices
         .iter()
         .map(|&idx| search_results[idx].clone())
         .collect();
-    let aggregated_result =
-        match parallel_visit_and_extract_relevant_info(query, &level_results, current_analysis)
-            .await
-        {
-            Ok(result) => result,
-            Err(e) => return Err(ParallelTreeAgentSearchError::ParallelAgentSearchError(e)),
-        };
-    Ok(aggregated_result.raw_analysis.content)
+    let aggregated_result = match parallel_visit_and_extract_relevant_info(
+        search_input,
+        &level_results,
+        current_analysis,
+        state,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => return Err(ParallelTreeAgentSearchError::ParallelAgentSearchError(e)),
+    };
+    Ok((
+        aggregated_result.raw_analysis.content,
+        aggregated_result.raw_analysis.skipped_results,
+    ))
 }
 
 pub async fn parallel_tree_agent_search(
     search_input: &AgentSearchInput,
-    searx_host: &str,
-    searx_port: &str,
+    state: &Arc<ServerState>,
 ) -> Result<PreFormattedAgentSearchResult, ParallelTreeAgentSearchError> {
-    let search_results = match search(
-        &search::SearchInput {
-            query: search_input.build_google_search_query(),
-            max_results_to_visit: search_input.max_results_to_visit,
-            whitelisted_base_urls: search_input.whitelisted_base_urls.clone(),
-            blacklisted_base_urls: search_input.blacklisted_base_urls.clone(),
-        },
-        searx_host,
-        searx_port,
-    )
-    .await
-    {
+    let search_results = match search_and_rerank(search_input, state).await {
         Ok(results) => results,
         Err(e) => return Err(ParallelTreeAgentSearchError::SearchError(e)),
     };
 
-    let dependency_tree = construct_dependency_tree(&search_input.query, &search_results)
-        .await
-        .map_err(ParallelTreeAgentSearchError::TreeConstructionError)?;
+    let inter_level_parallelism = search_input.inter_level_parallelism.unwrap_or(false);
+    let mut dependency_tree = construct_dependency_tree(
+        &search_input.query,
+        &search_results,
+        inter_level_parallelism,
+    )
+    .await
+    .map_err(ParallelTreeAgentSearchError::TreeConstructionError)?;
+    if let Some(max_dependency_tree_levels) = search_input.max_dependency_tree_levels {
+        dependency_tree.fold_levels_beyond(max_dependency_tree_levels);
+    }
+
+    if inter_level_parallelism {
+        return process_levels_in_waves(search_input, &search_results, &dependency_tree, state)
+            .await;
+    }
 
     let mut current_analysis = String::new();
     let mut visited_results = Vec::new();
+    let mut skipped_results = Vec::new();
 
     for level in dependency_tree.levels {
-        current_analysis = process_level(
-            &search_input.query,
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
+        let (level_analysis, level_skipped_results) = process_level(
+            search_input,
             &search_results,
             &level,
             &current_analysis,
+            state,
         )
         .await?;
+        current_analysis = level_analysis;
+        skipped_results.extend(level_skipped_results);
         visited_results.extend(level.iter().map(|&idx| search_results[idx].clone()));
     }
 
@@ -133,7 +255,78 @@ pub async fn parallel_tree_agent_search(
             content: current_analysis,
             visited_results,
             unvisited_results: Vec::new(),
+            skipped_results,
+        },
+        queries_executed: Vec::new(),
+    })
+}
+
+async fn process_levels_in_waves(
+    search_input: &AgentSearchInput,
+    search_results: &[SearchResult],
+    dependency_tree: &DependencyTree,
+    state: &Arc<ServerState>,
+) -> Result<PreFormattedAgentSearchResult, ParallelTreeAgentSearchError> {
+    let mut current_analysis = String::new();
+    let mut visited_results = Vec::new();
+    let mut skipped_results = Vec::new();
+
+    for wave in dependency_tree.execution_waves() {
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
+        let level_analyses = futures::future::try_join_all(wave.iter().map(|&level| {
+            process_level(
+                search_input,
+                search_results,
+                &dependency_tree.levels[level],
+                &current_analysis,
+                state,
+            )
+        }))
+        .await?;
+        let (level_analyses, level_skipped_results): (Vec<_>, Vec<_>) =
+            level_analyses.into_iter().unzip();
+        skipped_results.extend(level_skipped_results.into_iter().flatten());
+        current_analysis = if level_analyses.len() == 1 {
+            level_analyses.into_iter().next().unwrap_or_default()
+        } else {
+            let extraction_results = wave
+                .iter()
+                .zip(level_analyses)
+                .map(|(level, content)| ExtractionResult {
+                    search_result: SearchResult {
+                        title: format!("Level {} analysis", level),
+                        url: String::new(),
+                        content: String::new(),
+                        published_at: None,
+                        modified_at: None,
+                        link_depth: None,
+                        infobox: None,
+                        anchor_text: None,
+                        score: None,
+                    },
+                    content,
+                })
+                .collect();
+            aggregate_results(&search_input.query, extraction_results).await?
+        };
+        for &level in &wave {
+            visited_results.extend(
+                dependency_tree.levels[level]
+                    .iter()
+                    .map(|&idx| search_results[idx].clone()),
+            );
+        }
+    }
+
+    Ok(PreFormattedAgentSearchResult {
+        raw_analysis: AnalysisDocument {
+            content: current_analysis,
+            visited_results: dedup_search_results(visited_results),
+            unvisited_results: Vec::new(),
+            skipped_results,
         },
-        queries_executed: vec![search_input.query.clone()],
+        queries_executed: Vec::new(),
     })
 }
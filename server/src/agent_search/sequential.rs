@@ -1,12 +1,20 @@
 use crate::agent_search::{
-    check_sufficient_information, visit_and_extract_relevant_info, AgentSearchInput,
-    AnalysisDocument, PreFormattedAgentSearchResult, SufficientInformationCheckError,
+    aggregate_results, check_sufficient_information, search_and_rerank,
+    visit_and_extract_relevant_info, AgentSearchInput, AggregationPassError, ExtractionResult,
+    PreFormattedAgentSearchResult, SufficientInformationCheckError,
     VisitAndExtractRelevantInfoError,
 };
-use crate::search;
-use crate::search::{search, SearchError};
+use crate::checkpoint::CheckpointError;
+use crate::request_context::current_request_id;
+use crate::search::{SearchError, SearchResult, MAX_RESULTS_TO_VISIT};
+use crate::server::ServerState;
+use crate::session::{is_url_skipped, search_cancelled};
+use crate::usage::stop_for_llm_budget;
+use crate::utils::extract_urls_from_markdown;
 use thiserror::Error;
 
+const MIN_SUMMARIZATION_CHARS: usize = 500;
+
 #[derive(Error, Debug)]
 pub enum SequentialAgentSearchError {
     #[error("Search failed: {0}")]
@@ -15,46 +23,147 @@ pub enum SequentialAgentSearchError {
     VisitAndExtractRelevantInfoError(#[from] VisitAndExtractRelevantInfoError),
     #[error("Sufficient information check failed: {0}")]
     SufficientInformationCheckError(#[from] SufficientInformationCheckError),
+    #[error("Failed to resume from checkpoint: {0}")]
+    CheckpointError(#[from] CheckpointError),
+    #[error("Aggregation pass failed: {0}")]
+    AggregationPassError(#[from] AggregationPassError),
+}
+
+fn exceeds_analysis_growth_ratio(previous: &str, new: &str, max_growth_ratio: f32) -> bool {
+    let max_chars = (previous.len() as f32 * max_growth_ratio) as usize;
+    new.len() > max_chars.max(MIN_SUMMARIZATION_CHARS)
 }
 
 pub async fn sequential_agent_search(
     search_input: &AgentSearchInput,
-    searx_host: &str,
-    searx_port: &str,
+    state: &ServerState,
 ) -> Result<PreFormattedAgentSearchResult, SequentialAgentSearchError> {
-    let search_result = match search(
-        &search::SearchInput {
-            query: search_input.build_google_search_query(),
-            max_results_to_visit: search_input.max_results_to_visit,
-            whitelisted_base_urls: search_input.whitelisted_base_urls.clone(),
-            blacklisted_base_urls: search_input.blacklisted_base_urls.clone(),
-        },
-        searx_host,
-        searx_port,
-    )
-    .await
-    {
+    let search_result = match search_and_rerank(search_input, state).await {
         Ok(results) => results,
         Err(e) => return Err(SequentialAgentSearchError::SearchError(e)),
     };
-    let mut analysis = AnalysisDocument {
-        content: String::new(),
-        visited_results: Vec::new(),
-        unvisited_results: search_result.clone(),
+    let dom_parse_config = search_input.freshness_filtered_dom_parse_config();
+    let mut analysis = match search_input.initial_analysis() {
+        Ok(analysis) => analysis,
+        Err(e) => return Err(SequentialAgentSearchError::CheckpointError(e)),
     };
-    while !analysis.unvisited_results.is_empty() {
-        let result = analysis.unvisited_results.remove(0);
-        let new_analysis =
-            match visit_and_extract_relevant_info(&search_input.query, &analysis.content, &result)
-                .await
+    let new_results = analysis.filter_new_results(search_result);
+    analysis.unvisited_results.extend(new_results);
+    search_input.sort_by_source_credibility(&mut analysis.unvisited_results);
+    let checkpointer = search_input.checkpointer();
+    let max_results_to_visit = search_input
+        .max_results_to_visit
+        .unwrap_or(MAX_RESULTS_TO_VISIT);
+    let mut stale_results = Vec::new();
+    while !analysis.unvisited_results.is_empty()
+        && analysis.visited_results.len() < max_results_to_visit
+    {
+        if stop_for_llm_budget() || search_cancelled() {
+            break;
+        }
+        let mut result = analysis.unvisited_results.remove(0);
+        if is_url_skipped(&result.url) {
+            continue;
+        }
+        let follow_links = search_input.should_follow_links(&result);
+        let (new_analysis, discovered_results) = match visit_and_extract_relevant_info(
+            &search_input.query,
+            &analysis.content,
+            &mut result,
+            search_input.fallback_to_snippet.unwrap_or(false),
+            follow_links,
+            &dom_parse_config,
+            state,
+        )
+        .await
+        {
+            Ok(extracted) => extracted,
+            Err(e) if search_input.skip_bot_blocked_urls() && e.is_bot_challenge() => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Skipping bot-blocked result: {}",
+                    e
+                );
+                continue;
+            }
+            Err(e) if e.is_stale_content() => {
+                tracing::info!(
+                    request_id = %current_request_id(),
+                    "Skipping stale result: {}",
+                    e
+                );
+                stale_results.push(result);
+                continue;
+            }
+            Err(VisitAndExtractRelevantInfoError::Skipped(skipped_result)) => {
+                tracing::info!(
+                    request_id = %current_request_id(),
+                    "Skipping {}: {}",
+                    skipped_result.url,
+                    skipped_result.reason
+                );
+                analysis.skipped_results.push(skipped_result);
+                continue;
+            }
+            Err(e) => return Err(SequentialAgentSearchError::VisitAndExtractRelevantInfoError(e)),
+        };
+        let new_analysis = match search_input.max_analysis_growth_ratio {
+            Some(max_growth_ratio)
+                if exceeds_analysis_growth_ratio(
+                    &analysis.content,
+                    &new_analysis,
+                    max_growth_ratio,
+                ) =>
             {
-                Ok(new_analysis) => new_analysis,
-                Err(e) => {
-                    return Err(SequentialAgentSearchError::VisitAndExtractRelevantInfoError(e))
+                let extraction_results = vec![ExtractionResult {
+                    search_result: result.clone(),
+                    content: new_analysis,
+                }];
+                match aggregate_results(&search_input.query, extraction_results).await {
+                    Ok(content) => content,
+                    Err(e) => return Err(SequentialAgentSearchError::AggregationPassError(e)),
                 }
-            };
+            }
+            _ => new_analysis,
+        };
         analysis.content = new_analysis;
         analysis.visited_results.push(result);
+        let discovered_results = search_input.filter_discovered_results(
+            discovered_results,
+            analysis
+                .visited_results
+                .iter()
+                .chain(analysis.unvisited_results.iter()),
+        );
+        analysis.unvisited_results.extend(discovered_results);
+        if search_input.enable_multi_hop.unwrap_or(false) {
+            let multi_hop_results = extract_urls_from_markdown(&analysis.content)
+                .into_iter()
+                .map(|url| SearchResult {
+                    title: url.clone(),
+                    url,
+                    content: "[Linked from the findings document]".to_string(),
+                    published_at: None,
+                    modified_at: None,
+                    link_depth: None,
+                    infobox: None,
+                    anchor_text: None,
+                    score: None,
+                })
+                .collect();
+            let multi_hop_results = search_input.filter_discovered_results(
+                multi_hop_results,
+                analysis
+                    .visited_results
+                    .iter()
+                    .chain(analysis.unvisited_results.iter()),
+            );
+            analysis.unvisited_results.extend(multi_hop_results);
+        }
+        search_input.sort_by_source_credibility(&mut analysis.unvisited_results);
+        if let Some(checkpointer) = &checkpointer {
+            checkpointer.save_or_log(&analysis);
+        }
         match check_sufficient_information(
             &search_input.query,
             &analysis.content,
@@ -75,8 +184,14 @@ pub async fn sequential_agent_search(
             }
         }
     }
+    for stale_result in stale_results {
+        analysis.push_stale_result(stale_result);
+    }
+    if let Some(checkpointer) = &checkpointer {
+        checkpointer.remove();
+    }
     Ok(PreFormattedAgentSearchResult {
         raw_analysis: analysis,
-        queries_executed: vec![search_input.query.clone()],
+        queries_executed: Vec::new(),
     })
 }
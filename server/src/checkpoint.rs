@@ -0,0 +1,103 @@
+use crate::request_context::current_request_id;
+use crate::result_format::AnalysisDocument;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("Checkpoint IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Checkpoint serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Invalid search ID: {0}")]
+    InvalidSearchId(String),
+    #[error("Checkpoints are not enabled (SEARCH_CHECKPOINT_DIR is not set)")]
+    NotConfigured,
+}
+
+pub fn checkpoint_root() -> Option<PathBuf> {
+    std::env::var("SEARCH_CHECKPOINT_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+pub fn is_valid_search_id(search_id: &str) -> bool {
+    !search_id.is_empty()
+        && search_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+pub fn sub_search_id(search_id: &str, index: usize) -> String {
+    format!("{}-{}", search_id, index)
+}
+
+#[derive(Debug, Clone)]
+pub struct Checkpointer {
+    path: PathBuf,
+}
+
+impl Checkpointer {
+    pub fn new(search_id: &str) -> Result<Self, CheckpointError> {
+        if !is_valid_search_id(search_id) {
+            return Err(CheckpointError::InvalidSearchId(search_id.to_string()));
+        }
+        let checkpoint_root = match checkpoint_root() {
+            Some(checkpoint_root) => checkpoint_root,
+            None => return Err(CheckpointError::NotConfigured),
+        };
+        Ok(Self {
+            path: checkpoint_root.join(format!("{}.json", search_id)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn load(&self) -> Result<Option<AnalysisDocument>, CheckpointError> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CheckpointError::IoError(e)),
+        };
+        let analysis = serde_json::from_str(&content)?;
+        Ok(Some(analysis))
+    }
+
+    pub fn save(&self, analysis: &AnalysisDocument) -> Result<(), CheckpointError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(analysis)?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    pub fn save_or_log(&self, analysis: &AnalysisDocument) {
+        if let Err(e) = self.save(analysis) {
+            tracing::error!(
+                request_id = %current_request_id(),
+                "Failed to save checkpoint {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    pub fn remove(&self) {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::error!(
+                request_id = %current_request_id(),
+                "Failed to remove checkpoint {}: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::agent_search::adaptive_parallel::DEFAULT_ADAPTIVE_BATCH_SIZE;
+use crate::agent_search::{
+    aggregate_batch_size, AgentSearchInput, AgentSearchStrategy, DEFAULT_MAX_PAGE_CONTENT_CHARS,
+};
+use crate::llm::default_model_name;
+use crate::pricing::model_pricing_or_fallback;
+use crate::query::QueryStrategy;
+use crate::result_format::ResultFormat;
+use crate::search::MAX_RESULTS_TO_VISIT;
+use serde::Serialize;
+
+const ESTIMATED_SYNTHESIZED_QUERIES: u64 = 3;
+const EXTRACTION_PROMPT_OVERHEAD_TOKENS: u64 = 1000;
+const CHARS_PER_TOKEN: u64 = 4;
+
+const QUERY_SYNTHESIS_TOKENS: (u64, u64) = (600, 200);
+const QUERY_REWRITE_TOKENS: (u64, u64) = (300, 100);
+const RESULT_SELECTION_TOKENS: (u64, u64) = (2000, 100);
+const RERANKING_TOKENS: (u64, u64) = (2500, 200);
+const DEPENDENCY_TREE_TOKENS: (u64, u64) = (2000, 300);
+const EXTRACTION_COMPLETION_TOKENS: u64 = 500;
+const SUFFICIENCY_CHECK_TOKENS: (u64, u64) = (2500, 100);
+const AGGREGATION_PROMPT_TOKENS_PER_PAGE: u64 = 600;
+const AGGREGATION_COMPLETION_TOKENS: u64 = 1500;
+const DEDUPLICATION_TOKENS: (u64, u64) = (5000, 2500);
+const FORMATTING_TOKENS: (u64, u64) = (4000, 1500);
+const FOLLOW_UP_QUERIES_TOKENS: (u64, u64) = (3000, 200);
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CostEstimate {
+    pub estimated_llm_calls: u64,
+    pub estimated_prompt_tokens: u64,
+    pub estimated_completion_tokens: u64,
+    pub estimated_cost_usd: f64,
+    pub model: String,
+}
+
+impl CostEstimate {
+    fn add_calls(&mut self, calls: u64, (prompt_tokens, completion_tokens): (u64, u64)) {
+        self.estimated_llm_calls += calls;
+        self.estimated_prompt_tokens += calls * prompt_tokens;
+        self.estimated_completion_tokens += calls * completion_tokens;
+    }
+}
+
+fn extraction_prompt_tokens(search_input: &AgentSearchInput) -> u64 {
+    if let Some(tokens) = std::env::var("ESTIMATED_EXTRACTION_PROMPT_TOKENS")
+        .ok()
+        .and_then(|tokens| tokens.parse().ok())
+    {
+        return tokens;
+    }
+    let max_page_content_chars = search_input
+        .max_page_content_chars
+        .unwrap_or(DEFAULT_MAX_PAGE_CONTENT_CHARS) as u64;
+    max_page_content_chars / CHARS_PER_TOKEN + EXTRACTION_PROMPT_OVERHEAD_TOKENS
+}
+
+fn estimate_aggregation(pages: u64) -> (u64, (u64, u64)) {
+    let batch_size = aggregate_batch_size() as u64;
+    let mut documents = pages.max(1);
+    let mut calls = 1;
+    while documents > batch_size {
+        calls += documents / batch_size;
+        documents = documents.div_ceil(batch_size);
+    }
+    let tokens = (
+        pages.clamp(1, batch_size) * AGGREGATION_PROMPT_TOKENS_PER_PAGE,
+        AGGREGATION_COMPLETION_TOKENS,
+    );
+    (calls, tokens)
+}
+
+fn estimate_query_synthesis(estimate: &mut CostEstimate, query_strategy: &QueryStrategy) -> u64 {
+    match query_strategy {
+        QueryStrategy::Verbatim => 1,
+        QueryStrategy::Single => {
+            estimate.add_calls(1, QUERY_SYNTHESIS_TOKENS);
+            1
+        }
+        QueryStrategy::Parallel | QueryStrategy::Sequential => {
+            estimate.add_calls(1, QUERY_SYNTHESIS_TOKENS);
+            ESTIMATED_SYNTHESIZED_QUERIES
+        }
+        QueryStrategy::Auto | QueryStrategy::Adaptive => {
+            estimate.add_calls(2, QUERY_SYNTHESIS_TOKENS);
+            1
+        }
+    }
+}
+
+fn estimate_single_query_search(
+    estimate: &mut CostEstimate,
+    search_input: &AgentSearchInput,
+    search_strategy: &AgentSearchStrategy,
+) {
+    let pages = search_input
+        .max_results_to_visit
+        .unwrap_or(MAX_RESULTS_TO_VISIT) as u64;
+    let extraction_tokens = (
+        extraction_prompt_tokens(search_input),
+        EXTRACTION_COMPLETION_TOKENS,
+    );
+    let (aggregation_calls, aggregation_tokens) = estimate_aggregation(pages);
+    if search_input.rerank_results.unwrap_or(false) {
+        estimate.add_calls(1, RERANKING_TOKENS);
+    }
+    match search_strategy {
+        AgentSearchStrategy::Human => {
+            estimate.add_calls(pages, RESULT_SELECTION_TOKENS);
+            estimate.add_calls(pages, extraction_tokens);
+            estimate.add_calls(pages, SUFFICIENCY_CHECK_TOKENS);
+        }
+        AgentSearchStrategy::Sequential | AgentSearchStrategy::BestFirst => {
+            estimate.add_calls(pages, extraction_tokens);
+            estimate.add_calls(pages, SUFFICIENCY_CHECK_TOKENS);
+        }
+        AgentSearchStrategy::Parallel if search_input.dynamic_parallel.unwrap_or(false) => {
+            estimate.add_calls(pages, extraction_tokens);
+            estimate.add_calls(2 * aggregation_calls, aggregation_tokens);
+            estimate.add_calls(2, SUFFICIENCY_CHECK_TOKENS);
+        }
+        AgentSearchStrategy::Parallel => {
+            estimate.add_calls(pages, extraction_tokens);
+            estimate.add_calls(aggregation_calls, aggregation_tokens);
+        }
+        AgentSearchStrategy::AdaptiveParallel => {
+            let batch_size = search_input
+                .adaptive_batch_size
+                .unwrap_or(DEFAULT_ADAPTIVE_BATCH_SIZE)
+                .max(1) as u64;
+            estimate.add_calls(pages, extraction_tokens);
+            estimate.add_calls(pages.div_ceil(batch_size), SUFFICIENCY_CHECK_TOKENS);
+            estimate.add_calls(aggregation_calls, aggregation_tokens);
+        }
+        AgentSearchStrategy::ParallelTree => {
+            estimate.add_calls(1, DEPENDENCY_TREE_TOKENS);
+            estimate.add_calls(pages, extraction_tokens);
+            estimate.add_calls(aggregation_calls, aggregation_tokens);
+            if search_input.inter_level_parallelism.unwrap_or(false) {
+                estimate.add_calls(aggregation_calls, aggregation_tokens);
+            }
+        }
+        AgentSearchStrategy::MultiQueryParallelTree => {
+            estimate.add_calls(1, QUERY_SYNTHESIS_TOKENS);
+            for _ in 0..ESTIMATED_SYNTHESIZED_QUERIES {
+                estimate.add_calls(1, DEPENDENCY_TREE_TOKENS);
+                estimate.add_calls(pages, extraction_tokens);
+                estimate.add_calls(aggregation_calls, aggregation_tokens);
+            }
+            estimate.add_calls(1, aggregation_tokens);
+        }
+    }
+}
+
+pub fn estimate_agent_search_cost(search_input: &AgentSearchInput) -> CostEstimate {
+    let mut estimate = CostEstimate {
+        model: default_model_name(),
+        ..CostEstimate::default()
+    };
+    if search_input.spell_check_query.unwrap_or(false) {
+        estimate.add_calls(1, QUERY_REWRITE_TOKENS);
+    }
+    if search_input
+        .auto_translate_query_to_english
+        .unwrap_or(false)
+    {
+        estimate.add_calls(1, QUERY_REWRITE_TOKENS);
+    }
+    let result_formats = search_input.requested_result_formats();
+    let query_strategy = if result_formats
+        .iter()
+        .any(|result_format| matches!(result_format, ResultFormat::ProsConsComparison))
+    {
+        QueryStrategy::Parallel
+    } else {
+        search_input.query_strategy.clone().unwrap_or_default()
+    };
+    let num_queries = estimate_query_synthesis(&mut estimate, &query_strategy);
+    let search_strategy = search_input.search_strategy.clone().unwrap_or_default();
+    for _ in 0..num_queries {
+        estimate_single_query_search(&mut estimate, search_input, &search_strategy);
+    }
+    if num_queries > 1 {
+        estimate.add_calls(1, DEDUPLICATION_TOKENS);
+    }
+    estimate.add_calls(result_formats.len() as u64, FORMATTING_TOKENS);
+    if search_input.suggest_follow_up_queries.unwrap_or(false) {
+        estimate.add_calls(1, FOLLOW_UP_QUERIES_TOKENS);
+    }
+    if let Some(max_llm_calls) = search_input.max_llm_calls {
+        let max_llm_calls = max_llm_calls as u64;
+        if estimate.estimated_llm_calls > max_llm_calls {
+            let scale = max_llm_calls as f64 / estimate.estimated_llm_calls as f64;
+            estimate.estimated_llm_calls = max_llm_calls;
+            estimate.estimated_prompt_tokens =
+                (estimate.estimated_prompt_tokens as f64 * scale).round() as u64;
+            estimate.estimated_completion_tokens =
+                (estimate.estimated_completion_tokens as f64 * scale).round() as u64;
+        }
+    }
+    estimate.estimated_cost_usd = model_pricing_or_fallback(&estimate.model).cost_usd(
+        estimate.estimated_prompt_tokens,
+        estimate.estimated_completion_tokens,
+    );
+    estimate
+}
@@ -0,0 +1,316 @@
+use crate::agent_search::agent_search;
+use crate::grpc_server::messages::{
+    AgentSearchRequest, AgentSearchResponse, ScrapeSiteRequest, ScrapeSiteResponse, SearchRequest,
+    SearchResponse,
+};
+use crate::grpc_server::protobuf::ProtoMessage;
+use crate::request_context::{current_request_id, with_request_context, RequestContext};
+use crate::scrape_site::scrape_site;
+use crate::search::search;
+use crate::server::ServerState;
+use bytes::{BufMut, Bytes, BytesMut};
+use http_body_util::{BodyExt, LengthLimitError, Limited, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::http2;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use rocket::State;
+use std::convert::Infallible;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::TcpListener;
+
+pub mod messages;
+pub mod protobuf;
+
+const DEFAULT_GRPC_PORT: u16 = 8098;
+const GRPC_SERVICE_PATH: &str = "/web_browser.v1.WebBrowser/";
+const GRPC_REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_GRPC_REQUEST_BYTES: usize = 4 * 1024 * 1024;
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+type GrpcBody =
+    StreamBody<futures::stream::Iter<std::vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+#[derive(Error, Debug)]
+pub enum GrpcServerError {
+    #[error("Failed to bind gRPC server to {0}: {1}")]
+    Bind(SocketAddr, std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrpcStatusCode {
+    Ok = 0,
+    InvalidArgument = 3,
+    ResourceExhausted = 8,
+    Unimplemented = 12,
+    Internal = 13,
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcStatus {
+    pub code: GrpcStatusCode,
+    pub message: String,
+}
+
+impl GrpcStatus {
+    fn invalid_argument(message: impl Into<String>) -> Self {
+        Self {
+            code: GrpcStatusCode::InvalidArgument,
+            message: message.into(),
+        }
+    }
+
+    fn internal(message: impl Into<String>) -> Self {
+        Self {
+            code: GrpcStatusCode::Internal,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn grpc_port_from_env() -> u16 {
+    env::var("GRPC_PORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_GRPC_PORT)
+}
+
+pub async fn run_grpc_server(
+    state: Arc<ServerState>,
+    address: IpAddr,
+    port: u16,
+) -> Result<(), GrpcServerError> {
+    let addr = SocketAddr::new(address, port);
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => return Err(GrpcServerError::Bind(addr, e)),
+    };
+    tracing::info!("gRPC server listening on {}", addr);
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::warn!("Failed to accept gRPC connection: {}", e);
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req: Request<Incoming>| {
+                let state = state.clone();
+                let context = RequestContext::from_request_id(
+                    req.headers()
+                        .get(GRPC_REQUEST_ID_HEADER)
+                        .and_then(|value| value.to_str().ok()),
+                );
+                async move {
+                    let request_id = context.request_id.clone();
+                    let mut response =
+                        with_request_context(context, handle_grpc_request(req, &state)).await;
+                    if let Ok(request_id) = HeaderValue::from_str(&request_id) {
+                        response
+                            .headers_mut()
+                            .insert(GRPC_REQUEST_ID_HEADER, request_id);
+                    }
+                    Ok::<_, Infallible>(response)
+                }
+            });
+            if let Err(e) = http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::warn!("gRPC connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_grpc_request(
+    req: Request<Incoming>,
+    state: &Arc<ServerState>,
+) -> Response<GrpcBody> {
+    if req.method() != Method::POST {
+        return grpc_response(Err(GrpcStatus {
+            code: GrpcStatusCode::Unimplemented,
+            message: format!("Unsupported method {}", req.method()),
+        }));
+    }
+    let method = match req.uri().path().strip_prefix(GRPC_SERVICE_PATH) {
+        Some(method) => method.to_string(),
+        None => {
+            return grpc_response(Err(GrpcStatus {
+                code: GrpcStatusCode::Unimplemented,
+                message: format!("Unknown service path {}", req.uri().path()),
+            }))
+        }
+    };
+    let body = match Limited::new(req.into_body(), MAX_GRPC_REQUEST_BYTES)
+        .collect()
+        .await
+    {
+        Ok(body) => body.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            return grpc_response(Err(GrpcStatus {
+                code: GrpcStatusCode::ResourceExhausted,
+                message: format!(
+                    "Request body exceeds the maximum size of {} bytes",
+                    MAX_GRPC_REQUEST_BYTES
+                ),
+            }))
+        }
+        Err(e) => {
+            return grpc_response(Err(GrpcStatus::invalid_argument(format!(
+                "Failed to read request body: {}",
+                e
+            ))))
+        }
+    };
+    let message = match decode_grpc_frame(&body) {
+        Ok(message) => message,
+        Err(status) => return grpc_response(Err(status)),
+    };
+    let result = match method.as_str() {
+        "Search" => handle_search(message, state).await,
+        "AgentSearch" => handle_agent_search(message, state).await,
+        "ScrapeSite" => handle_scrape_site(message, state).await,
+        _ => Err(GrpcStatus {
+            code: GrpcStatusCode::Unimplemented,
+            message: format!("Unknown method {}", method),
+        }),
+    };
+    grpc_response(result)
+}
+
+fn decode_request<M: ProtoMessage>(message: &[u8]) -> Result<M, GrpcStatus> {
+    match M::decode(message) {
+        Ok(request) => Ok(request),
+        Err(e) => Err(GrpcStatus::invalid_argument(format!(
+            "Failed to decode request: {}",
+            e
+        ))),
+    }
+}
+
+async fn handle_search(message: &[u8], state: &ServerState) -> Result<Vec<u8>, GrpcStatus> {
+    let request: SearchRequest = decode_request(message)?;
+    let input = match request.to_input() {
+        Ok(input) => input,
+        Err(e) => return Err(GrpcStatus::invalid_argument(e)),
+    };
+    match search(&input, &state.searx.client, &state.searx).await {
+        Ok(results) => Ok(SearchResponse {
+            results: results.iter().map(Into::into).collect(),
+        }
+        .encode_to_vec()),
+        Err(e) => Err(GrpcStatus::internal(e.to_string())),
+    }
+}
+
+async fn handle_agent_search(
+    message: &[u8],
+    state: &Arc<ServerState>,
+) -> Result<Vec<u8>, GrpcStatus> {
+    let request: AgentSearchRequest = decode_request(message)?;
+    let input = match request.to_input() {
+        Ok(input) => input,
+        Err(e) => return Err(GrpcStatus::invalid_argument(e)),
+    };
+    if input.result_formats.is_some() {
+        return Err(GrpcStatus::invalid_argument(
+            "result_formats is not supported over gRPC, use result_format",
+        ));
+    }
+    match agent_search(&input, State::from(state)).await {
+        Ok(result) => Ok(AgentSearchResponse::from(&result).encode_to_vec()),
+        Err(e) => Err(GrpcStatus::internal(e.to_string())),
+    }
+}
+
+async fn handle_scrape_site(message: &[u8], state: &ServerState) -> Result<Vec<u8>, GrpcStatus> {
+    let request: ScrapeSiteRequest = decode_request(message)?;
+    let input = match request.to_input() {
+        Ok(input) => input,
+        Err(e) => return Err(GrpcStatus::invalid_argument(e)),
+    };
+    match scrape_site(&input, State::from(state)).await {
+        Ok(results) => Ok(ScrapeSiteResponse {
+            results: results.iter().map(Into::into).collect(),
+        }
+        .encode_to_vec()),
+        Err(e) => Err(GrpcStatus::internal(e.to_string())),
+    }
+}
+
+fn decode_grpc_frame(body: &[u8]) -> Result<&[u8], GrpcStatus> {
+    if body.len() < 5 {
+        return Err(GrpcStatus::invalid_argument("Missing gRPC message frame"));
+    }
+    if body[0] != 0 {
+        return Err(GrpcStatus {
+            code: GrpcStatusCode::Unimplemented,
+            message: "Compressed gRPC messages are not supported".to_string(),
+        });
+    }
+    let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+    match body.get(5..5 + len) {
+        Some(message) => Ok(message),
+        None => Err(GrpcStatus::invalid_argument("Truncated gRPC message frame")),
+    }
+}
+
+fn encode_grpc_frame(message: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(5 + message.len());
+    frame.put_u8(0);
+    frame.put_u32(message.len() as u32);
+    frame.put_slice(message);
+    frame.freeze()
+}
+
+fn percent_encode_grpc_message(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        if (0x20..=0x7e).contains(&byte) && byte != b'%' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn grpc_response(result: Result<Vec<u8>, GrpcStatus>) -> Response<GrpcBody> {
+    let mut frames = Vec::new();
+    let mut trailers = HeaderMap::new();
+    match result {
+        Ok(message) => {
+            frames.push(Ok(Frame::data(encode_grpc_frame(&message))));
+            trailers.insert("grpc-status", HeaderValue::from(GrpcStatusCode::Ok as u16));
+        }
+        Err(status) => {
+            tracing::warn!(
+                request_id = %current_request_id(),
+                "gRPC request failed: {}",
+                status.message
+            );
+            trailers.insert("grpc-status", HeaderValue::from(status.code as u16));
+            if let Ok(message) =
+                HeaderValue::from_str(&percent_encode_grpc_message(&status.message))
+            {
+                trailers.insert("grpc-message", message);
+            }
+        }
+    }
+    frames.push(Ok(Frame::trailers(trailers)));
+    let mut response = Response::new(StreamBody::new(futures::stream::iter(frames)));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+    response
+}
@@ -0,0 +1,499 @@
+use crate::agent_search::{AgentSearchInput, AgentSearchResult};
+use crate::grpc_server::protobuf::{ProtoError, ProtoMessage, ProtoReader, ProtoWriter};
+use crate::result_format;
+use crate::scrape_site::{self, ScrapeSiteInput};
+use crate::search::{self, SearchInput};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+fn non_empty(values: &[String]) -> Option<&[String]> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+fn input_from_json<T: DeserializeOwned>(fields: Map<String, Value>) -> Result<T, String> {
+    let fields = fields
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .collect::<Map<_, _>>();
+    match serde_json::from_value(Value::Object(fields)) {
+        Ok(input) => Ok(input),
+        Err(e) => Err(format!("Invalid request: {}", e)),
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub content: String,
+    pub published_at: Option<String>,
+    pub modified_at: Option<String>,
+    pub link_depth: Option<u32>,
+    pub infobox: Option<String>,
+    pub anchor_text: Option<String>,
+}
+
+impl From<&search::SearchResult> for SearchResult {
+    fn from(result: &search::SearchResult) -> Self {
+        Self {
+            title: result.title.clone(),
+            url: result.url.clone(),
+            content: result.content.clone(),
+            published_at: result.published_at.clone(),
+            modified_at: result.modified_at.clone(),
+            link_depth: result.link_depth,
+            infobox: result.infobox.clone(),
+            anchor_text: result.anchor_text.clone(),
+        }
+    }
+}
+
+impl ProtoMessage for SearchResult {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        writer.string(1, &self.title);
+        writer.string(2, &self.url);
+        writer.string(3, &self.content);
+        writer.optional_string(4, self.published_at.as_deref());
+        writer.optional_string(5, self.modified_at.as_deref());
+        writer.optional_uint32(6, self.link_depth);
+        writer.optional_string(7, self.infobox.as_deref());
+        writer.optional_string(8, self.anchor_text.as_deref());
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.title = reader.string(field, wire_type)?,
+            2 => self.url = reader.string(field, wire_type)?,
+            3 => self.content = reader.string(field, wire_type)?,
+            4 => self.published_at = Some(reader.string(field, wire_type)?),
+            5 => self.modified_at = Some(reader.string(field, wire_type)?),
+            6 => self.link_depth = Some(reader.uint32(field, wire_type)?),
+            7 => self.infobox = Some(reader.string(field, wire_type)?),
+            8 => self.anchor_text = Some(reader.string(field, wire_type)?),
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SearchRequest {
+    pub query: String,
+    pub max_results_to_visit: Option<u32>,
+    pub whitelisted_base_urls: Vec<String>,
+    pub blacklisted_base_urls: Vec<String>,
+    pub safe_search: Option<String>,
+    pub language: Option<String>,
+}
+
+impl SearchRequest {
+    pub fn to_input(&self) -> Result<SearchInput, String> {
+        let fields = json!({
+            "query": self.query,
+            "max_results_to_visit": self.max_results_to_visit,
+            "whitelisted_base_urls": non_empty(&self.whitelisted_base_urls),
+            "blacklisted_base_urls": non_empty(&self.blacklisted_base_urls),
+            "safe_search": self.safe_search,
+            "language": self.language,
+        });
+        match fields {
+            Value::Object(fields) => input_from_json(fields),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ProtoMessage for SearchRequest {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        writer.string(1, &self.query);
+        writer.optional_uint32(2, self.max_results_to_visit);
+        writer.repeated_string(3, &self.whitelisted_base_urls);
+        writer.repeated_string(4, &self.blacklisted_base_urls);
+        writer.optional_string(5, self.safe_search.as_deref());
+        writer.optional_string(6, self.language.as_deref());
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.query = reader.string(field, wire_type)?,
+            2 => self.max_results_to_visit = Some(reader.uint32(field, wire_type)?),
+            3 => self
+                .whitelisted_base_urls
+                .push(reader.string(field, wire_type)?),
+            4 => self
+                .blacklisted_base_urls
+                .push(reader.string(field, wire_type)?),
+            5 => self.safe_search = Some(reader.string(field, wire_type)?),
+            6 => self.language = Some(reader.string(field, wire_type)?),
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+}
+
+impl ProtoMessage for SearchResponse {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        writer.repeated_message(1, &self.results);
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.results.push(reader.message(field, wire_type)?),
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AgentSearchRequest {
+    pub query: String,
+    pub search_strategy: Option<String>,
+    pub query_strategy: Option<String>,
+    pub max_results_to_visit: Option<u32>,
+    pub result_format: Option<String>,
+    pub custom_result_format_description: Option<String>,
+    pub whitelisted_base_urls: Vec<String>,
+    pub blacklisted_base_urls: Vec<String>,
+    pub safe_search: Option<String>,
+    pub options_json: Option<String>,
+}
+
+impl AgentSearchRequest {
+    pub fn to_input(&self) -> Result<AgentSearchInput, String> {
+        let mut fields = match self.options_json.as_deref() {
+            Some(options_json) => match serde_json::from_str::<Value>(options_json) {
+                Ok(Value::Object(options)) => options,
+                Ok(_) => return Err("options_json must be a JSON object".to_string()),
+                Err(e) => return Err(format!("Invalid options_json: {}", e)),
+            },
+            None => Map::new(),
+        };
+        let typed_fields = [
+            ("query", json!(self.query)),
+            ("search_strategy", json!(self.search_strategy)),
+            ("query_strategy", json!(self.query_strategy)),
+            ("max_results_to_visit", json!(self.max_results_to_visit)),
+            ("result_format", json!(self.result_format)),
+            (
+                "custom_result_format_description",
+                json!(self.custom_result_format_description),
+            ),
+            (
+                "whitelisted_base_urls",
+                json!(non_empty(&self.whitelisted_base_urls)),
+            ),
+            (
+                "blacklisted_base_urls",
+                json!(non_empty(&self.blacklisted_base_urls)),
+            ),
+            ("safe_search", json!(self.safe_search)),
+        ];
+        for (name, value) in typed_fields {
+            if !value.is_null() {
+                fields.insert(name.to_string(), value);
+            }
+        }
+        input_from_json(fields)
+    }
+}
+
+impl ProtoMessage for AgentSearchRequest {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        writer.string(1, &self.query);
+        writer.optional_string(2, self.search_strategy.as_deref());
+        writer.optional_string(3, self.query_strategy.as_deref());
+        writer.optional_uint32(4, self.max_results_to_visit);
+        writer.optional_string(5, self.result_format.as_deref());
+        writer.optional_string(6, self.custom_result_format_description.as_deref());
+        writer.repeated_string(7, &self.whitelisted_base_urls);
+        writer.repeated_string(8, &self.blacklisted_base_urls);
+        writer.optional_string(9, self.safe_search.as_deref());
+        writer.optional_string(10, self.options_json.as_deref());
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.query = reader.string(field, wire_type)?,
+            2 => self.search_strategy = Some(reader.string(field, wire_type)?),
+            3 => self.query_strategy = Some(reader.string(field, wire_type)?),
+            4 => self.max_results_to_visit = Some(reader.uint32(field, wire_type)?),
+            5 => self.result_format = Some(reader.string(field, wire_type)?),
+            6 => self.custom_result_format_description = Some(reader.string(field, wire_type)?),
+            7 => self
+                .whitelisted_base_urls
+                .push(reader.string(field, wire_type)?),
+            8 => self
+                .blacklisted_base_urls
+                .push(reader.string(field, wire_type)?),
+            9 => self.safe_search = Some(reader.string(field, wire_type)?),
+            10 => self.options_json = Some(reader.string(field, wire_type)?),
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AnalysisDocument {
+    pub content: String,
+    pub visited_results: Vec<SearchResult>,
+    pub unvisited_results: Vec<SearchResult>,
+}
+
+impl From<&result_format::AnalysisDocument> for AnalysisDocument {
+    fn from(analysis: &result_format::AnalysisDocument) -> Self {
+        Self {
+            content: analysis.content.clone(),
+            visited_results: analysis.visited_results.iter().map(Into::into).collect(),
+            unvisited_results: analysis.unvisited_results.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl ProtoMessage for AnalysisDocument {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        writer.string(1, &self.content);
+        writer.repeated_message(2, &self.visited_results);
+        writer.repeated_message(3, &self.unvisited_results);
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.content = reader.string(field, wire_type)?,
+            2 => self.visited_results.push(reader.message(field, wire_type)?),
+            3 => self
+                .unvisited_results
+                .push(reader.message(field, wire_type)?),
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AgentSearchResponse {
+    pub raw_analysis: Option<AnalysisDocument>,
+    pub queries_executed: Vec<String>,
+    pub response_json: String,
+    pub truncated_due_to_llm_budget: bool,
+    pub suggested_follow_up_queries: Vec<String>,
+    pub total_prompt_tokens: u32,
+    pub total_completion_tokens: u32,
+}
+
+impl From<&AgentSearchResult> for AgentSearchResponse {
+    fn from(result: &AgentSearchResult) -> Self {
+        Self {
+            raw_analysis: Some((&result.raw_analysis).into()),
+            queries_executed: result
+                .queries_executed
+                .iter()
+                .map(|record| record.query.clone())
+                .collect(),
+            response_json: serde_json::to_string(&result.response).unwrap_or_default(),
+            truncated_due_to_llm_budget: result.truncated_due_to_llm_budget,
+            suggested_follow_up_queries: result
+                .suggested_follow_up_queries
+                .clone()
+                .unwrap_or_default(),
+            total_prompt_tokens: result.usage.total_prompt_tokens,
+            total_completion_tokens: result.usage.total_completion_tokens,
+        }
+    }
+}
+
+impl ProtoMessage for AgentSearchResponse {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        if let Some(raw_analysis) = &self.raw_analysis {
+            writer.message(1, raw_analysis);
+        }
+        writer.repeated_string(2, &self.queries_executed);
+        writer.string(3, &self.response_json);
+        writer.bool(4, self.truncated_due_to_llm_budget);
+        writer.repeated_string(5, &self.suggested_follow_up_queries);
+        writer.uint32(6, self.total_prompt_tokens);
+        writer.uint32(7, self.total_completion_tokens);
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.raw_analysis = Some(reader.message(field, wire_type)?),
+            2 => self.queries_executed.push(reader.string(field, wire_type)?),
+            3 => self.response_json = reader.string(field, wire_type)?,
+            4 => self.truncated_due_to_llm_budget = reader.bool(field, wire_type)?,
+            5 => self
+                .suggested_follow_up_queries
+                .push(reader.string(field, wire_type)?),
+            6 => self.total_prompt_tokens = reader.uint32(field, wire_type)?,
+            7 => self.total_completion_tokens = reader.uint32(field, wire_type)?,
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ScrapeSiteRequest {
+    pub base_url: String,
+    pub max_num_pages_to_visit: Option<u32>,
+    pub result_format: Option<String>,
+    pub max_concurrency: Option<u32>,
+    pub explicit_urls_to_visit: Vec<String>,
+    pub html_strip_tags: Vec<String>,
+    pub html_keep_attributes: Vec<String>,
+}
+
+impl ScrapeSiteRequest {
+    pub fn to_input(&self) -> Result<ScrapeSiteInput, String> {
+        let fields = json!({
+            "base_url": self.base_url,
+            "max_num_pages_to_visit": self.max_num_pages_to_visit,
+            "result_format": self.result_format,
+            "max_concurrency": self.max_concurrency,
+            "explicit_urls_to_visit": non_empty(&self.explicit_urls_to_visit),
+            "html_strip_tags": non_empty(&self.html_strip_tags),
+            "html_keep_attributes": non_empty(&self.html_keep_attributes),
+        });
+        match fields {
+            Value::Object(fields) => input_from_json(fields),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ProtoMessage for ScrapeSiteRequest {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        writer.string(1, &self.base_url);
+        writer.optional_uint32(2, self.max_num_pages_to_visit);
+        writer.optional_string(3, self.result_format.as_deref());
+        writer.optional_uint32(4, self.max_concurrency);
+        writer.repeated_string(5, &self.explicit_urls_to_visit);
+        writer.repeated_string(6, &self.html_strip_tags);
+        writer.repeated_string(7, &self.html_keep_attributes);
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.base_url = reader.string(field, wire_type)?,
+            2 => self.max_num_pages_to_visit = Some(reader.uint32(field, wire_type)?),
+            3 => self.result_format = Some(reader.string(field, wire_type)?),
+            4 => self.max_concurrency = Some(reader.uint32(field, wire_type)?),
+            5 => self
+                .explicit_urls_to_visit
+                .push(reader.string(field, wire_type)?),
+            6 => self.html_strip_tags.push(reader.string(field, wire_type)?),
+            7 => self
+                .html_keep_attributes
+                .push(reader.string(field, wire_type)?),
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ScrapeSiteResult {
+    pub search_result: Option<SearchResult>,
+    pub formatted_content: String,
+}
+
+impl From<&scrape_site::ScrapeSiteResult> for ScrapeSiteResult {
+    fn from(result: &scrape_site::ScrapeSiteResult) -> Self {
+        Self {
+            search_result: Some((&result.search_result).into()),
+            formatted_content: result.formatted_content.clone(),
+        }
+    }
+}
+
+impl ProtoMessage for ScrapeSiteResult {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        if let Some(search_result) = &self.search_result {
+            writer.message(1, search_result);
+        }
+        writer.string(2, &self.formatted_content);
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.search_result = Some(reader.message(field, wire_type)?),
+            2 => self.formatted_content = reader.string(field, wire_type)?,
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ScrapeSiteResponse {
+    pub results: Vec<ScrapeSiteResult>,
+}
+
+impl ProtoMessage for ScrapeSiteResponse {
+    fn encode(&self, writer: &mut ProtoWriter) {
+        writer.repeated_message(1, &self.results);
+    }
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError> {
+        match field {
+            1 => self.results.push(reader.message(field, wire_type)?),
+            _ => reader.skip(wire_type)?,
+        }
+        Ok(())
+    }
+}
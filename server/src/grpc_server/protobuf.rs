@@ -0,0 +1,229 @@
+use thiserror::Error;
+
+pub const WIRE_TYPE_VARINT: u8 = 0;
+pub const WIRE_TYPE_FIXED64: u8 = 1;
+pub const WIRE_TYPE_LENGTH_DELIMITED: u8 = 2;
+pub const WIRE_TYPE_FIXED32: u8 = 5;
+
+#[derive(Error, Debug)]
+pub enum ProtoError {
+    #[error("Unexpected end of message")]
+    UnexpectedEnd,
+    #[error("Varint is too long")]
+    VarintTooLong,
+    #[error("Unsupported wire type {0}")]
+    UnsupportedWireType(u8),
+    #[error("Field {field} has wire type {wire_type}, expected {expected}")]
+    WrongWireType {
+        field: u32,
+        wire_type: u8,
+        expected: u8,
+    },
+    #[error("Field {0} is not valid UTF-8")]
+    InvalidUtf8(u32),
+}
+
+#[derive(Default)]
+pub struct ProtoWriter {
+    buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.varint(((field as u64) << 3) | wire_type as u64);
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.key(field, WIRE_TYPE_LENGTH_DELIMITED);
+        self.varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn string(&mut self, field: u32, value: &str) {
+        if !value.is_empty() {
+            self.bytes(field, value.as_bytes());
+        }
+    }
+
+    pub fn optional_string(&mut self, field: u32, value: Option<&str>) {
+        if let Some(value) = value {
+            self.bytes(field, value.as_bytes());
+        }
+    }
+
+    pub fn repeated_string(&mut self, field: u32, values: &[String]) {
+        for value in values {
+            self.bytes(field, value.as_bytes());
+        }
+    }
+
+    pub fn uint32(&mut self, field: u32, value: u32) {
+        if value != 0 {
+            self.key(field, WIRE_TYPE_VARINT);
+            self.varint(value as u64);
+        }
+    }
+
+    pub fn optional_uint32(&mut self, field: u32, value: Option<u32>) {
+        if let Some(value) = value {
+            self.key(field, WIRE_TYPE_VARINT);
+            self.varint(value as u64);
+        }
+    }
+
+    pub fn bool(&mut self, field: u32, value: bool) {
+        if value {
+            self.key(field, WIRE_TYPE_VARINT);
+            self.varint(1);
+        }
+    }
+
+    pub fn message<M: ProtoMessage>(&mut self, field: u32, message: &M) {
+        self.bytes(field, &message.encode_to_vec());
+    }
+
+    pub fn repeated_message<M: ProtoMessage>(&mut self, field: u32, messages: &[M]) {
+        for message in messages {
+            self.message(field, message);
+        }
+    }
+}
+
+pub struct ProtoReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, ProtoError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = match self.buf.get(self.pos) {
+                Some(byte) => *byte,
+                None => return Err(ProtoError::UnexpectedEnd),
+            };
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ProtoError::VarintTooLong)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProtoError> {
+        let end = match self.pos.checked_add(len) {
+            Some(end) if end <= self.buf.len() => end,
+            _ => return Err(ProtoError::UnexpectedEnd),
+        };
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn next_field(&mut self) -> Result<Option<(u32, u8)>, ProtoError> {
+        if self.pos >= self.buf.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        Ok(Some(((key >> 3) as u32, (key & 0x7) as u8)))
+    }
+
+    fn expect_wire_type(field: u32, wire_type: u8, expected: u8) -> Result<(), ProtoError> {
+        if wire_type != expected {
+            return Err(ProtoError::WrongWireType {
+                field,
+                wire_type,
+                expected,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn bytes(&mut self, field: u32, wire_type: u8) -> Result<&'a [u8], ProtoError> {
+        Self::expect_wire_type(field, wire_type, WIRE_TYPE_LENGTH_DELIMITED)?;
+        let len = self.varint()? as usize;
+        self.take(len)
+    }
+
+    pub fn string(&mut self, field: u32, wire_type: u8) -> Result<String, ProtoError> {
+        let bytes = self.bytes(field, wire_type)?;
+        match std::str::from_utf8(bytes) {
+            Ok(value) => Ok(value.to_string()),
+            Err(_) => Err(ProtoError::InvalidUtf8(field)),
+        }
+    }
+
+    pub fn uint32(&mut self, field: u32, wire_type: u8) -> Result<u32, ProtoError> {
+        Self::expect_wire_type(field, wire_type, WIRE_TYPE_VARINT)?;
+        Ok(self.varint()? as u32)
+    }
+
+    pub fn bool(&mut self, field: u32, wire_type: u8) -> Result<bool, ProtoError> {
+        Self::expect_wire_type(field, wire_type, WIRE_TYPE_VARINT)?;
+        Ok(self.varint()? != 0)
+    }
+
+    pub fn message<M: ProtoMessage>(&mut self, field: u32, wire_type: u8) -> Result<M, ProtoError> {
+        M::decode(self.bytes(field, wire_type)?)
+    }
+
+    pub fn skip(&mut self, wire_type: u8) -> Result<(), ProtoError> {
+        match wire_type {
+            WIRE_TYPE_VARINT => self.varint().map(|_| ()),
+            WIRE_TYPE_FIXED64 => self.take(8).map(|_| ()),
+            WIRE_TYPE_LENGTH_DELIMITED => {
+                let len = self.varint()? as usize;
+                self.take(len).map(|_| ())
+            }
+            WIRE_TYPE_FIXED32 => self.take(4).map(|_| ()),
+            wire_type => Err(ProtoError::UnsupportedWireType(wire_type)),
+        }
+    }
+}
+
+pub trait ProtoMessage: Default {
+    fn encode(&self, writer: &mut ProtoWriter);
+
+    fn merge_field(
+        &mut self,
+        field: u32,
+        wire_type: u8,
+        reader: &mut ProtoReader,
+    ) -> Result<(), ProtoError>;
+
+    fn encode_to_vec(&self) -> Vec<u8> {
+        let mut writer = ProtoWriter::new();
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, ProtoError> {
+        let mut message = Self::default();
+        let mut reader = ProtoReader::new(bytes);
+        while let Some((field, wire_type)) = reader.next_field()? {
+            message.merge_field(field, wire_type, &mut reader)?;
+        }
+        Ok(message)
+    }
+}
@@ -0,0 +1,32 @@
+use crate::request_context::{with_request_context, RequestContext};
+use crate::search_diff::{diff_search_results, SearchDiff, SearchDiffError, SearchDiffInput};
+use rocket::http::Status;
+use rocket::post;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchDiffErrorResponse {
+    pub message: String,
+    pub error_type: String,
+}
+
+#[post("/search/diff", data = "<diff_input>")]
+pub async fn handle_search_diff(
+    context: RequestContext,
+    diff_input: Json<SearchDiffInput>,
+) -> Result<Json<SearchDiff>, (Status, Json<SearchDiffErrorResponse>)> {
+    match with_request_context(context, diff_search_results(&diff_input)).await {
+        Ok(diff) => Ok(Json(diff)),
+        Err(e) => Err((
+            Status::BadRequest,
+            Json(SearchDiffErrorResponse {
+                message: e.to_string(),
+                error_type: match e {
+                    SearchDiffError::LLMError(_) => "llm_error".to_string(),
+                    SearchDiffError::SerializationError(_) => "serialization_error".to_string(),
+                },
+            }),
+        )),
+    }
+}
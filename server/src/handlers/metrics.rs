@@ -0,0 +1,37 @@
+use crate::server::ServerState;
+use rocket::get;
+use rocket::http::ContentType;
+use rocket::State;
+use std::sync::Arc;
+
+#[get("/metrics")]
+pub fn handle_metrics(state: &State<Arc<ServerState>>) -> (ContentType, String) {
+    let cache_stats = state.completion_cache.lock().unwrap().stats();
+    let metrics = format!(
+        "# HELP llm_completion_cache_hits_total LLM completions served from the cache.
+# TYPE llm_completion_cache_hits_total counter
+llm_completion_cache_hits_total {}
+# HELP llm_completion_cache_misses_total LLM completions not found in the cache.
+# TYPE llm_completion_cache_misses_total counter
+llm_completion_cache_misses_total {}
+# HELP llm_completion_cache_hit_rate Fraction of cache lookups that were hits.
+# TYPE llm_completion_cache_hit_rate gauge
+llm_completion_cache_hit_rate {}
+# HELP llm_completion_cache_entries Number of completions in the cache.
+# TYPE llm_completion_cache_entries gauge
+llm_completion_cache_entries {}
+# HELP llm_completion_cache_capacity Maximum number of completions in the cache.
+# TYPE llm_completion_cache_capacity gauge
+llm_completion_cache_capacity {}
+",
+        cache_stats.hits,
+        cache_stats.misses,
+        cache_stats.hit_rate(),
+        cache_stats.entries,
+        cache_stats.capacity
+    );
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        metrics,
+    )
+}
@@ -1 +1,5 @@
+pub mod diff;
+pub mod metrics;
+pub mod openapi;
+pub mod site_search;
 pub mod v1;
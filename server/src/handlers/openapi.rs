@@ -0,0 +1,77 @@
+use rocket::get;
+use rocket::http::ContentType;
+use rocket::response::content::RawHtml;
+use rocket::{Build, Rocket};
+use std::collections::HashSet;
+
+const OPENAPI_SPEC: &str = include_str!("../../openapi.json");
+
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>web-browser API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+#[get("/openapi.json")]
+pub fn handle_openapi_spec() -> (ContentType, &'static str) {
+    (ContentType::JSON, OPENAPI_SPEC)
+}
+
+#[get("/swagger-ui")]
+pub fn handle_swagger_ui() -> RawHtml<&'static str> {
+    RawHtml(SWAGGER_UI_HTML)
+}
+
+pub fn check_openapi_spec_routes(rocket: &Rocket<Build>) {
+    let spec: serde_json::Value = match serde_json::from_str(OPENAPI_SPEC) {
+        Ok(spec) => spec,
+        Err(e) => {
+            tracing::warn!("Failed to parse the OpenAPI spec: {}", e);
+            return;
+        }
+    };
+    let documented_routes = spec["paths"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .flat_map(|(path, operations)| {
+            operations
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(move |(method, _)| (method.to_uppercase(), path.clone()))
+        })
+        .collect::<HashSet<_>>();
+    let mounted_routes = rocket
+        .routes()
+        .map(|route| {
+            (
+                route.method.as_str().to_string(),
+                route.uri.path().to_string(),
+            )
+        })
+        .collect::<HashSet<_>>();
+    for (method, path) in mounted_routes.difference(&documented_routes) {
+        tracing::warn!("Route {} {} is missing from the OpenAPI spec", method, path);
+    }
+    for (method, path) in documented_routes.difference(&mounted_routes) {
+        tracing::warn!(
+            "OpenAPI spec documents {} {}, which is not mounted",
+            method,
+            path
+        );
+    }
+}
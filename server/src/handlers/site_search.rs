@@ -0,0 +1,83 @@
+use crate::agent_search::{agent_search_from_pages, AgentSearchInput, AgentSearchResult};
+use crate::request_context::{with_request_context, RequestContext};
+use crate::result_format::ResultFormat;
+use crate::scrape_site::{scrape_site, ScrapeSiteInput, ScrapeSiteResultFormat};
+use crate::server::ServerState;
+use rocket::http::Status;
+use rocket::post;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const DEFAULT_SITE_SEARCH_MAX_PAGES: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SiteSearchInput {
+    pub base_url: String,
+    pub query: String,
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+    #[serde(default)]
+    pub result_format: Option<ResultFormat>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SiteSearchErrorResponse {
+    pub message: String,
+    pub error_type: String,
+}
+
+#[post("/site_search", data = "<site_search_input>")]
+pub async fn handle_site_search(
+    state: &State<Arc<ServerState>>,
+    context: RequestContext,
+    site_search_input: Json<SiteSearchInput>,
+) -> Result<Json<AgentSearchResult>, (Status, Json<SiteSearchErrorResponse>)> {
+    let max_pages = site_search_input
+        .max_pages
+        .unwrap_or(DEFAULT_SITE_SEARCH_MAX_PAGES);
+    let scrape_input = ScrapeSiteInput {
+        base_url: site_search_input.base_url.clone(),
+        max_num_pages_to_visit: Some(max_pages),
+        result_format: Some(ScrapeSiteResultFormat::Html),
+        max_concurrency: None,
+        explicit_urls_to_visit: None,
+        html_strip_tags: None,
+        html_keep_attributes: None,
+        user_agent: None,
+    };
+    let search_input = AgentSearchInput {
+        query: site_search_input.query.clone(),
+        max_results_to_visit: Some(max_pages),
+        result_format: site_search_input.result_format.clone(),
+        ..AgentSearchInput::default()
+    };
+    let search = async {
+        let pages = match scrape_site(&scrape_input, state).await {
+            Ok(pages) => pages,
+            Err(e) => return Err((e.to_string(), "scrape_site_error")),
+        };
+        {
+            let mut page_index = state.page_index.write().unwrap();
+            for page in &pages {
+                page_index.index(&page.search_result.url, &page.formatted_content);
+            }
+        }
+        let pages = pages.into_iter().map(|page| page.search_result).collect();
+        match agent_search_from_pages(&search_input, pages, state).await {
+            Ok(result) => Ok(result),
+            Err(e) => Err((e.to_string(), "search_error")),
+        }
+    };
+    match with_request_context(context, search).await {
+        Ok(result) => Ok(Json(result)),
+        Err((message, error_type)) => Err((
+            Status::BadRequest,
+            Json(SiteSearchErrorResponse {
+                message,
+                error_type: error_type.to_string(),
+            }),
+        )),
+    }
+}
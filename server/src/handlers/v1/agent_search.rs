@@ -1,10 +1,17 @@
-use crate::agent_search::{agent_search, AgentSearchInput, AgentSearchResult};
-use crate::server::ServerState;
+use crate::agent_search::{
+    agent_search, agent_search_multi_format, AgentSearchInput, AgentSearchMultiFormatResult,
+    AgentSearchResult,
+};
+use crate::request_context::{with_request_context, RequestContext};
+use crate::result_format::ResultFormatResponse;
+use crate::server::{InFlightRequests, ServerState};
 use rocket::http::Status;
 use rocket::post;
+use rocket::response::{self, Responder};
 use rocket::serde::json::Json;
-use rocket::State;
+use rocket::{Request, State};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AgentSearchErrorResponse {
@@ -12,13 +19,57 @@ pub struct AgentSearchErrorResponse {
     pub error_type: String,
 }
 
+pub enum AgentSearchResponse {
+    Single(AgentSearchResult),
+    MultiFormat(AgentSearchMultiFormatResult),
+}
+
+impl<'r> Responder<'r, 'static> for AgentSearchResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let (is_dry_run, response) = match self {
+            AgentSearchResponse::Single(result) => (
+                matches!(result.response, ResultFormatResponse::DryRun(_)),
+                Json(result).respond_to(request),
+            ),
+            AgentSearchResponse::MultiFormat(result) => (
+                result
+                    .responses
+                    .iter()
+                    .any(|response| matches!(response, ResultFormatResponse::DryRun(_))),
+                Json(result).respond_to(request),
+            ),
+        };
+        let mut response = response?;
+        if is_dry_run {
+            response.set_raw_header("X-Dry-Run", "true");
+        }
+        Ok(response)
+    }
+}
+
 #[post("/agent_search", data = "<search_input>")]
 pub async fn handle_agent_search(
-    state: &State<ServerState>,
+    state: &State<Arc<ServerState>>,
+    context: RequestContext,
     search_input: Json<AgentSearchInput>,
-) -> Result<Json<AgentSearchResult>, (Status, Json<AgentSearchErrorResponse>)> {
-    match agent_search(&search_input, &state.searx_host, &state.searx_port).await {
-        Ok(result) => Ok(Json(result)),
+) -> Result<AgentSearchResponse, (Status, Json<AgentSearchErrorResponse>)> {
+    let search = async {
+        if search_input.result_formats.is_some() {
+            agent_search_multi_format(&search_input, state)
+                .await
+                .map(AgentSearchResponse::MultiFormat)
+        } else {
+            let key = InFlightRequests::request_key(&search_input);
+            state
+                .in_flight_requests
+                .deduplicate(key, agent_search(&search_input, state))
+                .await
+                .map(AgentSearchResponse::Single)
+        }
+    };
+    let result = with_request_context(context, search).await;
+    match result {
+        Ok(response) => Ok(response),
         Err(e) => Err((
             Status::BadRequest,
             Json(AgentSearchErrorResponse {
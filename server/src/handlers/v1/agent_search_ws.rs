@@ -0,0 +1,142 @@
+use crate::agent_search::{agent_search, AgentSearchInput, AgentSearchResult};
+use crate::request_context::{current_request_id, with_request_context, RequestContext};
+use crate::server::ServerState;
+use crate::session::{with_search_session, SearchProgress, SearchSession};
+use crate::websocket::{WebSocket, WebSocketChannel, WebSocketMessage, WebSocketStream};
+use rocket::{get, State};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type")]
+pub enum AgentSearchServerMessage {
+    #[serde(rename = "progress")]
+    Progress {
+        url_visited: String,
+        analysis_so_far: String,
+    },
+    #[serde(rename = "complete")]
+    Complete { result: Box<AgentSearchResult> },
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum AgentSearchClientMessage {
+    #[serde(rename = "cancel")]
+    Cancel,
+    #[serde(rename = "skip_url")]
+    SkipUrl { url: String },
+}
+
+impl From<SearchProgress> for AgentSearchServerMessage {
+    fn from(progress: SearchProgress) -> Self {
+        AgentSearchServerMessage::Progress {
+            url_visited: progress.url_visited,
+            analysis_so_far: progress.analysis_so_far,
+        }
+    }
+}
+
+#[get("/ws/agent_search")]
+pub fn handle_agent_search_ws(
+    websocket: WebSocket,
+    state: &State<Arc<ServerState>>,
+    context: RequestContext,
+) -> WebSocketChannel {
+    let state = state.inner().clone();
+    websocket.channel(move |stream| {
+        with_request_context(context, run_agent_search_session(stream, state))
+    })
+}
+
+async fn run_agent_search_session(
+    stream: WebSocketStream,
+    state: Arc<ServerState>,
+) -> std::io::Result<()> {
+    let (mut reader, mut writer) = stream.split();
+    let search_input = loop {
+        match reader.recv().await? {
+            WebSocketMessage::Text(text) => match serde_json::from_str::<AgentSearchInput>(&text) {
+                Ok(search_input) => break search_input,
+                Err(e) => {
+                    let message = AgentSearchServerMessage::Error {
+                        message: format!("Invalid agent search input: {}", e),
+                    };
+                    writer.send_text(&serde_json::to_string(&message)?).await?;
+                    return writer.close().await;
+                }
+            },
+            WebSocketMessage::Ping(payload) => writer.send_pong(&payload).await?,
+            WebSocketMessage::Close => return writer.close().await,
+            WebSocketMessage::Binary(_) | WebSocketMessage::Pong(_) => {}
+        }
+    };
+
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let session = SearchSession::new(progress_tx);
+    let (incoming_tx, mut incoming_rx) = mpsc::unbounded_channel();
+    let reader_task = tokio::spawn(async move {
+        while let Ok(message) = reader.recv().await {
+            let is_close = message == WebSocketMessage::Close;
+            if incoming_tx.send(message).is_err() || is_close {
+                break;
+            }
+        }
+    });
+
+    let search = with_search_session(session.clone(), agent_search(&search_input, &state));
+    tokio::pin!(search);
+    let mut client_closed = false;
+    let result = loop {
+        tokio::select! {
+            result = &mut search => break result,
+            Some(progress) = progress_rx.recv() => {
+                if !client_closed {
+                    let message = AgentSearchServerMessage::from(progress);
+                    writer.send_text(&serde_json::to_string(&message)?).await?;
+                }
+            }
+            Some(message) = incoming_rx.recv() => match message {
+                WebSocketMessage::Text(text) => {
+                    match serde_json::from_str::<AgentSearchClientMessage>(&text) {
+                        Ok(AgentSearchClientMessage::Cancel) => session.cancel(),
+                        Ok(AgentSearchClientMessage::SkipUrl { url }) => session.skip_url(&url),
+                        Err(e) => tracing::warn!(
+                            request_id = %current_request_id(),
+                            "Ignoring invalid agent search message: {}",
+                            e
+                        ),
+                    }
+                }
+                WebSocketMessage::Ping(payload) => writer.send_pong(&payload).await?,
+                WebSocketMessage::Close => {
+                    client_closed = true;
+                    session.cancel();
+                }
+                WebSocketMessage::Binary(_) | WebSocketMessage::Pong(_) => {}
+            },
+        }
+    };
+    reader_task.abort();
+    if client_closed {
+        return writer.close().await;
+    }
+
+    while let Ok(progress) = progress_rx.try_recv() {
+        let message = AgentSearchServerMessage::from(progress);
+        writer.send_text(&serde_json::to_string(&message)?).await?;
+    }
+    let message = match result {
+        Ok(result) => AgentSearchServerMessage::Complete {
+            result: Box::new(result),
+        },
+        Err(e) => AgentSearchServerMessage::Error {
+            message: e.to_string(),
+        },
+    };
+    writer.send_text(&serde_json::to_string(&message)?).await?;
+    writer.close().await
+}
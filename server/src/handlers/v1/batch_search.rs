@@ -0,0 +1,17 @@
+use crate::request_context::{with_request_context, RequestContext};
+use crate::search::{batch_search, BatchSearchInput, BatchSearchResult};
+use crate::server::ServerState;
+use rocket::post;
+use rocket::serde::json::Json;
+use rocket::State;
+use std::sync::Arc;
+
+#[post("/batch_search", data = "<batch_search_input>")]
+pub async fn handle_batch_search(
+    state: &State<Arc<ServerState>>,
+    context: RequestContext,
+    batch_search_input: Json<BatchSearchInput>,
+) -> Json<Vec<BatchSearchResult>> {
+    let batch_search = batch_search(&batch_search_input, &state.searx.client, &state.searx);
+    Json(with_request_context(context, batch_search).await)
+}
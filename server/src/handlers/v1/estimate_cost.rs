@@ -0,0 +1,9 @@
+use crate::agent_search::AgentSearchInput;
+use crate::cost_estimate::{estimate_agent_search_cost, CostEstimate};
+use rocket::post;
+use rocket::serde::json::Json;
+
+#[post("/estimate_cost", data = "<search_input>")]
+pub async fn handle_estimate_cost(search_input: Json<AgentSearchInput>) -> Json<CostEstimate> {
+    Json(estimate_agent_search_cost(&search_input))
+}
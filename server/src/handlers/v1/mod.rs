@@ -1,3 +1,6 @@
 pub mod agent_search;
+pub mod agent_search_ws;
+pub mod batch_search;
+pub mod estimate_cost;
 pub mod scrape_site;
 pub mod search;
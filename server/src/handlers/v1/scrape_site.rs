@@ -1,3 +1,4 @@
+use crate::request_context::{with_request_context, RequestContext};
 use crate::scrape_site::{scrape_site, ScrapeSiteError, ScrapeSiteInput, ScrapeSiteResult};
 use crate::server::ServerState;
 use rocket::http::Status;
@@ -5,6 +6,7 @@ use rocket::post;
 use rocket::serde::json::Json;
 use rocket::State;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ScrapeSiteResponse {
@@ -19,10 +21,11 @@ pub struct ScrapeSiteErrorResponse {
 
 #[post("/scrape_site", data = "<scrape_site_input>")]
 pub async fn handle_scrape_site(
-    state: &State<ServerState>,
+    state: &State<Arc<ServerState>>,
+    context: RequestContext,
     scrape_site_input: Json<ScrapeSiteInput>,
 ) -> Result<Json<ScrapeSiteResponse>, (Status, Json<ScrapeSiteErrorResponse>)> {
-    match scrape_site(&scrape_site_input, &state.searx_host, &state.searx_port).await {
+    match with_request_context(context, scrape_site(&scrape_site_input, state)).await {
         Ok(results) => Ok(Json(ScrapeSiteResponse { results })),
         Err(e) => Err((
             Status::BadRequest,
@@ -1,3 +1,4 @@
+use crate::request_context::{with_request_context, RequestContext};
 use crate::search::{search, SearchError, SearchInput, SearchResult};
 use crate::server::ServerState;
 use rocket::http::Status;
@@ -5,6 +6,7 @@ use rocket::post;
 use rocket::serde::json::Json;
 use rocket::State;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchErrorResponse {
@@ -14,10 +16,12 @@ pub struct SearchErrorResponse {
 
 #[post("/search", data = "<search_input>")]
 pub async fn handle_search(
-    state: &State<ServerState>,
+    state: &State<Arc<ServerState>>,
+    context: RequestContext,
     search_input: Json<SearchInput>,
 ) -> Result<Json<Vec<SearchResult>>, (Status, Json<SearchErrorResponse>)> {
-    match search(&search_input, &state.searx_host, &state.searx_port).await {
+    let search = search(&search_input, &state.searx.client, &state.searx);
+    match with_request_context(context, search).await {
         Ok(results) => Ok(Json(results)),
         Err(e) => Err((
             Status::BadRequest,
@@ -27,6 +31,7 @@ pub async fn handle_search(
                     SearchError::RequestError(_) => "request_error".to_string(),
                     SearchError::InvalidSearxUrl { .. } => "invalid_url".to_string(),
                     SearchError::SearxError(_) => "searx_error".to_string(),
+                    SearchError::SpellCheckError(_) => "spell_check_error".to_string(),
                 },
             }),
         )),
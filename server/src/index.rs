@@ -0,0 +1,203 @@
+use crate::webpage_parse::{DomParseConfig, ParsedWebpage};
+use dashmap::DashMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+const MAX_INDEXED_PAGES: usize = 1000;
+const DEFAULT_PAGE_CACHE_TTL_SECS: u64 = 600;
+
+struct IndexedPage {
+    content: String,
+    term_counts: HashMap<String, u32>,
+}
+
+#[derive(Default)]
+pub struct LocalPageIndex {
+    pages: HashMap<String, IndexedPage>,
+    insertion_order: VecDeque<String>,
+    document_frequencies: HashMap<String, usize>,
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.chars().count() > 1)
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+fn count_terms(text: &str) -> HashMap<String, u32> {
+    let mut term_counts = HashMap::new();
+    for token in tokenize(text) {
+        *term_counts.entry(token).or_insert(0) += 1;
+    }
+    term_counts
+}
+
+struct CachedPage {
+    parsed_webpage: ParsedWebpage,
+    dom_parse_config: DomParseConfig,
+    fetched_at: Instant,
+}
+
+pub struct PageCache {
+    pages: DashMap<String, CachedPage>,
+    ttl: Duration,
+}
+
+impl PageCache {
+    pub fn from_env() -> Self {
+        let ttl_secs = std::env::var("PAGE_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|ttl_secs| ttl_secs.parse().ok())
+            .unwrap_or(DEFAULT_PAGE_CACHE_TTL_SECS);
+        Self {
+            pages: DashMap::new(),
+            ttl: Duration::from_secs(ttl_secs),
+        }
+    }
+
+    pub fn get(&self, url: &str, dom_parse_config: &DomParseConfig) -> Option<ParsedWebpage> {
+        let page = self.pages.get(url)?;
+        if page.fetched_at.elapsed() >= self.ttl
+            || page.dom_parse_config != dom_parse_config.parse_options()
+        {
+            return None;
+        }
+        Some(page.parsed_webpage.clone())
+    }
+
+    pub fn insert(
+        &self,
+        url: &str,
+        dom_parse_config: &DomParseConfig,
+        parsed_webpage: &ParsedWebpage,
+    ) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        if self.pages.len() >= MAX_INDEXED_PAGES {
+            self.pages
+                .retain(|_, page| page.fetched_at.elapsed() < self.ttl);
+        }
+        if self.pages.len() >= MAX_INDEXED_PAGES {
+            let oldest_url = self
+                .pages
+                .iter()
+                .min_by_key(|page| page.fetched_at)
+                .map(|page| page.key().clone());
+            if let Some(oldest_url) = oldest_url {
+                self.pages.remove(&oldest_url);
+            }
+        }
+        self.pages.insert(
+            url.to_string(),
+            CachedPage {
+                parsed_webpage: parsed_webpage.clone(),
+                dom_parse_config: dom_parse_config.parse_options(),
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+}
+
+impl LocalPageIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, url: &str) -> Option<&str> {
+        self.pages.get(url).map(|page| page.content.as_str())
+    }
+
+    pub fn index(&mut self, url: &str, content: &str) {
+        if self.pages.contains_key(url) {
+            self.remove(url);
+        }
+        while self.pages.len() >= MAX_INDEXED_PAGES {
+            match self.insertion_order.front().cloned() {
+                Some(oldest_url) => self.remove(&oldest_url),
+                None => break,
+            }
+        }
+        let term_counts = count_terms(content);
+        for term in term_counts.keys() {
+            *self.document_frequencies.entry(term.clone()).or_insert(0) += 1;
+        }
+        self.pages.insert(
+            url.to_string(),
+            IndexedPage {
+                content: content.to_string(),
+                term_counts,
+            },
+        );
+        self.insertion_order.push_back(url.to_string());
+    }
+
+    fn remove(&mut self, url: &str) {
+        let page = match self.pages.remove(url) {
+            Some(page) => page,
+            None => return,
+        };
+        self.insertion_order
+            .retain(|indexed_url| indexed_url != url);
+        for term in page.term_counts.keys() {
+            if let Some(document_frequency) = self.document_frequencies.get_mut(term) {
+                *document_frequency -= 1;
+                if *document_frequency == 0 {
+                    self.document_frequencies.remove(term);
+                }
+            }
+        }
+    }
+
+    fn idf(&self, term: &str) -> f64 {
+        let num_pages = self.pages.len() as f64;
+        let document_frequency = self.document_frequencies.get(term).copied().unwrap_or(0) as f64;
+        ((1.0 + num_pages) / (1.0 + document_frequency)).ln() + 1.0
+    }
+
+    pub fn query(&self, text: &str, top_k: usize) -> Vec<(String, f64)> {
+        let query_weights = count_terms(text)
+            .into_iter()
+            .map(|(term, count)| {
+                let weight = count as f64 * self.idf(&term);
+                (term, weight)
+            })
+            .collect::<HashMap<_, _>>();
+        let query_norm = query_weights
+            .values()
+            .map(|weight| weight * weight)
+            .sum::<f64>()
+            .sqrt();
+        if query_norm == 0.0 {
+            return Vec::new();
+        }
+        let mut scores = self
+            .pages
+            .iter()
+            .filter_map(|(url, page)| {
+                let dot_product = query_weights
+                    .iter()
+                    .filter_map(|(term, query_weight)| {
+                        page.term_counts
+                            .get(term)
+                            .map(|count| query_weight * *count as f64 * self.idf(term))
+                    })
+                    .sum::<f64>();
+                if dot_product == 0.0 {
+                    return None;
+                }
+                let page_norm = page
+                    .term_counts
+                    .iter()
+                    .map(|(term, count)| (*count as f64 * self.idf(term)).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                Some((url.clone(), dot_product / (query_norm * page_norm)))
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores.truncate(top_k);
+        scores
+    }
+}
@@ -1,13 +1,99 @@
 use crate::prompts::Prompt;
+use crate::request_context::current_request_id;
+use crate::usage::{
+    record_rate_limit_delay, record_usage, stage_temperature, with_current_search_context,
+    LLMCallStage,
+};
+use crate::utils::{duration_from_env_ms, truncate_at_sentence};
+use cache::CompletionCache;
+use circuit_breaker::CircuitBreakers;
+use dashmap::DashMap;
+use futures::stream::{FuturesUnordered, StreamExt};
+use regex::Regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use thiserror::Error;
 
 const DEFAULT_LLM_PROXY_HOST: &str = "localhost";
 const DEFAULT_LLM_PROXY_PORT: &str = "8097";
 const DEFAULT_MODEL_NAME: &str = "claude-3-5-sonnet-20241022";
 const DEFAULT_PROVIDER: &str = "anthropic";
+const LLM_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_LLM_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_LLM_READ_TIMEOUT: Duration = Duration::from_secs(90);
+const DEFAULT_LLM_MAX_RETRIES: u32 = 3;
+const DEFAULT_RATE_LIMIT_RETRY_AFTER_SECS: u64 = 5;
+const MAX_RATE_LIMIT_RETRY_AFTER_SECS: u64 = 60;
+const MAX_CONTEXT_WINDOW_TRUNCATIONS: u32 = 2;
+const DEFAULT_LLM_RACE_TIMEOUT_SECS: u64 = 30;
+const LLM_CLIENT_KEEPALIVE: Duration = Duration::from_secs(60);
+const LLM_CLIENT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const RESPONSE_FORMAT_NAME: &str = "response";
+const RESPONSE_FORMAT_TOOL_DESCRIPTION: &str = "Respond with the final answer in this format.";
+
+pub mod cache;
+pub mod circuit_breaker;
+pub mod vertex;
+
+static DEFAULT_LLM_CLIENT_POOL: OnceLock<Arc<LLMClientPool>> = OnceLock::new();
+static DEFAULT_COMPLETION_CACHE: OnceLock<Arc<Mutex<CompletionCache>>> = OnceLock::new();
+static DEFAULT_CIRCUIT_BREAKERS: OnceLock<Arc<CircuitBreakers>> = OnceLock::new();
+
+#[derive(Debug, Default)]
+pub struct LLMClientPool {
+    clients: DashMap<String, Arc<Client>>,
+}
+
+impl LLMClientPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn build_client() -> Client {
+        match Client::builder()
+            .tcp_keepalive(LLM_CLIENT_KEEPALIVE)
+            .pool_idle_timeout(LLM_CLIENT_POOL_IDLE_TIMEOUT)
+            .pool_max_idle_per_host(20)
+            .connect_timeout(duration_from_env_ms(
+                "LLM_CONNECT_TIMEOUT_MS",
+                DEFAULT_LLM_CONNECT_TIMEOUT,
+            ))
+            .read_timeout(duration_from_env_ms(
+                "LLM_READ_TIMEOUT_MS",
+                DEFAULT_LLM_READ_TIMEOUT,
+            ))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::warn!("Failed to build LLM HTTP client, using defaults: {}", e);
+                Client::new()
+            }
+        }
+    }
+
+    pub fn client(&self, provider: &str) -> Arc<Client> {
+        self.clients
+            .entry(provider.to_string())
+            .or_insert_with(|| Arc::new(Self::build_client()))
+            .clone()
+    }
+}
+
+pub fn set_default_llm_client_pool(pool: Arc<LLMClientPool>) {
+    let _ = DEFAULT_LLM_CLIENT_POOL.set(pool);
+}
+
+pub fn set_default_completion_cache(cache: Arc<Mutex<CompletionCache>>) {
+    let _ = DEFAULT_COMPLETION_CACHE.set(cache);
+}
+
+pub fn set_default_circuit_breakers(circuit_breakers: Arc<CircuitBreakers>) {
+    let _ = DEFAULT_CIRCUIT_BREAKERS.set(circuit_breakers);
+}
 
 fn llm_proxy_url() -> String {
     let host =
@@ -17,10 +103,97 @@ fn llm_proxy_url() -> String {
     format!("http://{}:{}", host, port)
 }
 
+fn provider_api_base_url(provider: &str) -> Option<String> {
+    let provider_prefix = match provider {
+        "fireworks_ai" => "FIREWORKS".to_string(),
+        provider => provider.to_uppercase().replace('-', "_"),
+    };
+    std::env::var(format!("{}_API_BASE_URL", provider_prefix))
+        .ok()
+        .filter(|base_url| !base_url.is_empty())
+}
+
+pub fn default_model_name() -> String {
+    std::env::var("DEFAULT_LLM_MODEL").unwrap_or_else(|_| DEFAULT_MODEL_NAME.to_string())
+}
+
+fn uses_tool_for_response_format(provider: &str) -> bool {
+    provider == "anthropic" || provider == vertex::VERTEX_AI_PROVIDER
+}
+
 fn llm_proxy_api_key() -> String {
     std::env::var("LLM_PROXY_API_KEY").unwrap()
 }
 
+fn llm_max_retries() -> u32 {
+    std::env::var("LLM_MAX_RETRIES")
+        .ok()
+        .and_then(|max_retries| max_retries.parse().ok())
+        .unwrap_or(DEFAULT_LLM_MAX_RETRIES)
+}
+
+fn parse_retry_after(headers: &reqwest::header::HeaderMap, response_text: &str) -> Option<u64> {
+    let header_value = headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok());
+    let body_value = || {
+        let body = serde_json::from_str::<serde_json::Value>(response_text).ok()?;
+        let retry_after = body
+            .get("retry_after")
+            .or_else(|| body.get("error")?.get("retry_after"))?;
+        retry_after
+            .as_f64()
+            .or_else(|| retry_after.as_str()?.trim().parse().ok())
+    };
+    header_value
+        .or_else(body_value)
+        .map(|secs| secs.max(0.0).ceil() as u64)
+}
+
+fn llm_race_timeout() -> Duration {
+    let timeout_secs = std::env::var("LLM_RACE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|timeout_secs| timeout_secs.parse().ok())
+        .unwrap_or(DEFAULT_LLM_RACE_TIMEOUT_SECS);
+    Duration::from_secs(timeout_secs)
+}
+
+fn llm_race_providers() -> Vec<(String, String)> {
+    std::env::var("LLM_RACE_PROVIDERS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let (provider, model) = entry.trim().split_once(':')?;
+            Some((provider.trim().to_string(), model.trim().to_string()))
+        })
+        .filter(|(provider, model)| !provider.is_empty() && !model.is_empty())
+        .collect()
+}
+
+fn large_context_fallback_model() -> Option<String> {
+    std::env::var("LARGE_CONTEXT_FALLBACK_MODEL")
+        .ok()
+        .filter(|model| !model.is_empty())
+}
+
+fn is_context_window_exceeded(error: &LLMResponseError) -> bool {
+    let message = error.message.to_lowercase();
+    error.code == "context_length_exceeded"
+        || error.r#type.contains("ContextWindowExceeded")
+        || message.contains("context_length_exceeded")
+        || message.contains("maximum context length")
+        || message.contains("context window")
+        || message.contains("prompt is too long")
+}
+
+fn parse_context_window_token_count(message: &str) -> Option<u32> {
+    let token_count = Regex::new(r"(?:resulted in|prompt is too long:) (\d+) tokens").unwrap();
+    token_count
+        .captures(message)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Role {
     #[serde(rename = "system")]
@@ -44,6 +217,7 @@ pub struct LLMResponse {
     pub created: i64,
     pub model: String,
     pub choices: Vec<LLMResponseChoice>,
+    #[serde(default, alias = "usageMetadata")]
     pub usage: LLMResponseUsage,
 }
 
@@ -56,15 +230,77 @@ pub struct LLMResponseChoice {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMResponseMessage {
-    pub content: String,
+    #[serde(default)]
+    pub content: Option<String>,
     pub role: Role,
+    #[serde(default)]
+    pub tool_calls: Vec<LLMResponseToolCall>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LLMResponseToolCall {
+    pub function: LLMResponseFunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LLMResponseFunctionCall {
+    pub name: String,
+    pub arguments: String,
+}
+
+impl LLMResponseMessage {
+    fn into_completion_result(self) -> CompletionResult {
+        match self.tool_calls.into_iter().next() {
+            Some(tool_call) => CompletionResult::ToolCall {
+                name: tool_call.function.name,
+                arguments: serde_json::from_str(&tool_call.function.arguments)
+                    .unwrap_or(serde_json::Value::String(tool_call.function.arguments)),
+            },
+            None => CompletionResult::Text(self.content.unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LLMResponseUsage {
-    pub prompt_tokens: i32,
-    pub completion_tokens: i32,
-    pub total_tokens: i32,
+    #[serde(default, alias = "input_tokens", alias = "promptTokenCount")]
+    pub prompt_tokens: u32,
+    #[serde(default, alias = "output_tokens", alias = "candidatesTokenCount")]
+    pub completion_tokens: u32,
+    #[serde(default, alias = "totalTokenCount")]
+    pub total_tokens: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CompletionResult {
+    Text(String),
+    ToolCall {
+        name: String,
+        arguments: serde_json::Value,
+    },
+}
+
+impl CompletionResult {
+    pub fn into_text(self) -> String {
+        match self {
+            CompletionResult::Text(text) => text,
+            CompletionResult::ToolCall { arguments, .. } => arguments.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionWithUsage {
+    pub result: CompletionResult,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +324,12 @@ pub struct CompletionBuilder {
     messages: Vec<Message>,
     temperature: Option<f64>,
     max_completion_tokens: Option<i32>,
+    max_retries: Option<u32>,
+    client: Option<Arc<Client>>,
+    cache: Option<Arc<Mutex<CompletionCache>>>,
+    tools: Vec<Tool>,
+    response_format_schema: Option<serde_json::Value>,
+    system_override: Option<String>,
 }
 
 impl CompletionBuilder {
@@ -120,8 +362,302 @@ impl CompletionBuilder {
         self
     }
 
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    pub fn client(mut self, client: Arc<Client>) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn response_format_schema(mut self, schema: serde_json::Value) -> Self {
+        self.response_format_schema = Some(schema);
+        self
+    }
+
+    pub fn system_override(mut self, content: String) -> Self {
+        self.system_override = Some(content);
+        self
+    }
+
+    fn apply_system_override(&mut self) {
+        let content = match self.system_override.take() {
+            Some(content) => content,
+            None => return,
+        };
+        match self.messages.first_mut() {
+            Some(message) if matches!(message.role, Role::System) => message.content = content,
+            _ => self.messages.insert(
+                0,
+                Message {
+                    role: Role::System,
+                    content,
+                },
+            ),
+        }
+    }
+
     pub async fn build(self) -> Result<String, LLMError> {
-        let client = Client::new();
+        self.build_result().await.map(CompletionResult::into_text)
+    }
+
+    pub async fn build_result(self) -> Result<CompletionResult, LLMError> {
+        self.build_with_usage()
+            .await
+            .map(|completion| completion.result)
+    }
+
+    fn truncate_longest_message(&mut self) {
+        let longest_message = self
+            .messages
+            .iter_mut()
+            .filter(|message| !matches!(message.role, Role::System))
+            .max_by_key(|message| message.content.len());
+        if let Some(message) = longest_message {
+            let max_chars = message.content.chars().count() / 2;
+            message.content = truncate_at_sentence(&message.content, max_chars).to_string();
+        }
+    }
+
+    pub fn cache(mut self, cache: Arc<Mutex<CompletionCache>>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub async fn build_with_usage(mut self) -> Result<CompletionWithUsage, LLMError> {
+        self.apply_system_override();
+        let cache = match self
+            .cache
+            .clone()
+            .or_else(|| DEFAULT_COMPLETION_CACHE.get().cloned())
+        {
+            Some(cache) if self.temperature.unwrap_or(0.0) == 0.0 => cache,
+            _ => return self.send_with_retries().await,
+        };
+        let key = CompletionCache::key(
+            self.model.as_deref().unwrap_or(DEFAULT_MODEL_NAME),
+            self.provider.as_deref().unwrap_or(DEFAULT_PROVIDER),
+            &self.messages,
+            &self.tools,
+            self.response_format_schema.as_ref(),
+        );
+        if let Some(result) = cache.lock().unwrap().get(key) {
+            return Ok(CompletionWithUsage {
+                result,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+            });
+        }
+        let completion = self.send_with_retries().await?;
+        cache.lock().unwrap().insert(key, completion.result.clone());
+        Ok(completion)
+    }
+
+    async fn send_with_retries(self) -> Result<CompletionWithUsage, LLMError> {
+        let max_retries = self.max_retries.unwrap_or(0);
+        let mut builder = self;
+        let mut attempts = 0;
+        let mut truncations = 0;
+        loop {
+            match builder.clone().send().await {
+                Err(LLMError::RateLimited {
+                    provider,
+                    retry_after_secs,
+                }) if attempts < max_retries => {
+                    attempts += 1;
+                    let delay = Duration::from_secs(
+                        retry_after_secs
+                            .unwrap_or(DEFAULT_RATE_LIMIT_RETRY_AFTER_SECS)
+                            .min(MAX_RATE_LIMIT_RETRY_AFTER_SECS),
+                    );
+                    tracing::warn!(
+                        request_id = %current_request_id(),
+                        "Rate limited by {}, retrying in {}s (attempt {}/{})",
+                        provider,
+                        delay.as_secs(),
+                        attempts,
+                        max_retries
+                    );
+                    tokio::time::sleep(delay).await;
+                    record_rate_limit_delay(delay);
+                }
+                Err(LLMError::ContextWindowExceeded { model, token_count }) => {
+                    match large_context_fallback_model() {
+                        Some(fallback_model) if fallback_model != model => {
+                            tracing::warn!(
+                                request_id = %current_request_id(),
+                                "Context window of {} exceeded ({:?} tokens), retrying with {}",
+                                model,
+                                token_count,
+                                fallback_model
+                            );
+                            builder.model = Some(fallback_model);
+                        }
+                        _ if truncations < MAX_CONTEXT_WINDOW_TRUNCATIONS => {
+                            truncations += 1;
+                            tracing::warn!(
+                                request_id = %current_request_id(),
+                                "Context window of {} exceeded ({:?} tokens), retrying with truncated input (attempt {}/{})",
+                                model,
+                                token_count,
+                                truncations,
+                                MAX_CONTEXT_WINDOW_TRUNCATIONS
+                            );
+                            builder.truncate_longest_message();
+                        }
+                        _ => return Err(LLMError::ContextWindowExceeded { model, token_count }),
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub async fn race(
+        self,
+        providers: Vec<(String, String)>,
+    ) -> Result<CompletionWithUsage, LLMError> {
+        let timeout = llm_race_timeout();
+        let handles = providers
+            .into_iter()
+            .map(|(provider, model)| {
+                let builder = self.clone().provider(provider.clone()).model(model.clone());
+                let handle = tokio::spawn(with_current_search_context(builder.build_with_usage()));
+                (provider, model, handle)
+            })
+            .collect::<Vec<_>>();
+        let abort_handles = handles
+            .iter()
+            .map(|(_, _, handle)| handle.abort_handle())
+            .collect::<Vec<_>>();
+        let mut racers = handles
+            .into_iter()
+            .map(|(provider, model, handle)| async move { (provider, model, handle.await) })
+            .collect::<FuturesUnordered<_>>();
+        let race = async {
+            let mut last_error = LLMError::Other("No providers to race".to_string());
+            while let Some((provider, model, result)) = racers.next().await {
+                match result {
+                    Ok(Ok(completion)) => {
+                        tracing::info!(
+                            request_id = %current_request_id(),
+                            "LLM race won by {} ({})",
+                            provider,
+                            model
+                        );
+                        return Ok(completion);
+                    }
+                    Ok(Err(e)) => {
+                        tracing::warn!(
+                            request_id = %current_request_id(),
+                            "LLM race entrant {} ({}) failed: {}",
+                            provider,
+                            model,
+                            e
+                        );
+                        last_error = e;
+                    }
+                    Err(e) => last_error = LLMError::Other(e.to_string()),
+                }
+            }
+            Err(last_error)
+        };
+        let result = tokio::select! {
+            result = race => result,
+            _ = tokio::time::sleep(timeout) => Err(LLMError::RaceTimedOut {
+                timeout_secs: timeout.as_secs(),
+            }),
+        };
+        for abort_handle in abort_handles {
+            abort_handle.abort();
+        }
+        result
+    }
+
+    async fn send(self) -> Result<CompletionWithUsage, LLMError> {
+        let provider = self
+            .provider
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+        let circuit_breaker = DEFAULT_CIRCUIT_BREAKERS
+            .get()
+            .map(|circuit_breakers| circuit_breakers.for_provider(&provider));
+        let circuit_breaker = match circuit_breaker {
+            Some(circuit_breaker) => circuit_breaker,
+            None => return self.send_request().await,
+        };
+        if !circuit_breaker.allow_request() {
+            return Err(LLMError::CircuitOpen { provider });
+        }
+        let result = self.send_request().await;
+        match &result {
+            Ok(_) => circuit_breaker.record_success(),
+            Err(LLMError::RequestBuildingError(_)) => {}
+            Err(e) => {
+                if circuit_breaker.record_failure() {
+                    tracing::warn!(
+                        request_id = %current_request_id(),
+                        "Circuit breaker for {} opened after failure: {}",
+                        provider,
+                        e
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    async fn send_request(self) -> Result<CompletionWithUsage, LLMError> {
+        let provider = self
+            .provider
+            .unwrap_or_else(|| DEFAULT_PROVIDER.to_string());
+        let model = self.model.unwrap_or_else(|| DEFAULT_MODEL_NAME.to_string());
+        let client = match self.client.or_else(|| {
+            DEFAULT_LLM_CLIENT_POOL
+                .get()
+                .map(|pool| pool.client(&provider))
+        }) {
+            Some(client) => client,
+            None => Arc::new(Client::new()),
+        };
+        let mut tools = self.tools;
+        let mut response_format = None;
+        if let Some(schema) = self.response_format_schema {
+            if uses_tool_for_response_format(&provider) {
+                tools.push(Tool {
+                    name: RESPONSE_FORMAT_NAME.to_string(),
+                    description: RESPONSE_FORMAT_TOOL_DESCRIPTION.to_string(),
+                    parameters: schema,
+                });
+            } else {
+                response_format = Some(json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": RESPONSE_FORMAT_NAME,
+                        "schema": schema,
+                        "strict": true
+                    }
+                }));
+            }
+        }
+        if provider == vertex::VERTEX_AI_PROVIDER {
+            return vertex::send_vertex_completion(
+                &client,
+                model,
+                self.messages,
+                self.temperature.unwrap_or(0.0),
+                self.max_completion_tokens.unwrap_or(8192),
+                &tools,
+            )
+            .await;
+        }
         let messages: Vec<serde_json::Value> = self
             .messages
             .into_iter()
@@ -132,17 +668,44 @@ impl CompletionBuilder {
                 })
             })
             .collect();
+        let mut body = json!({
+            "model": model,
+            "custom_llm_provider": provider,
+            "messages": messages,
+            "temperature": self.temperature.unwrap_or(0.0),
+            "max_tokens": self.max_completion_tokens.unwrap_or(8192)
+        });
+        if !tools.is_empty() {
+            body["tools"] = json!(tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": tool.name,
+                            "description": tool.description,
+                            "parameters": tool.parameters
+                        }
+                    })
+                })
+                .collect::<Vec<_>>());
+            body["tool_choice"] = match tools.as_slice() {
+                [tool] => json!({"type": "function", "function": {"name": tool.name}}),
+                _ => json!("required"),
+            };
+        }
+        if let Some(response_format) = response_format {
+            body["response_format"] = response_format;
+        }
+        if let Some(api_base_url) = provider_api_base_url(&provider) {
+            body["api_base"] = json!(api_base_url);
+        }
         let response = match client
             .post(format!("{}/v1/chat/completions", llm_proxy_url()))
+            .timeout(LLM_REQUEST_TIMEOUT)
             .header("Content-Type", "application/json")
             .header("Authorization", format!("Bearer {}", llm_proxy_api_key()))
-            .json(&json!({
-                "model": self.model.unwrap_or(DEFAULT_MODEL_NAME.to_string()),
-                "custom_llm_provider": self.provider.unwrap_or(DEFAULT_PROVIDER.to_string()),
-                "messages": messages,
-                "temperature": self.temperature.unwrap_or(0.0),
-                "max_tokens": self.max_completion_tokens.unwrap_or(8192)
-            }))
+            .json(&body)
             .send()
             .await
         {
@@ -156,10 +719,23 @@ impl CompletionBuilder {
                     Ok(response_json) => response_json,
                     Err(e) => return Err(LLMError::RequestError(e)),
                 };
-                if response_json.choices.is_empty() {
-                    return Err(LLMError::EmptyResponse);
-                }
-                Ok(response_json.choices[0].message.content.clone())
+                let choice = match response_json.choices.into_iter().next() {
+                    Some(choice) => choice,
+                    None => return Err(LLMError::EmptyResponse),
+                };
+                Ok(CompletionWithUsage {
+                    result: choice.message.into_completion_result(),
+                    prompt_tokens: response_json.usage.prompt_tokens,
+                    completion_tokens: response_json.usage.completion_tokens,
+                })
+            }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let headers = response.headers().clone();
+                let response_text = response.text().await.unwrap_or_default();
+                Err(LLMError::RateLimited {
+                    provider,
+                    retry_after_secs: parse_retry_after(&headers, &response_text),
+                })
             }
             status => {
                 let response_text = match response.text().await {
@@ -167,6 +743,14 @@ impl CompletionBuilder {
                     Err(e) => return Err(LLMError::RequestError(e)),
                 };
                 let error_message = match serde_json::from_str::<LiteLLMError>(&response_text) {
+                    Ok(lite_error) if is_context_window_exceeded(&lite_error.error) => {
+                        return Err(LLMError::ContextWindowExceeded {
+                            model,
+                            token_count: parse_context_window_token_count(
+                                &lite_error.error.message,
+                            ),
+                        })
+                    }
                     Ok(lite_error) => lite_error.error.message,
                     Err(_) => response_text,
                 };
@@ -187,19 +771,57 @@ pub enum LLMError {
     RequestError(#[from] reqwest::Error),
     #[error("LLM response is empty")]
     EmptyResponse,
+    #[error("LLM race timed out after {timeout_secs}s")]
+    RaceTimedOut { timeout_secs: u64 },
+    #[error("Context window of {model} exceeded ({token_count:?} tokens)")]
+    ContextWindowExceeded {
+        model: String,
+        token_count: Option<u32>,
+    },
+    #[error("Rate limited by {provider} (retry after {retry_after_secs:?}s)")]
+    RateLimited {
+        provider: String,
+        retry_after_secs: Option<u64>,
+    },
+    #[error("Circuit breaker for {provider} is open, skipping request")]
+    CircuitOpen { provider: String },
     #[error("Other error: {0}")]
     Other(String),
 }
 
-pub async fn default_completion(prompt: &Prompt) -> Result<String, LLMError> {
-    let model =
-        std::env::var("DEFAULT_LLM_MODEL").unwrap_or_else(|_| DEFAULT_MODEL_NAME.to_string());
+pub async fn default_completion(prompt: &Prompt, stage: LLMCallStage) -> Result<String, LLMError> {
+    default_completion_with_messages(prompt.build_messages(), stage).await
+}
+
+pub async fn race_completion(prompt: &Prompt, stage: LLMCallStage) -> Result<String, LLMError> {
+    let providers = llm_race_providers();
+    if providers.len() < 2 {
+        return default_completion(prompt, stage).await;
+    }
+    let completion = CompletionBuilder::new()
+        .messages(prompt.build_messages())
+        .temperature(stage_temperature(stage).unwrap_or(0.0))
+        .max_retries(llm_max_retries())
+        .race(providers)
+        .await?;
+    record_usage(stage, &completion);
+    Ok(completion.result.into_text())
+}
+
+pub async fn default_completion_with_messages(
+    messages: Vec<Message>,
+    stage: LLMCallStage,
+) -> Result<String, LLMError> {
+    let model = default_model_name();
     let provider =
         std::env::var("DEFAULT_LLM_PROVIDER").unwrap_or_else(|_| DEFAULT_PROVIDER.to_string());
     let builder = CompletionBuilder::new()
         .model(model)
         .provider(provider)
-        .messages(prompt.clone().build_messages())
-        .temperature(0.0);
-    builder.build().await
+        .messages(messages)
+        .temperature(stage_temperature(stage).unwrap_or(0.0))
+        .max_retries(llm_max_retries());
+    let completion = builder.build_with_usage().await?;
+    record_usage(stage, &completion);
+    Ok(completion.result.into_text())
 }
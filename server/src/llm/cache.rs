@@ -0,0 +1,132 @@
+use crate::llm::{CompletionResult, Message, Tool};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+pub const DEFAULT_LLM_CACHE_SIZE: usize = 1000;
+
+#[derive(Debug)]
+struct CacheEntry {
+    result: CompletionResult,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+pub struct CompletionCache {
+    capacity: usize,
+    entries: HashMap<u64, CacheEntry>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompletionCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+impl CompletionCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+impl CompletionCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let capacity = std::env::var("LLM_CACHE_SIZE")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_LLM_CACHE_SIZE);
+        Self::new(capacity)
+    }
+
+    pub fn key(
+        model: &str,
+        provider: &str,
+        messages: &[Message],
+        tools: &[Tool],
+        response_format_schema: Option<&Value>,
+    ) -> u64 {
+        let serialized_messages = serde_json::to_string(messages).unwrap_or_default();
+        let serialized_tools = serde_json::to_string(tools).unwrap_or_default();
+        let serialized_schema = response_format_schema
+            .map(Value::to_string)
+            .unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        (
+            model,
+            provider,
+            serialized_messages,
+            serialized_tools,
+            serialized_schema,
+        )
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<CompletionResult> {
+        self.clock += 1;
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.last_used = self.clock;
+                self.hits += 1;
+                Some(entry.result.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: u64, result: CompletionResult) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.clock += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let least_recently_used = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            if let Some(least_recently_used) = least_recently_used {
+                self.entries.remove(&least_recently_used);
+            }
+        }
+        self.entries.insert(
+            key,
+            CacheEntry {
+                result,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    pub fn stats(&self) -> CompletionCacheStats {
+        CompletionCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+}
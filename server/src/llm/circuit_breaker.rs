@@ -0,0 +1,120 @@
+use dashmap::DashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct CircuitBreakerState {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_started_at: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    inner: Mutex<CircuitBreakerState>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_OPEN_DURATION)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+            inner: Mutex::new(CircuitBreakerState {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_started_at: None,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state == CircuitState::Open
+            && inner
+                .opened_at
+                .is_some_and(|opened_at| opened_at.elapsed() >= self.open_duration)
+        {
+            inner.state = CircuitState::HalfOpen;
+            inner.probe_started_at = None;
+        }
+        inner.state
+    }
+
+    pub fn allow_request(&self) -> bool {
+        let state = self.state();
+        let mut inner = self.inner.lock().unwrap();
+        match state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => match inner.probe_started_at {
+                Some(probe_started_at) if probe_started_at.elapsed() < self.open_duration => false,
+                _ => {
+                    inner.probe_started_at = Some(Instant::now());
+                    true
+                }
+            },
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state = CircuitState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_started_at = None;
+    }
+
+    pub fn record_failure(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        let should_open = match inner.state {
+            CircuitState::Closed => inner.consecutive_failures >= self.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if should_open {
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(Instant::now());
+            inner.probe_started_at = None;
+        }
+        should_open
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CircuitBreakers {
+    breakers: DashMap<String, Arc<CircuitBreaker>>,
+}
+
+impl CircuitBreakers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn for_provider(&self, provider: &str) -> Arc<CircuitBreaker> {
+        self.breakers
+            .entry(provider.to_string())
+            .or_default()
+            .clone()
+    }
+}
@@ -0,0 +1,427 @@
+use crate::llm::{
+    parse_retry_after, provider_api_base_url, CompletionResult, CompletionWithUsage, LLMError,
+    LLMResponseUsage, Message, Role, Tool, LLM_REQUEST_TIMEOUT,
+};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use regex::Regex;
+use reqwest::Client;
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const VERTEX_AI_PROVIDER: &str = "vertex_ai";
+
+const DEFAULT_GOOGLE_CLOUD_REGION: &str = "us-central1";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const JWT_LIFETIME_SECS: u64 = 3600;
+const ACCESS_TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+static ACCESS_TOKEN: Mutex<Option<CachedAccessToken>> = Mutex::new(None);
+
+struct CachedAccessToken {
+    token: String,
+    expires_at: Instant,
+}
+
+fn default_token_uri() -> String {
+    DEFAULT_TOKEN_URI.to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum GoogleCredentials {
+    #[serde(rename = "service_account")]
+    ServiceAccount {
+        client_email: String,
+        private_key: String,
+        #[serde(default = "default_token_uri")]
+        token_uri: String,
+    },
+    #[serde(rename = "authorized_user")]
+    AuthorizedUser {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct AccessTokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct VertexResponse {
+    #[serde(default)]
+    candidates: Vec<VertexCandidate>,
+    #[serde(default, rename = "usageMetadata")]
+    usage_metadata: LLMResponseUsage,
+}
+
+#[derive(Deserialize)]
+struct VertexCandidate {
+    content: Option<VertexContent>,
+}
+
+#[derive(Deserialize)]
+struct VertexContent {
+    #[serde(default)]
+    parts: Vec<VertexPart>,
+}
+
+#[derive(Deserialize)]
+struct VertexPart {
+    #[serde(default)]
+    text: String,
+    #[serde(default, rename = "functionCall")]
+    function_call: Option<VertexFunctionCall>,
+}
+
+#[derive(Deserialize)]
+struct VertexFunctionCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct VertexErrorResponse {
+    error: VertexError,
+}
+
+#[derive(Deserialize)]
+struct VertexError {
+    message: String,
+}
+
+fn google_cloud_project() -> Result<String, LLMError> {
+    match std::env::var("GOOGLE_CLOUD_PROJECT") {
+        Ok(project) => Ok(project),
+        Err(_) => Err(LLMError::RequestBuildingError(
+            "GOOGLE_CLOUD_PROJECT is not set".to_string(),
+        )),
+    }
+}
+
+fn google_cloud_region() -> String {
+    std::env::var("GOOGLE_CLOUD_REGION").unwrap_or_else(|_| DEFAULT_GOOGLE_CLOUD_REGION.to_string())
+}
+
+fn credentials_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+        return Some(PathBuf::from(path));
+    }
+    let home = std::env::var("HOME").ok()?;
+    let path = PathBuf::from(home).join(".config/gcloud/application_default_credentials.json");
+    path.exists().then_some(path)
+}
+
+fn load_credentials() -> Result<Option<GoogleCredentials>, LLMError> {
+    let path = match credentials_path() {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return Err(LLMError::RequestBuildingError(format!(
+                "Failed to read Google credentials from {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    match serde_json::from_str(&contents) {
+        Ok(credentials) => Ok(Some(credentials)),
+        Err(e) => Err(LLMError::RequestBuildingError(format!(
+            "Failed to parse Google credentials from {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+fn sign_service_account_jwt(
+    client_email: &str,
+    private_key: &str,
+    token_uri: &str,
+) -> Result<String, LLMError> {
+    let issued_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let header = URL_SAFE_NO_PAD.encode(json!({"alg": "RS256", "typ": "JWT"}).to_string());
+    let claims = URL_SAFE_NO_PAD.encode(
+        json!({
+            "iss": client_email,
+            "scope": CLOUD_PLATFORM_SCOPE,
+            "aud": token_uri,
+            "iat": issued_at,
+            "exp": issued_at + JWT_LIFETIME_SECS,
+        })
+        .to_string(),
+    );
+    let signing_input = format!("{}.{}", header, claims);
+    let pem_body = private_key
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<String>();
+    let der = match STANDARD.decode(pem_body.trim()) {
+        Ok(der) => der,
+        Err(e) => {
+            return Err(LLMError::RequestBuildingError(format!(
+                "Invalid service account private key: {}",
+                e
+            )))
+        }
+    };
+    let key_pair = match RsaKeyPair::from_pkcs8(&der) {
+        Ok(key_pair) => key_pair,
+        Err(e) => {
+            return Err(LLMError::RequestBuildingError(format!(
+                "Invalid service account private key: {}",
+                e
+            )))
+        }
+    };
+    let mut signature = vec![0u8; key_pair.public().modulus_len()];
+    if let Err(e) = key_pair.sign(
+        &RSA_PKCS1_SHA256,
+        &SystemRandom::new(),
+        signing_input.as_bytes(),
+        &mut signature,
+    ) {
+        return Err(LLMError::RequestBuildingError(format!(
+            "Failed to sign service account token: {}",
+            e
+        )));
+    }
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature)
+    ))
+}
+
+async fn fetch_access_token(client: &Client) -> Result<AccessTokenResponse, LLMError> {
+    let request = match load_credentials()? {
+        Some(GoogleCredentials::ServiceAccount {
+            client_email,
+            private_key,
+            token_uri,
+        }) => {
+            let assertion = sign_service_account_jwt(&client_email, &private_key, &token_uri)?;
+            client.post(token_uri).form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+        }
+        Some(GoogleCredentials::AuthorizedUser {
+            client_id,
+            client_secret,
+            refresh_token,
+        }) => client.post(DEFAULT_TOKEN_URI).form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+            ("refresh_token", refresh_token.as_str()),
+        ]),
+        None => client
+            .get(METADATA_TOKEN_URL)
+            .header("Metadata-Flavor", "Google"),
+    };
+    let response = match request.timeout(LLM_REQUEST_TIMEOUT).send().await {
+        Ok(response) => response,
+        Err(e) => return Err(LLMError::RequestError(e)),
+    };
+    if !response.status().is_success() {
+        let status = response.status();
+        let response_text = response.text().await.unwrap_or_default();
+        return Err(LLMError::Other(format!(
+            "Google access token exchange failed with status {}: {}",
+            status, response_text
+        )));
+    }
+    match response.json::<AccessTokenResponse>().await {
+        Ok(access_token) => Ok(access_token),
+        Err(e) => Err(LLMError::RequestError(e)),
+    }
+}
+
+async fn access_token(client: &Client) -> Result<String, LLMError> {
+    if let Some(cached) = ACCESS_TOKEN.lock().unwrap().as_ref() {
+        if cached.expires_at > Instant::now() + ACCESS_TOKEN_REFRESH_MARGIN {
+            return Ok(cached.token.clone());
+        }
+    }
+    let access_token = fetch_access_token(client).await?;
+    *ACCESS_TOKEN.lock().unwrap() = Some(CachedAccessToken {
+        token: access_token.access_token.clone(),
+        expires_at: Instant::now() + Duration::from_secs(access_token.expires_in),
+    });
+    Ok(access_token.access_token)
+}
+
+fn parse_input_token_count(message: &str) -> Option<u32> {
+    let token_count = Regex::new(r"input token count \((\d+)\)").unwrap();
+    token_count
+        .captures(message)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+pub async fn send_vertex_completion(
+    client: &Client,
+    model: String,
+    messages: Vec<Message>,
+    temperature: f64,
+    max_output_tokens: i32,
+    tools: &[Tool],
+) -> Result<CompletionWithUsage, LLMError> {
+    let project = google_cloud_project()?;
+    let region = google_cloud_region();
+    let token = access_token(client).await?;
+    let system_instruction = messages
+        .iter()
+        .filter(|message| matches!(message.role, Role::System))
+        .map(|message| message.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let contents = messages
+        .iter()
+        .filter(|message| !matches!(message.role, Role::System))
+        .map(|message| {
+            let role = match message.role {
+                Role::Assistant => "model",
+                _ => "user",
+            };
+            json!({
+                "role": role,
+                "parts": [{"text": message.content}]
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut body = json!({
+        "contents": contents,
+        "generationConfig": {
+            "temperature": temperature,
+            "maxOutputTokens": max_output_tokens
+        }
+    });
+    if !system_instruction.is_empty() {
+        body["systemInstruction"] = json!({"parts": [{"text": system_instruction}]});
+    }
+    if !tools.is_empty() {
+        let function_declarations = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters
+                })
+            })
+            .collect::<Vec<_>>();
+        let function_names = tools
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect::<Vec<_>>();
+        body["tools"] = json!([{"functionDeclarations": function_declarations}]);
+        body["toolConfig"] = json!({
+            "functionCallingConfig": {
+                "mode": "ANY",
+                "allowedFunctionNames": function_names
+            }
+        });
+    }
+    let api_base_url = provider_api_base_url(VERTEX_AI_PROVIDER)
+        .unwrap_or_else(|| format!("https://{region}-aiplatform.googleapis.com/v1"));
+    let url = format!(
+        "{}/projects/{project}/locations/{region}/publishers/google/models/{model}:generateContent",
+        api_base_url.trim_end_matches('/')
+    );
+    let response = match client
+        .post(url)
+        .timeout(LLM_REQUEST_TIMEOUT)
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => return Err(LLMError::RequestError(e)),
+    };
+
+    match response.status() {
+        reqwest::StatusCode::OK => {
+            let response_json = match response.json::<VertexResponse>().await {
+                Ok(response_json) => response_json,
+                Err(e) => return Err(LLMError::RequestError(e)),
+            };
+            let parts = response_json
+                .candidates
+                .into_iter()
+                .next()
+                .and_then(|candidate| candidate.content)
+                .map(|content| content.parts)
+                .unwrap_or_default();
+            let mut content = String::new();
+            let mut function_call = None;
+            for part in parts {
+                content.push_str(&part.text);
+                if function_call.is_none() {
+                    function_call = part.function_call;
+                }
+            }
+            let result = match function_call {
+                Some(function_call) => CompletionResult::ToolCall {
+                    name: function_call.name,
+                    arguments: function_call.args,
+                },
+                None if content.is_empty() => return Err(LLMError::EmptyResponse),
+                None => CompletionResult::Text(content),
+            };
+            Ok(CompletionWithUsage {
+                result,
+                prompt_tokens: response_json.usage_metadata.prompt_tokens,
+                completion_tokens: response_json.usage_metadata.completion_tokens,
+            })
+        }
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            let headers = response.headers().clone();
+            let response_text = response.text().await.unwrap_or_default();
+            Err(LLMError::RateLimited {
+                provider: VERTEX_AI_PROVIDER.to_string(),
+                retry_after_secs: parse_retry_after(&headers, &response_text),
+            })
+        }
+        status => {
+            let response_text = match response.text().await {
+                Ok(text) => text,
+                Err(e) => return Err(LLMError::RequestError(e)),
+            };
+            let error_message = match serde_json::from_str::<VertexErrorResponse>(&response_text) {
+                Ok(vertex_error) => vertex_error.error.message,
+                Err(_) => response_text,
+            };
+            if error_message.contains("exceeds the maximum number of tokens") {
+                return Err(LLMError::ContextWindowExceeded {
+                    model,
+                    token_count: parse_input_token_count(&error_message),
+                });
+            }
+            Err(LLMError::Other(format!(
+                "HTTP error status {}: {}",
+                status, error_message
+            )))
+        }
+    }
+}
@@ -1,22 +1,42 @@
-use crate::server::{create_server, run_server};
+use crate::grpc_server::{grpc_port_from_env, run_grpc_server};
+use crate::server::{create_server, create_server_state, run_server, SharedHttpClient};
 use std::env;
+use std::io::IsTerminal;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
 
 pub mod agent_search;
+pub mod checkpoint;
+pub mod cost_estimate;
+pub mod grpc_server;
 pub mod handlers;
+pub mod index;
 pub mod llm;
+pub mod pii;
+pub mod pricing;
 pub mod prompts;
 pub mod query;
+pub mod request_context;
 pub mod result_format;
 pub mod scrape_site;
 pub mod search;
+pub mod search_diff;
 pub mod server;
+pub mod session;
+pub mod usage;
 pub mod utils;
 pub mod webpage_parse;
+pub mod websocket;
 
 #[rocket::main]
 async fn main() {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .init();
     if let Err(e) = run().await {
-        eprintln!("Server error: {}", e);
+        tracing::error!("Server error: {}", e);
         std::process::exit(1);
     }
 }
@@ -27,6 +47,26 @@ async fn run() -> Result<(), server::ServerError> {
         .and_then(|p| p.parse().ok())
         .unwrap_or(8095);
     let config = rocket::Config::figment().merge(("port", port));
-    let rocket = create_server().configure(config);
-    run_server(rocket).await
+    let address: IpAddr = config
+        .extract_inner("address")
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let http_client = match SharedHttpClient::new() {
+        Ok(http_client) => http_client,
+        Err(e) => {
+            return Err(server::ServerError::Configuration(format!(
+                "Failed to build HTTP client: {}",
+                e
+            )))
+        }
+    };
+    let state = Arc::new(create_server_state(http_client));
+    let grpc_server = run_grpc_server(state.clone(), address, grpc_port_from_env());
+    let rocket = create_server(state).configure(config);
+    tokio::select! {
+        result = run_server(rocket) => result,
+        result = grpc_server => match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(server::ServerError::Grpc(e)),
+        },
+    }
 }
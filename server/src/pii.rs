@@ -0,0 +1,129 @@
+use crate::request_context::current_request_id;
+use crate::result_format::{AnalysisDocument, ResultFormatResponse};
+use crate::search::SearchResult;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+struct PiiPatterns {
+    email: Regex,
+    credit_card: Regex,
+    ssn: Regex,
+    us_phone: Regex,
+    international_phone: Regex,
+}
+
+static PII_PATTERNS: OnceLock<PiiPatterns> = OnceLock::new();
+
+fn pii_patterns() -> &'static PiiPatterns {
+    PII_PATTERNS.get_or_init(|| PiiPatterns {
+        email: Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}\b").unwrap(),
+        credit_card: Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap(),
+        ssn: Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap(),
+        us_phone: Regex::new(r"(?:\+?1[-.\s]?)?(?:\(\d{3}\)\s?|\b\d{3}[-.\s])\d{3}[-.\s]\d{4}\b")
+            .unwrap(),
+        international_phone: Regex::new(
+            r"\+\d{1,3}[\s.-]?(?:\(\d{1,4}\)[\s.-]?)?\d{2,4}(?:[\s.-]?\d{2,4}){1,4}\b",
+        )
+        .unwrap(),
+    })
+}
+
+fn passes_luhn_check(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| {
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+pub fn redact_pii(content: &str) -> String {
+    let patterns = pii_patterns();
+    let content = patterns.email.replace_all(content, "[EMAIL REDACTED]");
+    let content = patterns
+        .credit_card
+        .replace_all(&content, |caps: &Captures| {
+            let digits = caps[0]
+                .chars()
+                .filter_map(|c| c.to_digit(10))
+                .collect::<Vec<_>>();
+            if (13..=19).contains(&digits.len()) && passes_luhn_check(&digits) {
+                "[CC REDACTED]".to_string()
+            } else {
+                caps[0].to_string()
+            }
+        });
+    let content = patterns.ssn.replace_all(&content, "[SSN REDACTED]");
+    let content = patterns
+        .international_phone
+        .replace_all(&content, "[PHONE REDACTED]");
+    let content = patterns.us_phone.replace_all(&content, "[PHONE REDACTED]");
+    content.into_owned()
+}
+
+fn redact_search_result(result: &mut SearchResult) {
+    result.title = redact_pii(&result.title);
+    result.content = redact_pii(&result.content);
+    if let Some(infobox) = &result.infobox {
+        result.infobox = Some(redact_pii(infobox));
+    }
+}
+
+pub fn redact_analysis_document(analysis: &mut AnalysisDocument) {
+    analysis.content = redact_pii(&analysis.content);
+    for result in analysis
+        .visited_results
+        .iter_mut()
+        .chain(analysis.unvisited_results.iter_mut())
+    {
+        redact_search_result(result);
+    }
+}
+
+fn redact_json_strings(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = redact_pii(text),
+        Value::Array(values) => values.iter_mut().for_each(redact_json_strings),
+        Value::Object(fields) => fields.values_mut().for_each(redact_json_strings),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+pub fn redact_result_format_response(response: ResultFormatResponse) -> ResultFormatResponse {
+    let mut value = match serde_json::to_value(&response) {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::error!(
+                request_id = %current_request_id(),
+                "Failed to serialize response for PII redaction: {}",
+                e
+            );
+            return response;
+        }
+    };
+    redact_json_strings(&mut value);
+    match serde_json::from_value(value) {
+        Ok(redacted) => redacted,
+        Err(e) => {
+            tracing::error!(
+                request_id = %current_request_id(),
+                "Failed to restore response after PII redaction: {}",
+                e
+            );
+            response
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use serde::Serialize;
+
+const PRICES_PER_MILLION_TOKENS: [(&str, ModelPricing); 14] = [
+    ("claude-3-5-sonnet", ModelPricing::new(3.0, 15.0)),
+    ("claude-3-7-sonnet", ModelPricing::new(3.0, 15.0)),
+    ("claude-sonnet-4", ModelPricing::new(3.0, 15.0)),
+    ("claude-3-5-haiku", ModelPricing::new(0.8, 4.0)),
+    ("claude-3-haiku", ModelPricing::new(0.25, 1.25)),
+    ("claude-3-opus", ModelPricing::new(15.0, 75.0)),
+    ("claude-opus-4", ModelPricing::new(15.0, 75.0)),
+    ("gpt-4o-mini", ModelPricing::new(0.15, 0.6)),
+    ("gpt-4o", ModelPricing::new(2.5, 10.0)),
+    ("gpt-4-turbo", ModelPricing::new(10.0, 30.0)),
+    ("gemini-1.5-flash", ModelPricing::new(0.075, 0.3)),
+    ("gemini-1.5-pro", ModelPricing::new(1.25, 5.0)),
+    ("gemini-2.0-flash", ModelPricing::new(0.1, 0.4)),
+    ("gemini-2.5-pro", ModelPricing::new(1.25, 10.0)),
+];
+
+const FALLBACK_PRICING: ModelPricing = ModelPricing::new(3.0, 15.0);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub prompt_usd_per_million_tokens: f64,
+    pub completion_usd_per_million_tokens: f64,
+}
+
+impl ModelPricing {
+    pub const fn new(
+        prompt_usd_per_million_tokens: f64,
+        completion_usd_per_million_tokens: f64,
+    ) -> Self {
+        Self {
+            prompt_usd_per_million_tokens,
+            completion_usd_per_million_tokens,
+        }
+    }
+
+    pub fn cost_usd(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt_usd_per_million_tokens
+            + completion_tokens as f64 * self.completion_usd_per_million_tokens)
+            / 1_000_000.0
+    }
+}
+
+pub fn model_pricing(model: &str) -> Option<ModelPricing> {
+    let model = model.to_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    PRICES_PER_MILLION_TOKENS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, pricing)| *pricing)
+}
+
+pub fn model_pricing_or_fallback(model: &str) -> ModelPricing {
+    model_pricing(model).unwrap_or(FALLBACK_PRICING)
+}
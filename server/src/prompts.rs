@@ -31,10 +31,24 @@ impl Prompt {
 pub const WEB_SEARCH_CONTEXT: &str = r#"You are serving a verify specific task within a web search tool for a large language model.
 The context is that you are looping over a set of web search results to build a "findings" document."#;
 
+pub const INVALID_JSON_CORRECTION_PROMPT: &str =
+    "Your previous response was not valid JSON. Please respond with only a valid JSON code block.";
+
 pub const WEB_SEARCH_USE_SAME_WEB_SEARCH_FINDINGS_DOCUMENT: &str =
     "USE_SAME_WEB_SEARCH_FINDINGS_DOCUMENT";
 
-pub fn build_analyze_result_system_prompt() -> String {
+pub const WEB_SEARCH_SNIPPET_ONLY_NOTE: &str = r#"
+## Snippet-only content
+The full webpage for the current search result could not be fetched, so you are only given the short snippet returned by the search engine.
+Only extract information that is directly stated in the snippet and note in the findings document that it comes from a search snippet rather than the full page.
+"#;
+
+pub fn build_analyze_result_system_prompt(is_snippet: bool) -> String {
+    let snippet_note = if is_snippet {
+        WEB_SEARCH_SNIPPET_ONLY_NOTE
+    } else {
+        ""
+    };
     format!(
         r#"# Task
 You will be given a search query and an in-progress analysis document that contains web search findings and a new search result to analyze.
@@ -47,7 +61,7 @@ Examples of relevant information include:
 
 ## General context
 {WEB_SEARCH_CONTEXT}
-
+{snippet_note}
 ## Format
 Respond with the new findings document so far (or `{WEB_SEARCH_USE_SAME_WEB_SEARCH_FINDINGS_DOCUMENT}` - this will be parsed). If you need to add more information to the findings document, do so.
 If there is no new information to add, respond with `{WEB_SEARCH_USE_SAME_WEB_SEARCH_FINDINGS_DOCUMENT}`.
@@ -118,6 +132,16 @@ Your task is to aggregate the information from the visited search results into a
 ## Format
 Your response will be directly used as the document. Write it in markdown."#;
 
+pub const DEDUP_ANALYSIS_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a findings document that was merged from several independent searches about the same request.
+Because the searches overlapped, the document may state the same fact more than once, sometimes in different words.
+Your task is to remove duplicate and highly redundant sentences while preserving every unique fact, number, date, name, and source URL.
+When two sentences state the same fact with different levels of detail, keep the more detailed one.
+Do not add new information, summarize, or change the meaning of any sentence. Keep the original structure and headings where possible.
+
+## Format
+Your response will be directly used as the document. Write it in markdown."#;
+
 pub fn build_dependency_tree_system_prompt() -> String {
     format!(
         r#"# Task
@@ -150,6 +174,86 @@ Each number represents the index of a search result in the input list."#
     )
 }
 
+pub const DEPENDENCY_TREE_LEVEL_DEPENDENCIES_NOTE: &str = r#"
+
+## Level dependencies
+Also include a `depends_on` list with one entry per level. Each entry lists the indices of the earlier levels whose results are needed to process that level. A level that does not need any earlier level should have an empty list, so that it can be processed at the same time as earlier levels.
+
+```json
+{
+    "levels": [[0, 2], [1, 4], [3]],
+    "depends_on": [[], [0], []]
+}
+```"#;
+
+pub const CLASSIFY_QUERY_INTENT_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a natural language request from a user. Your task is to classify the intent of the request so that the best search strategy can be chosen.
+Classify the request along the following dimensions:
+- factual: the request asks for a specific fact that can likely be found on a single page
+- multi_part: the request has multiple independent parts that should be researched separately
+- time_sensitive: the request is about recent events or information that changes frequently
+- depth_required: the request needs an in-depth answer that builds on information from multiple sources
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "factual": <true or false>,
+    "multi_part": <true or false>,
+    "time_sensitive": <true or false>,
+    "depth_required": <true or false>
+}
+```
+"#;
+
+pub const SUGGEST_FOLLOW_UP_QUERIES_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query and the findings document that was written while researching it.
+Your task is to suggest 3 to 5 follow-up search queries that the user may want to ask next.
+Focus on gaps in the findings document and on closely related aspects of the topic that it does not cover yet. Do not suggest queries that the findings document already answers.
+Each query should be a standalone natural language request.
+
+## Format
+Respond with a JSON array of strings in a markdown code block:
+
+```json
+["a follow-up query", ...]
+```
+"#;
+
+pub const RERANK_SEARCH_RESULTS_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query and a list of search results, each with a title, URL, and content preview.
+Your task is to rank the search results by how relevant they are to the query, from most relevant to least relevant.
+Judge relevance by meaning rather than keyword overlap: prefer results that are likely to directly answer the query.
+
+## Format
+Each search result is labeled with an index.
+Respond with a JSON array of the indices in a markdown code block, most relevant first:
+
+```json
+[2, 0, 1, ...]
+```
+"#;
+
+pub const CLASSIFY_QUERY_COMPLEXITY_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a natural language request from a user. Your task is to decide how many web searches are needed to answer it and how those searches depend on each other.
+- requires_multiple_searches: the request cannot be answered well with a single search, for example because it has several parts or needs information from different kinds of sources
+- searches_are_sequential: the searches depend on each other, so a later search can only be written once the results of an earlier search are known (for example, "who founded the company that makes X, and where did they study?")
+If requires_multiple_searches is false, set searches_are_sequential to false.
+First, write a short reasoning trace, then write the classification.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "reasoning": "the reasoning trace for the classification",
+    "requires_multiple_searches": <true or false>,
+    "searches_are_sequential": <true or false>
+}
+```
+"#;
+
 pub const GENERATE_SINGLE_QUERY_SYSTEM_PROMPT: &str = r#"# Task
 You will be given a natural language request from a user. Your task is to generate a Google search query that will help find the most relevant information to answer the question.
 First, write a reasoning trace, then write the search query. Brainstorm the best place to find the information you need. Your query should search for specific sites, documents, or other information.
@@ -182,6 +286,49 @@ Respond with a JSON object in a markdown code block in the following format:
 ```
 "#;
 
+pub const GENERATE_COMPARISON_QUERIES_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a natural language request from a user that compares two or more options. Your task is to generate a list of Google search queries that are required to find the pros, cons, and trade-offs of each option.
+These queries will be searched in parallel and the results will be aggregated at the end.
+Write at least one query for each option being compared and one query for a direct comparison of the options.
+First, write a reasoning trace, then write the search queries. Brainstorm the best places to find the information you need. Your queries should search for specific sites, documents, or other pieces of information.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "reasoning": "the reasoning trace for identifying the options being compared and the best places to find their trade-offs",
+    "queries": ["query1", "query2", ...]
+}
+```
+"#;
+
+pub const TRANSLATE_QUERY_TO_ENGLISH_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a natural language search request from a user. Your task is to detect the language it is written in and translate it to English.
+Keep names, product names, code, and quoted phrases as they are. Do not answer the request or add any information to it.
+If the request is already in English, return it unchanged.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "translated": "the request translated to English",
+    "original_language": "the name of the language the request was written in, in English (e.g. Japanese)"
+}
+```
+"#;
+
+pub const SPELL_CHECK_QUERY_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query written by a user. Your task is to fix obvious spelling mistakes and typos in it.
+Only correct words that are clearly misspelled. Do not rephrase, reorder, expand, or shorten the query, and do not change its meaning.
+Keep names, product names, code, acronyms, and quoted phrases as they are unless they are clearly mistyped.
+If the query has no obvious typos, return it unchanged.
+
+## Format
+Respond with only the corrected query on a single line, with no quotes or explanation.
+"#;
+
 pub const GENERATE_SEQUENTIAL_QUERIES_SYSTEM_PROMPT: &str = r#"# Task
 You will be given a natural language request from a user. Your task is to generate a list of one or more Google search queries that are required to find the most relevant information to answer the question.
 These queries will be searched in sequence, so write them accordingly.
@@ -248,6 +395,281 @@ Your response will be directly used as the webpage. Write it in html in the foll
 <html>
 "#;
 
+pub const RESULT_FORMAT_PROS_CONS_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query that compares two options and a list of search results.
+Your task is to write a pros and cons comparison of the two options based on the search results.
+If more than two options are compared, compare the two options that are most relevant to the query.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "entity_a": "the name of the first option",
+    "entity_b": "the name of the second option",
+    "pros_a": ["a pro of the first option", ...],
+    "cons_a": ["a con of the first option", ...],
+    "pros_b": ["a pro of the second option", ...],
+    "cons_b": ["a con of the second option", ...],
+    "recommendation": "which option to choose and in which situations"
+}
+```
+"#;
+
+pub const RESULT_FORMAT_TIMELINE_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query and a list of search results.
+Your task is to extract a chronological timeline of the events that are relevant to the query from the search results.
+
+## Format
+Respond with a JSON array in a markdown code block in the following format:
+
+```json
+[
+    {
+        "date": "the date of the event in ISO 8601 format (YYYY-MM-DD, YYYY-MM, or YYYY if only the year is known)",
+        "event": "a short description of the event",
+        "significance": "why the event matters with respect to the query"
+    },
+    ...
+]
+```
+"#;
+
+pub const RESULT_FORMAT_TIMELINE_INVALID_DATES_PROMPT: &str = "The following dates in your previous response are not valid ISO 8601 dates (YYYY-MM-DD, YYYY-MM, or YYYY). Please respond with the full timeline again in a JSON code block, using valid dates only:";
+
+pub const RESULT_FORMAT_EXECUTIVE_SUMMARY_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query and a list of search results.
+Your task is to write an executive summary of the search results for a busy reader.
+Assess your confidence in the summary based on how many high-quality sources support it:
+- high: several reliable sources agree on the key points
+- medium: some reliable sources support the key points, but coverage is incomplete or sources disagree
+- low: few or low-quality sources were found
+List any information that the query asks for but that the search results do not cover as data gaps.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "summary": "a 2-3 sentence paragraph that answers the query",
+    "key_takeaways": ["3 to 7 short bullet-point takeaways", ...],
+    "confidence": "high, medium, or low",
+    "data_gaps": ["information that could not be found", ...]
+}
+```
+"#;
+
+pub const RESULT_FORMAT_SWOT_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query about a company or product and a list of search results.
+Your task is to write a SWOT analysis (strengths, weaknesses, opportunities, and threats) of the subject of the query based on the search results.
+Each quadrant must contain between 3 and 10 points.
+For each point, set `source_url` to the URL of the search result that supports it. Only use URLs from the given search results. If no specific search result supports the point, set `source_url` to null.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "subject": "the company or product being analyzed",
+    "strengths": [{"point": "a strength", "source_url": "https://..." or null}, ...],
+    "weaknesses": [{"point": "a weakness", "source_url": "https://..." or null}, ...],
+    "opportunities": [{"point": "an opportunity", "source_url": "https://..." or null}, ...],
+    "threats": [{"point": "a threat", "source_url": "https://..." or null}, ...]
+}
+```
+"#;
+
+pub const RESULT_FORMAT_SWOT_INVALID_POINT_COUNTS_PROMPT: &str = "Each quadrant of the SWOT analysis must contain between 3 and 10 points, but the following quadrants in your previous response do not. Please respond with the full SWOT analysis again in a JSON code block:";
+
+pub const RESULT_FORMAT_PRODUCT_COMPARISON_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query about products and a list of search results.
+Your task is to compare the products that are relevant to the query based on the search results.
+Only include prices, features, and availability that are stated in the search results. If a value is not stated, write "unknown".
+For each product, set `source_url` to the URL of the search result that the product details come from. Only use URLs from the given search results. If no specific search result supports the product, set `source_url` to null.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "products": [
+        {
+            "name": "the product name",
+            "price": "the price, including the currency",
+            "key_features": ["a key feature", ...],
+            "pros": ["a pro", ...],
+            "cons": ["a con", ...],
+            "availability": "where and whether the product can be bought",
+            "source_url": "https://..." or null
+        },
+        ...
+    ],
+    "recommendation": "which product to choose and in which situations",
+    "best_for": {
+        "budget": "the best product for buyers on a budget",
+        "premium": "the best product regardless of price",
+        "feature_richness": "the product with the most features"
+    }
+}
+```
+"#;
+
+pub const RESULT_FORMAT_ENTITY_PROFILE_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query about a person, organization, product, place, or other entity and a list of search results.
+Your task is to write a profile of the entity that the query is about based on the search results.
+Only include facts and events that are stated in the search results.
+List other entities (people, organizations, products, or places) that are closely related to the entity in `related_entities`, so that they can be profiled next.
+Set `sources` to the URLs of the search results that the profile is based on. Only use URLs from the given search results.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "name": "the name of the entity",
+    "type": "person" | "organization" | "product" | "place" | "other",
+    "summary": "a short summary of who or what the entity is",
+    "key_facts": [
+        {
+            "label": "the kind of fact, e.g. Founded",
+            "value": "the fact, e.g. 2021"
+        },
+        ...
+    ],
+    "timeline": [
+        {
+            "date": "the date of the event",
+            "event": "what happened"
+        },
+        ...
+    ],
+    "related_entities": ["the name of a related entity", ...],
+    "sources": ["https://...", ...]
+}
+```
+"#;
+
+pub const RESULT_FORMAT_CALENDAR_EVENT_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query about one or more events and a list of search results.
+Your task is to extract the events that are relevant to the query from the search results as calendar entries.
+Only include events whose dates are stated in the search results.
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "events": [
+        {
+            "title": "the name of the event",
+            "start_date": "the start of the event in ISO 8601 format (YYYY-MM-DD, or YYYY-MM-DDTHH:MM:SS with a UTC offset if the time is known)",
+            "end_date": "the end of the event in the same format as start_date, or null if unknown",
+            "location": "where the event takes place, or null if unknown",
+            "url": "the URL of the event's official page, or null if unknown",
+            "description": "a short description of the event"
+        },
+        ...
+    ]
+}
+```
+"#;
+
+pub const RESULT_FORMAT_CALENDAR_EVENT_INVALID_DATES_PROMPT: &str = "The following dates in your previous response are not valid ISO 8601 dates (YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS with an optional UTC offset). Please respond with all of the events again in a JSON code block, using valid dates only:";
+
+pub const RESULT_FORMAT_LEGAL_CITATION_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a legal or regulatory search query and a list of search results.
+Your task is to identify the statutes, regulations, and court cases that are relevant to the query and summarize the legal framework.
+Only include statutes and cases that are mentioned in the search results, and copy their citations exactly as they appear.
+If the search results do not state the jurisdiction, use "Unknown".
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "jurisdiction": "the jurisdiction the query applies to, e.g. United States (federal) or European Union",
+    "relevant_statutes": [
+        {
+            "name": "the name of the statute or regulation",
+            "citation": "the citation, e.g. 17 U.S.C. § 107",
+            "relevance": "how the statute or regulation applies to the query"
+        },
+        ...
+    ],
+    "relevant_cases": [
+        {
+            "name": "the name of the case",
+            "citation": "the citation, e.g. 510 U.S. 569 (1994)",
+            "holding": "what the court held"
+        },
+        ...
+    ],
+    "summary": "a summary of the legal or regulatory framework with respect to the query"
+}
+```
+"#;
+
+pub const RESULT_FORMAT_FINANCIAL_SUMMARY_SYSTEM_PROMPT: &str = r#"# Task
+You will be given a search query about a company and a list of search results.
+Your task is to extract a financial summary of the company that the query is about from the search results.
+Only include figures and facts that are stated in the search results. If a value is not stated, set it to null (or an empty list).
+Write all monetary values as strings with their currency and units as they appear in the search results, e.g. "$4.2B" or "€350M", and include the fiscal period if it is stated, e.g. "$4.2B (FY2023)".
+Set `financial_health` based on what the search results say about revenue growth, profitability, and debt:
+- `strong`: growing revenue and profitable, with no signs of financial distress
+- `moderate`: mixed signals, e.g. growing but unprofitable, or profitable but shrinking
+- `weak`: declining revenue, ongoing losses, or signs of financial distress
+- `unknown`: the search results do not say enough to tell
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{
+    "company_name": "the name of the company",
+    "ticker": "the stock ticker, e.g. NASDAQ:AAPL" | null,
+    "revenue": "the most recent annual revenue, e.g. $4.2B (FY2023)" | null,
+    "net_income": "the most recent annual net income, e.g. -$120M (FY2023)" | null,
+    "employees": 12000 | null,
+    "founded": "the founding year or date" | null,
+    "headquarters": "the city and country of the headquarters" | null,
+    "key_products": ["the name of a key product or service", ...],
+    "recent_news": ["a one-sentence summary of a recent news item about the company", ...],
+    "financial_health": "strong" | "moderate" | "weak" | "unknown"
+}
+```
+"#;
+
+pub fn build_result_format_qa_pairs_system_prompt(qa_pair_count: usize) -> String {
+    format!(
+        r#"# Task
+You will be given a search query and a list of search results.
+Your task is to extract {qa_pair_count} question-answer pairs from the search results that would be useful in an FAQ dataset about the subject of the query.
+Each answer must be supported by the search results. Set `source_url` to the URL of the search result that the answer comes from. Only use URLs from the given search results. If no specific search result supports the answer, set `source_url` to null.
+Set `confidence` based on how directly the search results answer the question:
+- `high`: the answer is directly stated or quoted in the search results
+- `medium`: the answer is inferred from the search results
+- `low`: the answer is speculative
+
+## Format
+Respond with a JSON object in a markdown code block in the following format:
+
+```json
+{{
+    "qa_pairs": [
+        {{
+            "question": "a question",
+            "answer": "the answer to the question",
+            "source_url": "https://..." or null,
+            "confidence": "high, medium, or low"
+        }},
+        ...
+    ]
+}}
+```
+"#
+    )
+}
+
 pub const RESULT_FORMAT_CUSTOM_SYSTEM_PROMPT: &str = r#"# Task
 You will be given a search query and a list of search results.
 Your task is to write a response according to the custom format description.
@@ -268,3 +690,18 @@ Write a JSON object in a markdown code block in the following format:
 }
 ```
 "#;
+
+pub const SEARCH_DIFF_SYSTEM_PROMPT: &str = r#"# Task
+You will be given two analysis documents (A and B) that were written for the same search query, along with the sources each one used.
+Your task is to identify the factual differences between the two documents.
+
+## Instructions
+- List facts, figures, or conclusions that appear in A but not in B, and in B but not in A.
+- List any claims on which A and B contradict each other, quoting both versions.
+- Ignore differences in wording, structure, or formatting that do not change the facts.
+- Where possible, attribute each difference to the sources that support it.
+- If there are no factual differences, say so.
+
+## Format
+Write the differences in markdown with the sections "Only in A", "Only in B", and "Contradictions".
+"#;
@@ -1,14 +1,23 @@
+use crate::agent_search::AgentSearchStrategy;
 use crate::llm::{default_completion, LLMError};
 use crate::prompts::{
-    Prompt, GENERATE_PARALLEL_QUERIES_SYSTEM_PROMPT, GENERATE_SEQUENTIAL_QUERIES_SYSTEM_PROMPT,
-    GENERATE_SINGLE_QUERY_SYSTEM_PROMPT,
+    Prompt, CLASSIFY_QUERY_COMPLEXITY_SYSTEM_PROMPT, CLASSIFY_QUERY_INTENT_SYSTEM_PROMPT,
+    GENERATE_COMPARISON_QUERIES_SYSTEM_PROMPT, GENERATE_PARALLEL_QUERIES_SYSTEM_PROMPT,
+    GENERATE_SEQUENTIAL_QUERIES_SYSTEM_PROMPT, GENERATE_SINGLE_QUERY_SYSTEM_PROMPT,
+    SPELL_CHECK_QUERY_SYSTEM_PROMPT, SUGGEST_FOLLOW_UP_QUERIES_SYSTEM_PROMPT,
+    TRANSLATE_QUERY_TO_ENGLISH_SYSTEM_PROMPT,
+};
+use crate::request_context::current_request_id;
+use crate::result_format::AnalysisDocument;
+use crate::usage::LLMCallStage;
+use crate::utils::{
+    parse_json_response_with_retry, ParseJsonError, DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
 };
-use crate::utils::{parse_json_response, ParseJsonError};
 use rocket::form::FromFormField;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Deserialize, FromFormField)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromFormField)]
 pub enum QueryStrategy {
     #[serde(rename = "verbatim")]
     Verbatim,
@@ -18,6 +27,10 @@ pub enum QueryStrategy {
     Parallel,
     #[serde(rename = "sequential")]
     Sequential,
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "adaptive")]
+    Adaptive,
 }
 
 impl Default for QueryStrategy {
@@ -44,6 +57,102 @@ pub struct QueryResponse {
 pub struct MultiQueryResponse {
     pub reasoning: String,
     pub queries: Vec<String>,
+    #[serde(default)]
+    pub strategy_selection: Option<StrategySelection>,
+    #[serde(default)]
+    pub resolved_query_strategy: Option<QueryStrategy>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryIntent {
+    pub factual: bool,
+    pub multi_part: bool,
+    pub time_sensitive: bool,
+    pub depth_required: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StrategySelection {
+    pub query_strategy: QueryStrategy,
+    pub search_strategy: AgentSearchStrategy,
+    pub intent: QueryIntent,
+}
+
+impl StrategySelection {
+    pub fn from_intent(intent: QueryIntent) -> Self {
+        let (query_strategy, search_strategy) = if intent.multi_part {
+            (QueryStrategy::Parallel, AgentSearchStrategy::ParallelTree)
+        } else if intent.depth_required {
+            (QueryStrategy::Single, AgentSearchStrategy::Sequential)
+        } else {
+            (QueryStrategy::Verbatim, AgentSearchStrategy::Parallel)
+        };
+        Self {
+            query_strategy,
+            search_strategy,
+            intent,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryComplexity {
+    pub requires_multiple_searches: bool,
+    pub searches_are_sequential: bool,
+    pub reasoning: String,
+}
+
+impl QueryComplexity {
+    pub fn query_strategy(&self) -> QueryStrategy {
+        match (
+            self.requires_multiple_searches,
+            self.searches_are_sequential,
+        ) {
+            (false, _) => QueryStrategy::Verbatim,
+            (true, false) => QueryStrategy::Parallel,
+            (true, true) => QueryStrategy::Sequential,
+        }
+    }
+}
+
+pub async fn classify_query_complexity(
+    original_query: &str,
+) -> Result<QueryComplexity, QuerySynthesisError> {
+    let prompt = Prompt::new(
+        CLASSIFY_QUERY_COMPLEXITY_SYSTEM_PROMPT.to_string(),
+        original_query.to_string(),
+    );
+    let completion = match default_completion(&prompt, LLMCallStage::QuerySynthesis).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(QuerySynthesisError::LLMError(e)),
+    };
+    let complexity: QueryComplexity =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(complexity) => complexity,
+            Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+        };
+    Ok(complexity)
+}
+
+async fn classify_query_intent(original_query: &str) -> Result<QueryIntent, QuerySynthesisError> {
+    let prompt = Prompt::new(
+        CLASSIFY_QUERY_INTENT_SYSTEM_PROMPT.to_string(),
+        original_query.to_string(),
+    );
+    let completion = match default_completion(&prompt, LLMCallStage::QuerySynthesis).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(QuerySynthesisError::LLMError(e)),
+    };
+    let intent: QueryIntent =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(intent) => intent,
+            Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+        };
+    Ok(intent)
 }
 
 async fn generate_single_query(original_query: &str) -> Result<QueryResponse, QuerySynthesisError> {
@@ -51,14 +160,17 @@ async fn generate_single_query(original_query: &str) -> Result<QueryResponse, Qu
         GENERATE_SINGLE_QUERY_SYSTEM_PROMPT.to_string(),
         original_query.to_string(),
     );
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::QuerySynthesis).await {
         Ok(completion) => completion,
         Err(e) => return Err(QuerySynthesisError::LLMError(e)),
     };
-    let query: QueryResponse = match parse_json_response(&completion) {
-        Ok(query) => query,
-        Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
-    };
+    let query: QueryResponse =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(query) => query,
+            Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+        };
     Ok(query)
 }
 
@@ -69,14 +181,17 @@ async fn generate_parallel_queries(
         GENERATE_PARALLEL_QUERIES_SYSTEM_PROMPT.to_string(),
         original_query.to_string(),
     );
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::QuerySynthesis).await {
         Ok(completion) => completion,
         Err(e) => return Err(QuerySynthesisError::LLMError(e)),
     };
-    let queries: MultiQueryResponse = match parse_json_response(&completion) {
-        Ok(queries) => queries,
-        Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
-    };
+    let queries: MultiQueryResponse =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(queries) => queries,
+            Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+        };
     Ok(queries)
 }
 
@@ -87,17 +202,135 @@ async fn generate_sequential_queries(
         GENERATE_SEQUENTIAL_QUERIES_SYSTEM_PROMPT.to_string(),
         original_query.to_string(),
     );
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::QuerySynthesis).await {
         Ok(completion) => completion,
         Err(e) => return Err(QuerySynthesisError::LLMError(e)),
     };
-    let queries: MultiQueryResponse = match parse_json_response(&completion) {
-        Ok(queries) => queries,
-        Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+    let queries: MultiQueryResponse =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(queries) => queries,
+            Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+        };
+    Ok(queries)
+}
+
+pub async fn synthesize_comparison_queries(
+    original_query: &str,
+) -> Result<MultiQueryResponse, QuerySynthesisError> {
+    let prompt = Prompt::new(
+        GENERATE_COMPARISON_QUERIES_SYSTEM_PROMPT.to_string(),
+        original_query.to_string(),
+    );
+    let completion = match default_completion(&prompt, LLMCallStage::QuerySynthesis).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(QuerySynthesisError::LLMError(e)),
     };
+    let queries: MultiQueryResponse =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(queries) => queries,
+            Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+        };
     Ok(queries)
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryTranslation {
+    pub original_query: String,
+    pub translated: String,
+    pub original_language: String,
+}
+
+#[derive(Deserialize)]
+struct TranslationResponse {
+    translated: String,
+    original_language: String,
+}
+
+pub async fn translate_to_english(query: &str) -> Result<QueryTranslation, QuerySynthesisError> {
+    let prompt = Prompt::new(
+        TRANSLATE_QUERY_TO_ENGLISH_SYSTEM_PROMPT.to_string(),
+        query.to_string(),
+    );
+    let completion = match default_completion(&prompt, LLMCallStage::QueryTranslation).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(QuerySynthesisError::LLMError(e)),
+    };
+    let translation: TranslationResponse =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(translation) => translation,
+            Err(e) => return Err(QuerySynthesisError::JsonParsingError(e)),
+        };
+    Ok(QueryTranslation {
+        original_query: query.to_string(),
+        translated: translation.translated,
+        original_language: translation.original_language,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryCorrection {
+    pub original: String,
+    pub corrected: String,
+    pub was_changed: bool,
+}
+
+pub async fn correct_query_spelling(query: &str) -> Result<QueryCorrection, LLMError> {
+    let prompt = Prompt::new(
+        SPELL_CHECK_QUERY_SYSTEM_PROMPT.to_string(),
+        query.to_string(),
+    );
+    let completion = match default_completion(&prompt, LLMCallStage::QueryCorrection).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(e),
+    };
+    let corrected = completion
+        .lines()
+        .map(|line| line.trim().trim_matches('"').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or(query.trim())
+        .to_string();
+    let was_changed = corrected != query.trim();
+    if was_changed {
+        tracing::info!(
+            request_id = %current_request_id(),
+            "Corrected query spelling: {:?} -> {:?}",
+            query,
+            corrected
+        );
+    }
+    Ok(QueryCorrection {
+        original: query.to_string(),
+        corrected,
+        was_changed,
+    })
+}
+
+pub async fn generate_follow_up_queries(
+    original_query: &str,
+    analysis: &AnalysisDocument,
+) -> Result<Vec<String>, LLMError> {
+    let prompt = Prompt::new(
+        SUGGEST_FOLLOW_UP_QUERIES_SYSTEM_PROMPT.to_string(),
+        format!(
+            "# Query:\n{}\n\n# Findings document:\n{}",
+            original_query, analysis.content
+        ),
+    );
+    let completion = default_completion(&prompt, LLMCallStage::FollowUpQueries).await?;
+    match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+        .await
+    {
+        Ok(queries) => Ok(queries),
+        Err(e) => Err(LLMError::Other(e.to_string())),
+    }
+}
+
 pub async fn synthesize_queries(
     original_query: &str,
     strategy: &QueryStrategy,
@@ -106,6 +339,8 @@ pub async fn synthesize_queries(
         QueryStrategy::Verbatim => Ok(MultiQueryResponse {
             reasoning: "".to_string(),
             queries: vec![original_query.to_string()],
+            strategy_selection: None,
+            resolved_query_strategy: None,
         }),
         QueryStrategy::Single => {
             let query = match generate_single_query(original_query).await {
@@ -115,6 +350,8 @@ pub async fn synthesize_queries(
             Ok(MultiQueryResponse {
                 reasoning: query.reasoning,
                 queries: vec![query.query],
+                strategy_selection: None,
+                resolved_query_strategy: None,
             })
         }
         QueryStrategy::Parallel => {
@@ -131,5 +368,58 @@ pub async fn synthesize_queries(
             };
             Ok(queries)
         }
+        QueryStrategy::Auto => {
+            let intent = match classify_query_intent(original_query).await {
+                Ok(intent) => intent,
+                Err(e) => return Err(e),
+            };
+            let selection = StrategySelection::from_intent(intent);
+            tracing::info!(
+                request_id = %current_request_id(),
+                "Auto-selected query strategy {:?} and search strategy {:?} for query: {}",
+                selection.query_strategy,
+                selection.search_strategy,
+                original_query
+            );
+            let mut queries = match Box::pin(synthesize_queries(
+                original_query,
+                &selection.query_strategy,
+            ))
+            .await
+            {
+                Ok(queries) => queries,
+                Err(e) => return Err(e),
+            };
+            queries.strategy_selection = Some(selection);
+            Ok(queries)
+        }
+        QueryStrategy::Adaptive => {
+            let complexity = match classify_query_complexity(original_query).await {
+                Ok(complexity) => complexity,
+                Err(e) => return Err(e),
+            };
+            let query_strategy = complexity.query_strategy();
+            tracing::info!(
+                request_id = %current_request_id(),
+                "Adaptive query strategy chose {:?} for query: {}",
+                query_strategy,
+                original_query
+            );
+            let mut queries =
+                match Box::pin(synthesize_queries(original_query, &query_strategy)).await {
+                    Ok(queries) => queries,
+                    Err(e) => return Err(e),
+                };
+            queries.reasoning = if queries.reasoning.is_empty() {
+                format!("Query complexity: {}", complexity.reasoning)
+            } else {
+                format!(
+                    "Query complexity: {}\n\n{}",
+                    complexity.reasoning, queries.reasoning
+                )
+            };
+            queries.resolved_query_strategy = Some(query_strategy);
+            Ok(queries)
+        }
     }
 }
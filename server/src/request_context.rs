@@ -0,0 +1,80 @@
+use crate::utils::new_uuid_v4;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use std::convert::Infallible;
+use std::future::Future;
+use std::time::Instant;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+const MAX_REQUEST_ID_LEN: usize = 128;
+const NO_REQUEST_ID: &str = "-";
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    pub received_at: Instant,
+}
+
+impl RequestContext {
+    pub fn new() -> Self {
+        Self::from_request_id(None)
+    }
+
+    pub fn from_request_id(request_id: Option<&str>) -> Self {
+        let request_id = match request_id.map(str::trim) {
+            Some(request_id) if is_valid_request_id(request_id) => request_id.to_string(),
+            _ => new_uuid_v4(),
+        };
+        Self {
+            request_id,
+            received_at: Instant::now(),
+        }
+    }
+
+    pub fn from_request(request: &Request<'_>) -> Self {
+        Self::from_request_id(request.headers().get_one(REQUEST_ID_HEADER))
+    }
+}
+
+impl Default for RequestContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestContext {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(
+            request
+                .local_cache(|| RequestContext::from_request(request))
+                .clone(),
+        )
+    }
+}
+
+fn is_valid_request_id(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= MAX_REQUEST_ID_LEN
+        && request_id.chars().all(|c| c.is_ascii_graphic())
+}
+
+pub async fn with_request_context<F: Future>(context: RequestContext, future: F) -> F::Output {
+    REQUEST_CONTEXT.scope(context, future).await
+}
+
+pub fn current_request_context() -> Option<RequestContext> {
+    REQUEST_CONTEXT.try_with(|context| context.clone()).ok()
+}
+
+pub fn current_request_id() -> String {
+    REQUEST_CONTEXT
+        .try_with(|context| context.request_id.clone())
+        .unwrap_or_else(|_| NO_REQUEST_ID.to_string())
+}
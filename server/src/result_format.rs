@@ -1,13 +1,31 @@
-use crate::llm::{default_completion, LLMError};
+pub mod ical;
+
+use crate::agent_search::AgentSearchStrategy;
+use crate::llm::{default_completion, default_completion_with_messages, LLMError, Message, Role};
 use crate::prompts::{
-    Prompt, RESULT_FORMAT_ANSWER_SYSTEM_PROMPT, RESULT_FORMAT_CUSTOM_SYSTEM_PROMPT,
-    RESULT_FORMAT_FAQ_SYSTEM_PROMPT, RESULT_FORMAT_NEWS_ARTICLE_SYSTEM_PROMPT,
-    RESULT_FORMAT_RESEARCH_SUMMARY_SYSTEM_PROMPT, RESULT_FORMAT_WEBPAGE_SYSTEM_PROMPT,
+    build_result_format_qa_pairs_system_prompt, Prompt, RESULT_FORMAT_ANSWER_SYSTEM_PROMPT,
+    RESULT_FORMAT_CALENDAR_EVENT_INVALID_DATES_PROMPT, RESULT_FORMAT_CALENDAR_EVENT_SYSTEM_PROMPT,
+    RESULT_FORMAT_CUSTOM_SYSTEM_PROMPT, RESULT_FORMAT_ENTITY_PROFILE_SYSTEM_PROMPT,
+    RESULT_FORMAT_EXECUTIVE_SUMMARY_SYSTEM_PROMPT, RESULT_FORMAT_FAQ_SYSTEM_PROMPT,
+    RESULT_FORMAT_FINANCIAL_SUMMARY_SYSTEM_PROMPT, RESULT_FORMAT_LEGAL_CITATION_SYSTEM_PROMPT,
+    RESULT_FORMAT_NEWS_ARTICLE_SYSTEM_PROMPT, RESULT_FORMAT_PRODUCT_COMPARISON_SYSTEM_PROMPT,
+    RESULT_FORMAT_PROS_CONS_SYSTEM_PROMPT, RESULT_FORMAT_RESEARCH_SUMMARY_SYSTEM_PROMPT,
+    RESULT_FORMAT_SWOT_INVALID_POINT_COUNTS_PROMPT, RESULT_FORMAT_SWOT_SYSTEM_PROMPT,
+    RESULT_FORMAT_TIMELINE_INVALID_DATES_PROMPT, RESULT_FORMAT_TIMELINE_SYSTEM_PROMPT,
+    RESULT_FORMAT_WEBPAGE_SYSTEM_PROMPT,
 };
 use crate::search::SearchResult;
-use rocket::form::FromFormField;
+use crate::usage::LLMCallStage;
+use crate::utils::{
+    normalize_url, parse_json_response_with_retry, ParseJsonError, DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
+};
+use regex::Regex;
+use rocket::form::{self, DataField, FromForm, FromFormField, Options, ValueField};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::OnceLock;
 use thiserror::Error;
+use time::{Date, Month, Time};
 
 #[derive(Error, Debug)]
 pub enum ResultFormatError {
@@ -15,6 +33,25 @@ pub enum ResultFormatError {
     LLMError(#[from] LLMError),
     #[error("Custom format description is missing")]
     CustomFormatDescriptionMissing,
+    #[error("Failed to parse formatted result: {0}")]
+    ParseError(#[from] ParseJsonError),
+    #[error("Timeline contains invalid dates: {0}")]
+    InvalidTimelineDates(String),
+    #[error("Calendar events contain invalid dates: {0}")]
+    InvalidCalendarEventDates(String),
+    #[error("SWOT analysis quadrants must have between 3 and 10 points: {0}")]
+    InvalidSwotPointCounts(String),
+    #[error("Post-processor failed: {0}")]
+    PostProcessorError(String),
+}
+
+pub const STALE_RESULT_MARKER: &str = "skipped (stale)";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SkippedResult {
+    pub url: String,
+    pub title: String,
+    pub reason: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,6 +59,28 @@ pub struct AnalysisDocument {
     pub content: String,
     pub visited_results: Vec<SearchResult>,
     pub unvisited_results: Vec<SearchResult>,
+    #[serde(default)]
+    pub skipped_results: Vec<SkippedResult>,
+}
+
+impl AnalysisDocument {
+    pub fn filter_new_results(&self, results: Vec<SearchResult>) -> Vec<SearchResult> {
+        let known_urls = self
+            .visited_results
+            .iter()
+            .chain(self.unvisited_results.iter())
+            .map(|result| normalize_url(&result.url))
+            .collect::<HashSet<_>>();
+        results
+            .into_iter()
+            .filter(|result| !known_urls.contains(&normalize_url(&result.url)))
+            .collect()
+    }
+
+    pub fn push_stale_result(&mut self, mut result: SearchResult) {
+        result.content = format!("[{}] {}", STALE_RESULT_MARKER, result.content);
+        self.unvisited_results.push(result);
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, FromFormField)]
@@ -38,6 +97,26 @@ pub enum ResultFormat {
     Webpage,
     #[serde(rename = "custom")]
     Custom,
+    #[serde(rename = "pros_cons_comparison")]
+    ProsConsComparison,
+    #[serde(rename = "timeline")]
+    Timeline,
+    #[serde(rename = "executive_summary")]
+    ExecutiveSummary,
+    #[serde(rename = "swot_analysis")]
+    SwotAnalysis,
+    #[serde(rename = "product_comparison")]
+    ProductComparison,
+    #[serde(rename = "entity_profile")]
+    EntityProfile,
+    #[serde(rename = "calendar_event")]
+    CalendarEvent,
+    #[serde(rename = "legal_citation")]
+    LegalCitation,
+    #[serde(rename = "qa_pairs")]
+    QAPairs,
+    #[serde(rename = "financial_summary")]
+    FinancialSummary,
 }
 
 impl Default for ResultFormat {
@@ -60,6 +139,164 @@ pub enum ResultFormatResponse {
     Webpage(Article),
     #[serde(rename = "custom")]
     Custom(String),
+    #[serde(rename = "pros_cons_comparison")]
+    ProsConsComparison(ProsConsResult),
+    #[serde(rename = "timeline")]
+    Timeline(Vec<TimelineEvent>),
+    #[serde(rename = "executive_summary")]
+    ExecutiveSummary(ExecutiveSummaryResult),
+    #[serde(rename = "swot_analysis")]
+    SwotAnalysis(SwotAnalysisResult),
+    #[serde(rename = "product_comparison")]
+    ProductComparison(ProductComparisonResult),
+    #[serde(rename = "entity_profile")]
+    EntityProfile(EntityProfile),
+    #[serde(rename = "calendar_event")]
+    CalendarEvent(CalendarEventResult),
+    #[serde(rename = "legal_citation")]
+    LegalCitation(LegalCitationResult),
+    #[serde(rename = "qa_pairs")]
+    QAPairs(Vec<QAPair>),
+    #[serde(rename = "financial_summary")]
+    FinancialSummary(FinancialSummary),
+    #[serde(rename = "dry_run")]
+    DryRun(DryRunResult),
+}
+
+impl ResultFormatResponse {
+    fn text_content_mut(&mut self) -> Option<&mut String> {
+        match self {
+            ResultFormatResponse::Answer(content)
+            | ResultFormatResponse::ResearchSummary(content)
+            | ResultFormatResponse::Custom(content) => Some(content),
+            ResultFormatResponse::FAQArticle(article)
+            | ResultFormatResponse::NewsArticle(article)
+            | ResultFormatResponse::Webpage(article) => Some(&mut article.content),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum PostProcessor {
+    #[serde(rename = "trim_whitespace")]
+    TrimWhitespace,
+    #[serde(rename = "regex_replace")]
+    RegexReplace {
+        pattern: String,
+        replacement: String,
+    },
+    #[serde(rename = "truncate_to_chars")]
+    TruncateToChars(usize),
+    #[serde(rename = "llm_transform")]
+    LLMTransform { prompt: String },
+}
+
+#[derive(Debug, Clone, FromFormField)]
+pub enum PostProcessorKind {
+    #[field(value = "trim_whitespace")]
+    TrimWhitespace,
+    #[field(value = "regex_replace")]
+    RegexReplace,
+    #[field(value = "truncate_to_chars")]
+    TruncateToChars,
+    #[field(value = "llm_transform")]
+    LLMTransform,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct PostProcessorForm {
+    kind: PostProcessorKind,
+    pattern: Option<String>,
+    replacement: Option<String>,
+    max_chars: Option<usize>,
+    prompt: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromForm<'r> for PostProcessor {
+    type Context = <PostProcessorForm as FromForm<'r>>::Context;
+
+    fn init(opts: Options) -> Self::Context {
+        PostProcessorForm::init(opts)
+    }
+
+    fn push_value(ctxt: &mut Self::Context, field: ValueField<'r>) {
+        PostProcessorForm::push_value(ctxt, field)
+    }
+
+    async fn push_data(ctxt: &mut Self::Context, field: DataField<'r, '_>) {
+        PostProcessorForm::push_data(ctxt, field).await
+    }
+
+    fn finalize(ctxt: Self::Context) -> form::Result<'r, Self> {
+        let post_processor = PostProcessorForm::finalize(ctxt)?;
+        match post_processor {
+            PostProcessorForm {
+                kind: PostProcessorKind::TrimWhitespace,
+                ..
+            } => Ok(PostProcessor::TrimWhitespace),
+            PostProcessorForm {
+                kind: PostProcessorKind::RegexReplace,
+                pattern: Some(pattern),
+                replacement,
+                ..
+            } => Ok(PostProcessor::RegexReplace {
+                pattern,
+                replacement: replacement.unwrap_or_default(),
+            }),
+            PostProcessorForm {
+                kind: PostProcessorKind::TruncateToChars,
+                max_chars: Some(max_chars),
+                ..
+            } => Ok(PostProcessor::TruncateToChars(max_chars)),
+            PostProcessorForm {
+                kind: PostProcessorKind::LLMTransform,
+                prompt: Some(prompt),
+                ..
+            } => Ok(PostProcessor::LLMTransform { prompt }),
+            _ => Err(form::Error::validation("missing post-processor argument").into()),
+        }
+    }
+}
+
+async fn apply_post_processor(
+    content: &str,
+    post_processor: &PostProcessor,
+) -> Result<String, ResultFormatError> {
+    match post_processor {
+        PostProcessor::TrimWhitespace => Ok(content.trim().to_string()),
+        PostProcessor::RegexReplace {
+            pattern,
+            replacement,
+        } => match Regex::new(pattern) {
+            Ok(regex) => Ok(regex.replace_all(content, replacement.as_str()).to_string()),
+            Err(e) => Err(ResultFormatError::PostProcessorError(format!(
+                "Invalid regex pattern {}: {}",
+                pattern, e
+            ))),
+        },
+        PostProcessor::TruncateToChars(max_chars) => Ok(content.chars().take(*max_chars).collect()),
+        PostProcessor::LLMTransform { prompt } => {
+            let prompt = Prompt::new(prompt.clone(), content.to_string());
+            match default_completion(&prompt, LLMCallStage::Formatting).await {
+                Ok(completion) => Ok(completion),
+                Err(e) => Err(ResultFormatError::LLMError(e)),
+            }
+        }
+    }
+}
+
+pub async fn apply_post_processors(
+    mut response: ResultFormatResponse,
+    post_processors: &[PostProcessor],
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    if let Some(content) = response.text_content_mut() {
+        for post_processor in post_processors {
+            *content = apply_post_processor(content, post_processor).await?;
+        }
+    }
+    Ok(response)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -68,11 +305,305 @@ pub struct Article {
     pub content: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProsConsResult {
+    pub entity_a: String,
+    pub entity_b: String,
+    pub pros_a: Vec<String>,
+    pub cons_a: Vec<String>,
+    pub pros_b: Vec<String>,
+    pub cons_b: Vec<String>,
+    pub recommendation: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelineEvent {
+    pub date: String,
+    pub event: String,
+    pub significance: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SummaryConfidence {
+    #[serde(rename = "high")]
+    High,
+    #[serde(rename = "medium")]
+    Medium,
+    #[serde(rename = "low")]
+    Low,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecutiveSummaryResult {
+    pub summary: String,
+    pub key_takeaways: Vec<String>,
+    pub confidence: SummaryConfidence,
+    pub data_gaps: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwotPoint {
+    pub point: String,
+    pub source_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwotAnalysisResult {
+    pub subject: String,
+    pub strengths: Vec<SwotPoint>,
+    pub weaknesses: Vec<SwotPoint>,
+    pub opportunities: Vec<SwotPoint>,
+    pub threats: Vec<SwotPoint>,
+}
+
+const MIN_SWOT_POINTS_PER_QUADRANT: usize = 3;
+const MAX_SWOT_POINTS_PER_QUADRANT: usize = 10;
+
+impl SwotAnalysisResult {
+    fn invalid_quadrants(&self) -> Vec<String> {
+        [
+            ("strengths", &self.strengths),
+            ("weaknesses", &self.weaknesses),
+            ("opportunities", &self.opportunities),
+            ("threats", &self.threats),
+        ]
+        .iter()
+        .filter(|(_, points)| {
+            !(MIN_SWOT_POINTS_PER_QUADRANT..=MAX_SWOT_POINTS_PER_QUADRANT).contains(&points.len())
+        })
+        .map(|(name, points)| format!("{} ({} points)", name, points.len()))
+        .collect()
+    }
+
+    fn drop_unknown_source_urls(&mut self, visited_results: &[SearchResult]) {
+        let visited_urls = visited_results
+            .iter()
+            .map(|result| normalize_url(&result.url))
+            .collect::<HashSet<_>>();
+        for points in [
+            &mut self.strengths,
+            &mut self.weaknesses,
+            &mut self.opportunities,
+            &mut self.threats,
+        ] {
+            for point in points.iter_mut() {
+                if let Some(source_url) = &point.source_url {
+                    if !visited_urls.contains(&normalize_url(source_url)) {
+                        point.source_url = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Product {
+    pub name: String,
+    pub price: String,
+    pub key_features: Vec<String>,
+    pub pros: Vec<String>,
+    pub cons: Vec<String>,
+    pub availability: String,
+    pub source_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProductBestFor {
+    pub budget: String,
+    pub premium: String,
+    pub feature_richness: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProductComparisonResult {
+    pub products: Vec<Product>,
+    pub recommendation: String,
+    pub best_for: ProductBestFor,
+}
+
+impl ProductComparisonResult {
+    fn drop_unknown_source_urls(&mut self, visited_results: &[SearchResult]) {
+        let visited_urls = visited_results
+            .iter()
+            .map(|result| normalize_url(&result.url))
+            .collect::<HashSet<_>>();
+        for product in self.products.iter_mut() {
+            if let Some(source_url) = &product.source_url {
+                if !visited_urls.contains(&normalize_url(source_url)) {
+                    product.source_url = None;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum EntityType {
+    #[serde(rename = "person")]
+    Person,
+    #[serde(rename = "organization")]
+    Organization,
+    #[serde(rename = "product")]
+    Product,
+    #[serde(rename = "place")]
+    Place,
+    #[serde(rename = "other")]
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntityFact {
+    pub label: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntityTimelineEvent {
+    pub date: String,
+    pub event: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntityProfile {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub entity_type: EntityType,
+    pub summary: String,
+    pub key_facts: Vec<EntityFact>,
+    pub timeline: Vec<EntityTimelineEvent>,
+    pub related_entities: Vec<String>,
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub low_confidence: bool,
+}
+
+impl EntityProfile {
+    fn drop_unknown_sources(&mut self, visited_results: &[SearchResult]) {
+        let visited_urls = visited_results
+            .iter()
+            .map(|result| normalize_url(&result.url))
+            .collect::<HashSet<_>>();
+        self.sources
+            .retain(|source| visited_urls.contains(&normalize_url(source)));
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CalendarEvent {
+    pub title: String,
+    pub start_date: String,
+    pub end_date: Option<String>,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+struct CalendarEventsResponse {
+    events: Vec<CalendarEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CalendarEventResult {
+    pub events: Vec<CalendarEvent>,
+    pub ical_export: Option<String>,
+}
+
+pub const LEGAL_DISCLAIMER: &str = "This is not legal advice. It is an automated summary of search results that may be incomplete, outdated, or wrong for your jurisdiction. Consult a qualified lawyer before acting on it.";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LegalStatute {
+    pub name: String,
+    pub citation: String,
+    pub relevance: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LegalCase {
+    pub name: String,
+    pub citation: String,
+    pub holding: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LegalCitationResult {
+    pub jurisdiction: String,
+    pub relevant_statutes: Vec<LegalStatute>,
+    pub relevant_cases: Vec<LegalCase>,
+    pub summary: String,
+    #[serde(default)]
+    pub disclaimer: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum QAConfidence {
+    #[serde(rename = "high")]
+    High,
+    #[serde(rename = "medium")]
+    Medium,
+    #[serde(rename = "low")]
+    Low,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QAPair {
+    pub question: String,
+    pub answer: String,
+    pub source_url: Option<String>,
+    pub confidence: QAConfidence,
+}
+
+#[derive(Deserialize)]
+struct QAPairsResponse {
+    qa_pairs: Vec<QAPair>,
+}
+
+pub const DEFAULT_QA_PAIR_COUNT: usize = 10;
+const MIN_QA_PAIR_COUNT: usize = 5;
+const MAX_QA_PAIR_COUNT: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum FinancialHealth {
+    #[serde(rename = "strong")]
+    Strong,
+    #[serde(rename = "moderate")]
+    Moderate,
+    #[serde(rename = "weak")]
+    Weak,
+    #[serde(rename = "unknown")]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FinancialSummary {
+    pub company_name: String,
+    pub ticker: Option<String>,
+    pub revenue: Option<String>,
+    pub net_income: Option<String>,
+    pub employees: Option<u64>,
+    pub founded: Option<String>,
+    pub headquarters: Option<String>,
+    #[serde(default)]
+    pub key_products: Vec<String>,
+    #[serde(default)]
+    pub recent_news: Vec<String>,
+    pub financial_health: FinancialHealth,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DryRunResult {
+    pub queries: Vec<String>,
+    pub strategy: AgentSearchStrategy,
+    pub reasoning: String,
+}
+
 pub async fn format_result(
     query: &str,
     analysis_document: &AnalysisDocument,
     result_format: &ResultFormat,
     custom_format_description: Option<&str>,
+    qa_pair_count: Option<usize>,
 ) -> Result<ResultFormatResponse, ResultFormatError> {
     match result_format {
         ResultFormat::Answer => format_result_answer(query, analysis_document).await,
@@ -89,6 +620,24 @@ pub async fn format_result(
                 Err(ResultFormatError::CustomFormatDescriptionMissing)
             }
         }
+        ResultFormat::ProsConsComparison => format_result_pros_cons(query, analysis_document).await,
+        ResultFormat::Timeline => format_result_timeline(query, analysis_document).await,
+        ResultFormat::ExecutiveSummary => {
+            format_result_executive_summary(query, analysis_document).await
+        }
+        ResultFormat::SwotAnalysis => format_result_swot(query, analysis_document).await,
+        ResultFormat::ProductComparison => {
+            format_result_product_comparison(query, analysis_document).await
+        }
+        ResultFormat::EntityProfile => format_result_entity_profile(query, analysis_document).await,
+        ResultFormat::CalendarEvent => format_result_calendar_event(query, analysis_document).await,
+        ResultFormat::LegalCitation => format_result_legal_citation(query, analysis_document).await,
+        ResultFormat::QAPairs => {
+            format_result_qa_pairs(query, analysis_document, qa_pair_count).await
+        }
+        ResultFormat::FinancialSummary => {
+            format_result_financial_summary(query, analysis_document).await
+        }
     }
 }
 
@@ -109,7 +658,7 @@ pub async fn format_result_answer(
                 .join("\n\n")
         ),
     };
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
         Ok(completion) => completion,
         Err(e) => return Err(ResultFormatError::LLMError(e)),
     };
@@ -133,7 +682,7 @@ pub async fn format_result_research_summary(
                 .join("\n\n")
         ),
     };
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
         Ok(completion) => completion,
         Err(e) => return Err(ResultFormatError::LLMError(e)),
     };
@@ -157,7 +706,7 @@ pub async fn format_result_faq(
                 .join("\n\n")
         ),
     };
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
         Ok(completion) => completion,
         Err(e) => return Err(ResultFormatError::LLMError(e)),
     };
@@ -182,7 +731,7 @@ pub async fn format_result_news_article(
                 .join("\n\n")
         ),
     };
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
         Ok(completion) => completion,
         Err(e) => return Err(ResultFormatError::LLMError(e)),
     };
@@ -212,7 +761,7 @@ pub async fn format_result_webpage(
                 .join("\n\n")
         ),
     };
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
         Ok(completion) => completion,
         Err(e) => return Err(ResultFormatError::LLMError(e)),
     };
@@ -243,9 +792,493 @@ pub async fn format_result_custom(
                 .join("\n\n")
         ),
     };
-    let completion = match default_completion(&prompt).await {
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
         Ok(completion) => completion,
         Err(e) => return Err(ResultFormatError::LLMError(e)),
     };
     Ok(ResultFormatResponse::Custom(completion))
 }
+
+pub async fn format_result_pros_cons(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_PROS_CONS_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let pros_cons: ProsConsResult =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(pros_cons) => pros_cons,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+    Ok(ResultFormatResponse::ProsConsComparison(pros_cons))
+}
+
+static TIMELINE_DATE_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+fn timeline_date_pattern() -> &'static Regex {
+    TIMELINE_DATE_PATTERN.get_or_init(|| {
+        Regex::new(
+            r"^(?<year>\d{4})(-(?<month>0[1-9]|1[0-2])(-(?<day>0[1-9]|[12]\d|3[01])(T(?<hour>\d{2}):(?<minute>\d{2})(:(?<second>\d{2})(\.\d+)?)?(Z|[+-]\d{2}:\d{2})?)?)?)?$",
+        )
+        .unwrap()
+    })
+}
+
+fn is_valid_timeline_date(date: &str) -> bool {
+    let captures = match timeline_date_pattern().captures(date) {
+        Some(captures) => captures,
+        None => return false,
+    };
+    let field = |name: &str| {
+        captures
+            .name(name)
+            .and_then(|m| m.as_str().parse::<u8>().ok())
+    };
+    if let (Some(year), Some(month), Some(day)) = (
+        captures
+            .name("year")
+            .and_then(|m| m.as_str().parse::<i32>().ok()),
+        field("month").and_then(|month| Month::try_from(month).ok()),
+        field("day"),
+    ) {
+        if Date::from_calendar_date(year, month, day).is_err() {
+            return false;
+        }
+    }
+    match (field("hour"), field("minute")) {
+        (Some(hour), Some(minute)) => {
+            Time::from_hms(hour, minute, field("second").unwrap_or(0)).is_ok()
+        }
+        _ => true,
+    }
+}
+
+pub async fn format_result_timeline(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_TIMELINE_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let mut completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let mut attempts = 1;
+    loop {
+        let mut events: Vec<TimelineEvent> = match parse_json_response_with_retry(
+            &completion,
+            &prompt,
+            DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
+        )
+        .await
+        {
+            Ok(events) => events,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+        let invalid_dates = events
+            .iter()
+            .filter(|event| !is_valid_timeline_date(&event.date))
+            .map(|event| event.date.clone())
+            .collect::<Vec<_>>();
+        if invalid_dates.is_empty() {
+            events.sort_by(|a, b| a.date.cmp(&b.date));
+            return Ok(ResultFormatResponse::Timeline(events));
+        }
+        if attempts >= DEFAULT_PARSE_JSON_MAX_ATTEMPTS {
+            return Err(ResultFormatError::InvalidTimelineDates(
+                invalid_dates.join(", "),
+            ));
+        }
+        attempts += 1;
+        let correction = format!(
+            "{}\n{}",
+            RESULT_FORMAT_TIMELINE_INVALID_DATES_PROMPT,
+            invalid_dates.join("\n")
+        );
+        completion = reprompt_with_correction(&prompt, &completion, correction).await?;
+    }
+}
+
+pub async fn format_result_legal_citation(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_LEGAL_CITATION_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let mut legal_citation: LegalCitationResult =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(legal_citation) => legal_citation,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+    legal_citation.disclaimer = LEGAL_DISCLAIMER.to_string();
+    Ok(ResultFormatResponse::LegalCitation(legal_citation))
+}
+
+pub async fn format_result_calendar_event(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_CALENDAR_EVENT_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let mut completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let mut attempts = 1;
+    loop {
+        let response: CalendarEventsResponse = match parse_json_response_with_retry(
+            &completion,
+            &prompt,
+            DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+        let invalid_dates = response
+            .events
+            .iter()
+            .flat_map(|event| std::iter::once(&event.start_date).chain(event.end_date.as_ref()))
+            .filter(|date| ical::parse_event_date(date).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        if invalid_dates.is_empty() {
+            let ical_export = if response.events.is_empty() {
+                None
+            } else {
+                Some(ical::to_ical(&response.events))
+            };
+            return Ok(ResultFormatResponse::CalendarEvent(CalendarEventResult {
+                events: response.events,
+                ical_export,
+            }));
+        }
+        if attempts >= DEFAULT_PARSE_JSON_MAX_ATTEMPTS {
+            return Err(ResultFormatError::InvalidCalendarEventDates(
+                invalid_dates.join(", "),
+            ));
+        }
+        attempts += 1;
+        let correction = format!(
+            "{}\n{}",
+            RESULT_FORMAT_CALENDAR_EVENT_INVALID_DATES_PROMPT,
+            invalid_dates.join("\n")
+        );
+        completion = reprompt_with_correction(&prompt, &completion, correction).await?;
+    }
+}
+
+async fn reprompt_with_correction(
+    prompt: &Prompt,
+    completion: &str,
+    correction: String,
+) -> Result<String, ResultFormatError> {
+    let mut messages = prompt.build_messages();
+    messages.push(Message {
+        role: Role::Assistant,
+        content: completion.to_string(),
+    });
+    messages.push(Message {
+        role: Role::User,
+        content: correction,
+    });
+    match default_completion_with_messages(messages, LLMCallStage::Formatting).await {
+        Ok(completion) => Ok(completion),
+        Err(e) => Err(ResultFormatError::LLMError(e)),
+    }
+}
+
+pub async fn format_result_executive_summary(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_EXECUTIVE_SUMMARY_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let executive_summary: ExecutiveSummaryResult =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(executive_summary) => executive_summary,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+    Ok(ResultFormatResponse::ExecutiveSummary(executive_summary))
+}
+
+pub async fn format_result_swot(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_SWOT_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let mut completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let mut attempts = 1;
+    loop {
+        let mut swot: SwotAnalysisResult = match parse_json_response_with_retry(
+            &completion,
+            &prompt,
+            DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
+        )
+        .await
+        {
+            Ok(swot) => swot,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+        let invalid_quadrants = swot.invalid_quadrants();
+        if invalid_quadrants.is_empty() {
+            swot.drop_unknown_source_urls(&analysis_document.visited_results);
+            return Ok(ResultFormatResponse::SwotAnalysis(swot));
+        }
+        if attempts >= DEFAULT_PARSE_JSON_MAX_ATTEMPTS {
+            return Err(ResultFormatError::InvalidSwotPointCounts(
+                invalid_quadrants.join(", "),
+            ));
+        }
+        attempts += 1;
+        let correction = format!(
+            "{}\n{}",
+            RESULT_FORMAT_SWOT_INVALID_POINT_COUNTS_PROMPT,
+            invalid_quadrants.join("\n")
+        );
+        completion = reprompt_with_correction(&prompt, &completion, correction).await?;
+    }
+}
+
+pub async fn format_result_product_comparison(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_PRODUCT_COMPARISON_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let mut product_comparison: ProductComparisonResult =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(product_comparison) => product_comparison,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+    product_comparison.drop_unknown_source_urls(&analysis_document.visited_results);
+    Ok(ResultFormatResponse::ProductComparison(product_comparison))
+}
+
+pub async fn format_result_entity_profile(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_ENTITY_PROFILE_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let mut entity_profile: EntityProfile =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(entity_profile) => entity_profile,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+    entity_profile.drop_unknown_sources(&analysis_document.visited_results);
+    let distinct_sources = analysis_document
+        .visited_results
+        .iter()
+        .map(|result| normalize_url(&result.url))
+        .collect::<HashSet<_>>();
+    entity_profile.low_confidence = distinct_sources.len() <= 1;
+    Ok(ResultFormatResponse::EntityProfile(entity_profile))
+}
+
+pub async fn format_result_qa_pairs(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+    qa_pair_count: Option<usize>,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let qa_pair_count = qa_pair_count
+        .unwrap_or(DEFAULT_QA_PAIR_COUNT)
+        .clamp(MIN_QA_PAIR_COUNT, MAX_QA_PAIR_COUNT);
+    let prompt = Prompt {
+        instruction: build_result_format_qa_pairs_system_prompt(qa_pair_count),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let response: QAPairsResponse =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+    let visited_urls = analysis_document
+        .visited_results
+        .iter()
+        .map(|result| normalize_url(&result.url))
+        .collect::<HashSet<_>>();
+    let qa_pairs = response
+        .qa_pairs
+        .into_iter()
+        .take(qa_pair_count)
+        .map(|mut qa_pair| {
+            if let Some(source_url) = &qa_pair.source_url {
+                if !visited_urls.contains(&normalize_url(source_url)) {
+                    qa_pair.source_url = None;
+                }
+            }
+            qa_pair
+        })
+        .collect();
+    Ok(ResultFormatResponse::QAPairs(qa_pairs))
+}
+
+pub async fn format_result_financial_summary(
+    query: &str,
+    analysis_document: &AnalysisDocument,
+) -> Result<ResultFormatResponse, ResultFormatError> {
+    let prompt = Prompt {
+        instruction: RESULT_FORMAT_FINANCIAL_SUMMARY_SYSTEM_PROMPT.to_string(),
+        context: format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            analysis_document
+                .visited_results
+                .iter()
+                .map(|r| format!("## {} ({})\n\n{}", r.title, r.url, r.content))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ),
+    };
+    let completion = match default_completion(&prompt, LLMCallStage::Formatting).await {
+        Ok(completion) => completion,
+        Err(e) => return Err(ResultFormatError::LLMError(e)),
+    };
+    let financial_summary: FinancialSummary =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(financial_summary) => financial_summary,
+            Err(e) => return Err(ResultFormatError::ParseError(e)),
+        };
+    Ok(ResultFormatResponse::FinancialSummary(financial_summary))
+}
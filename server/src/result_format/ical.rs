@@ -0,0 +1,142 @@
+use crate::result_format::CalendarEvent;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use time::format_description::well_known::Iso8601;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, Clone, Copy)]
+pub enum EventDate {
+    Date(Date),
+    Floating(PrimitiveDateTime),
+    Utc(OffsetDateTime),
+}
+
+pub fn parse_event_date(date: &str) -> Option<EventDate> {
+    let date = date.trim();
+    if let Ok(date_time) = OffsetDateTime::parse(date, &Iso8601::DEFAULT) {
+        return Some(EventDate::Utc(date_time.to_offset(UtcOffset::UTC)));
+    }
+    if let Ok(date_time) = PrimitiveDateTime::parse(date, &Iso8601::DEFAULT) {
+        return Some(EventDate::Floating(date_time));
+    }
+    match Date::parse(date, &Iso8601::DEFAULT) {
+        Ok(date) => Some(EventDate::Date(date)),
+        Err(_) => None,
+    }
+}
+
+impl EventDate {
+    fn date(&self) -> Date {
+        match self {
+            EventDate::Date(date) => *date,
+            EventDate::Floating(date_time) => date_time.date(),
+            EventDate::Utc(date_time) => date_time.date(),
+        }
+    }
+
+    fn value(&self) -> String {
+        let date_format = format_description!("[year][month][day]");
+        let date_time_format = format_description!("[year][month][day]T[hour][minute][second]");
+        match self {
+            EventDate::Date(date) => date.format(&date_format).unwrap_or_default(),
+            EventDate::Floating(date_time) => {
+                date_time.format(&date_time_format).unwrap_or_default()
+            }
+            EventDate::Utc(date_time) => {
+                format!(
+                    "{}Z",
+                    date_time.format(&date_time_format).unwrap_or_default()
+                )
+            }
+        }
+    }
+
+    fn to_property(self, name: &str) -> String {
+        match self {
+            EventDate::Date(_) => format!("{};VALUE=DATE:{}", name, self.value()),
+            _ => format!("{}:{}", name, self.value()),
+        }
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3);
+    let mut line_octets = 0;
+    for c in line.chars() {
+        if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            line_octets = 1;
+        }
+        folded.push(c);
+        line_octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+fn event_uid(event: &CalendarEvent, index: usize) -> String {
+    let mut hasher = DefaultHasher::new();
+    (&event.title, &event.start_date, index).hash(&mut hasher);
+    format!("{:016x}@web-browser", hasher.finish())
+}
+
+fn event_lines(event: &CalendarEvent, index: usize, dtstamp: &str) -> Option<Vec<String>> {
+    let start = parse_event_date(&event.start_date)?;
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", event_uid(event, index)),
+        format!("DTSTAMP:{}", dtstamp),
+        start.to_property("DTSTART"),
+    ];
+    let end = event.end_date.as_deref().and_then(parse_event_date);
+    match (start, end) {
+        (EventDate::Date(_), Some(end)) => {
+            lines.push(EventDate::Date(end.date() + Duration::days(1)).to_property("DTEND"))
+        }
+        (EventDate::Floating(_), Some(end @ EventDate::Floating(_)))
+        | (EventDate::Utc(_), Some(end @ EventDate::Utc(_))) => {
+            lines.push(end.to_property("DTEND"))
+        }
+        _ => {}
+    }
+    lines.push(format!("SUMMARY:{}", escape_text(&event.title)));
+    if let Some(location) = &event.location {
+        lines.push(format!("LOCATION:{}", escape_text(location)));
+    }
+    if let Some(url) = &event.url {
+        lines.push(format!("URL:{}", url.trim()));
+    }
+    if !event.description.is_empty() {
+        lines.push(format!("DESCRIPTION:{}", escape_text(&event.description)));
+    }
+    lines.push("END:VEVENT".to_string());
+    Some(lines)
+}
+
+pub fn to_ical(events: &[CalendarEvent]) -> String {
+    let dtstamp = EventDate::Utc(OffsetDateTime::now_utc()).value();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//web-browser//agent search//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for (index, event) in events.iter().enumerate() {
+        if let Some(event_lines) = event_lines(event, index, &dtstamp) {
+            lines.extend(event_lines);
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_line(line)).collect()
+}
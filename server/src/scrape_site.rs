@@ -1,13 +1,16 @@
-use crate::llm::{CompletionBuilder, LLMError};
+use crate::llm::{CompletionBuilder, LLMError, Tool};
 use crate::prompts::{Prompt, SCRAPE_SITE_RESULT_FORMAT_MD_SYSTEM_PROMPT};
 use crate::search::{search, SearchError, SearchInput, SearchResult};
-use crate::utils::{parse_json_response, ParseJsonError};
-use crate::webpage_parse::{visit_and_parse_webpage, ParsedWebpage, WebpageParseError};
+use crate::server::ServerState;
+use crate::utils::{normalize_url, parse_json_response, ParseJsonError};
+use crate::webpage_parse::{
+    validate_user_agent, visit_and_parse_webpage, DomParseConfig, ParsedWebpage, WebpageParseError,
+};
 use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::collections::HashSet;
 use thiserror::Error;
-use url::Url;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ScrapeSiteInput {
@@ -16,6 +19,9 @@ pub struct ScrapeSiteInput {
     pub result_format: Option<ScrapeSiteResultFormat>,
     pub max_concurrency: Option<usize>,
     pub explicit_urls_to_visit: Option<Vec<String>>,
+    pub html_strip_tags: Option<Vec<String>>,
+    pub html_keep_attributes: Option<Vec<String>>,
+    pub user_agent: Option<String>,
 }
 
 const DEFAULT_MAX_CONCURRENCY: usize = 10;
@@ -61,9 +67,11 @@ struct ParsedSearchResult {
 
 pub async fn scrape_site(
     scrape_input: &ScrapeSiteInput,
-    searx_host: &str,
-    searx_port: &str,
+    state: &ServerState,
 ) -> Result<Vec<ScrapeSiteResult>, ScrapeSiteError> {
+    if let Some(user_agent) = &scrape_input.user_agent {
+        validate_user_agent(user_agent)?;
+    }
     let num_pages = scrape_input
         .max_num_pages_to_visit
         .unwrap_or(MAX_NUM_PAGES_TO_VISIT);
@@ -72,65 +80,75 @@ pub async fn scrape_site(
         max_results_to_visit: Some(num_pages),
         whitelisted_base_urls: Some(vec![scrape_input.base_url.clone()]),
         blacklisted_base_urls: None,
+        safe_search: None,
+        spell_check_query: None,
+        language: None,
+        whitelisted_url_patterns: None,
     };
-    let mut json_results = match search(&search_input, searx_host, searx_port).await {
+    let mut json_results = match search(&search_input, &state.searx.client, &state.searx).await {
         Ok(results) => results,
         Err(e) => return Err(ScrapeSiteError::SearchError(e)),
     };
-    let mut visited_urls = HashSet::new();
-    for result in json_results.iter() {
-        match Url::parse(&result.url) {
-            Ok(parsed_url) => {
-                visited_urls.insert(parsed_url.to_string());
-            }
-            Err(_) => {
-                visited_urls.insert(result.url.clone());
-            }
-        }
-    }
+    json_results.retain(|result| result.infobox.is_none());
+    let mut visited_urls = json_results
+        .iter()
+        .map(|result| normalize_url(&result.url))
+        .collect::<HashSet<_>>();
     if let Some(explicit_urls_to_visit) = scrape_input.explicit_urls_to_visit.clone() {
         for url in explicit_urls_to_visit {
-            let should_add = match Url::parse(&url) {
-                Ok(parsed_url) => !visited_urls.contains(&parsed_url.to_string()),
-                Err(_) => !visited_urls.contains(&url),
-            };
-            if should_add {
+            if visited_urls.insert(normalize_url(&url)) {
                 json_results.push(SearchResult {
-                    url: url.clone(),
+                    url,
                     title: "[Title in article body]".to_string(),
                     content: "[Content in article body]".to_string(),
+                    published_at: None,
+                    modified_at: None,
+                    link_depth: None,
+                    infobox: None,
+                    anchor_text: None,
+                    score: None,
                 });
-                match Url::parse(&url) {
-                    Ok(parsed_url) => {
-                        visited_urls.insert(parsed_url.to_string());
-                    }
-                    Err(_) => {
-                        visited_urls.insert(url);
-                    }
-                }
             }
         }
     }
-    let futures = json_results
-        .into_iter()
-        .map(|result| async {
-            match visit_and_parse_webpage(&result.url).await {
+    let max_concurrency = scrape_input
+        .max_concurrency
+        .unwrap_or(DEFAULT_MAX_CONCURRENCY)
+        .max(1);
+    let dom_parse_config = &DomParseConfig::from_overrides(
+        scrape_input.html_strip_tags.as_ref(),
+        scrape_input.html_keep_attributes.as_ref(),
+    );
+    let results = stream::iter(json_results)
+        .map(|result| async move {
+            match visit_and_parse_webpage(
+                &result.url,
+                &state.http_client.0,
+                &state.user_agents,
+                scrape_input.user_agent.as_deref(),
+                &state.domain_rate_limiter,
+                dom_parse_config,
+            )
+            .await
+            {
                 Ok(parsed_webpage) => Ok(ParsedSearchResult {
-                    search_result: result,
+                    search_result: SearchResult {
+                        published_at: parsed_webpage.published_at.clone(),
+                        modified_at: parsed_webpage.modified_at.clone(),
+                        ..result
+                    },
                     parsed_webpage,
                 }),
                 Err(e) => Err(ScrapeSiteError::WebpageParseError(e)),
             }
         })
-        .collect::<Vec<_>>();
-    let results = futures::future::join_all(futures).await;
+        .buffer_unordered(max_concurrency)
+        .collect::<Vec<_>>()
+        .await;
     let results = results
         .into_iter()
         .filter_map(|r| r.ok())
         .collect::<Vec<_>>();
-    let max_concurrency = scrape_input
-        .max_concurrency
-        .unwrap_or(DEFAULT_MAX_CONCURRENCY);
 
     let default_result_format = ScrapeSiteResultFormat::default();
     let result_format = scrape_input
@@ -216,6 +234,27 @@ struct SearchResultObject {
     content: String,
 }
 
+fn search_result_object_tool() -> Tool {
+    Tool {
+        name: "write_markdown_webpage".to_string(),
+        description: "Write the markdown version of the webpage".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "title": {
+                    "type": "string",
+                    "description": "The title of the webpage"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The body of the webpage in markdown format"
+                }
+            },
+            "required": ["title", "content"]
+        }),
+    }
+}
+
 async fn format_result_md(
     search_result: SearchResult,
     parsed_webpage: ParsedWebpage,
@@ -228,8 +267,9 @@ async fn format_result_md(
         .model("gpt-4o".to_string())
         .provider("openai".to_string())
         .messages(prompt.clone().build_messages())
-        .temperature(0.0);
-    let completion = match builder.build().await {
+        .temperature(0.0)
+        .tools(vec![search_result_object_tool()]);
+    let completion = match builder.build_result().await {
         Ok(completion) => completion,
         Err(e) => return Err(ScrapeSiteFormatError::LLMError(e)),
     };
@@ -242,6 +282,12 @@ async fn format_result_md(
         title: search_result_object.title,
         url: search_result.url.clone(),
         content: search_result_object.content.clone(),
+        published_at: search_result.published_at.clone(),
+        modified_at: search_result.modified_at.clone(),
+        link_depth: search_result.link_depth,
+        infobox: search_result.infobox.clone(),
+        anchor_text: search_result.anchor_text.clone(),
+        score: search_result.score,
     };
     Ok(ScrapeSiteResult {
         search_result,
@@ -1,7 +1,28 @@
+use crate::llm::{default_completion, LLMError};
+use crate::prompts::{Prompt, RERANK_SEARCH_RESULTS_SYSTEM_PROMPT};
+use crate::query::correct_query_spelling;
+use crate::request_context::current_request_id;
+use crate::usage::LLMCallStage;
+use crate::utils::{
+    decode_html_entities, display_search_results_with_indices, duration_from_env_ms,
+    filter_results_by_patterns, normalize_url, parse_json_response_with_retry,
+    validate_and_normalize_search_result_url, DEFAULT_PARSE_JSON_MAX_ATTEMPTS,
+};
 use futures::future::join_all;
-use rocket::form::FromForm;
+use futures::stream::{self, StreamExt};
+use rocket::form::{FromForm, FromFormField};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use url::Url;
+
+pub mod domain_lists;
+pub mod pool;
+
+pub use domain_lists::{merge_domain_lists, spawn_domain_list_reload_on_sighup, GlobalDomainLists};
+pub use pool::{spawn_searx_health_checks, PoolStrategy, SearxInstance, SearxPool};
 
 #[derive(FromForm, Deserialize, Debug, Clone)]
 pub struct SearchInput {
@@ -12,6 +33,138 @@ pub struct SearchInput {
     pub whitelisted_base_urls: Option<Vec<String>>,
     #[serde(default)]
     pub blacklisted_base_urls: Option<Vec<String>>,
+    #[serde(default)]
+    pub safe_search: Option<SearxSafeSearch>,
+    #[serde(default)]
+    pub spell_check_query: Option<bool>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub whitelisted_url_patterns: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearxLanguage {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+impl SearxLanguage {
+    pub const fn new(code: &'static str, name: &'static str) -> Self {
+        Self { code, name }
+    }
+}
+
+pub const COMMON_SEARX_LANGUAGES: [SearxLanguage; 16] = [
+    SearxLanguage::new("all", "All languages"),
+    SearxLanguage::new("en", "English"),
+    SearxLanguage::new("en-US", "English (United States)"),
+    SearxLanguage::new("en-GB", "English (United Kingdom)"),
+    SearxLanguage::new("de", "German"),
+    SearxLanguage::new("es", "Spanish"),
+    SearxLanguage::new("fr", "French"),
+    SearxLanguage::new("it", "Italian"),
+    SearxLanguage::new("pt", "Portuguese"),
+    SearxLanguage::new("nl", "Dutch"),
+    SearxLanguage::new("pl", "Polish"),
+    SearxLanguage::new("ru", "Russian"),
+    SearxLanguage::new("ja", "Japanese"),
+    SearxLanguage::new("ko", "Korean"),
+    SearxLanguage::new("zh", "Chinese"),
+    SearxLanguage::new("ar", "Arabic"),
+];
+
+pub const DEFAULT_SEARX_LANGUAGE: &str = "en";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, FromFormField)]
+pub enum SearxSafeSearch {
+    #[serde(rename = "off")]
+    Off,
+    #[default]
+    #[serde(rename = "moderate")]
+    Moderate,
+    #[serde(rename = "strict")]
+    Strict,
+}
+
+impl SearxSafeSearch {
+    pub fn searx_value(&self) -> &'static str {
+        match self {
+            SearxSafeSearch::Off => "0",
+            SearxSafeSearch::Moderate => "1",
+            SearxSafeSearch::Strict => "2",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "0" | "off" => Some(SearxSafeSearch::Off),
+            "1" | "moderate" => Some(SearxSafeSearch::Moderate),
+            "2" | "strict" => Some(SearxSafeSearch::Strict),
+            _ => None,
+        }
+    }
+}
+
+const DEFAULT_SEARX_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_SEARX_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn build_searx_client() -> reqwest::Client {
+    match reqwest::Client::builder()
+        .connect_timeout(duration_from_env_ms(
+            "SEARX_CONNECT_TIMEOUT_MS",
+            DEFAULT_SEARX_CONNECT_TIMEOUT,
+        ))
+        .read_timeout(duration_from_env_ms(
+            "SEARX_READ_TIMEOUT_MS",
+            DEFAULT_SEARX_READ_TIMEOUT,
+        ))
+        .tcp_keepalive(Duration::from_secs(60))
+        .gzip(true)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!("Failed to build SearxNG HTTP client, using defaults: {}", e);
+            reqwest::Client::new()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearxConfig {
+    pub pool: Arc<SearxPool>,
+    pub client: reqwest::Client,
+    pub results_per_page: usize,
+    pub default_safe_search: SearxSafeSearch,
+    pub default_language: String,
+}
+
+impl SearxConfig {
+    pub fn from_env() -> Self {
+        Self {
+            pool: Arc::new(SearxPool::from_env()),
+            client: build_searx_client(),
+            results_per_page: std::env::var("SEARX_RESULTS_PER_PAGE")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|&value| value > 0)
+                .unwrap_or(SEARX_RESULTS_PER_PAGE),
+            default_safe_search: std::env::var("SEARX_DEFAULT_SAFE_SEARCH")
+                .ok()
+                .and_then(|value| SearxSafeSearch::parse(&value))
+                .unwrap_or_default(),
+            default_language: std::env::var("SEARX_DEFAULT_LANGUAGE")
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| DEFAULT_SEARX_LANGUAGE.to_string()),
+        }
+    }
+
+    pub fn num_pages(&self, max_results: usize) -> usize {
+        max_results.div_ceil(self.results_per_page.max(1))
+    }
 }
 
 impl Default for SearchInput {
@@ -21,6 +174,10 @@ impl Default for SearchInput {
             max_results_to_visit: Some(10),
             whitelisted_base_urls: None,
             blacklisted_base_urls: None,
+            safe_search: None,
+            spell_check_query: None,
+            language: None,
+            whitelisted_url_patterns: None,
         }
     }
 }
@@ -33,6 +190,13 @@ impl SearchInput {
             self.blacklisted_base_urls.as_ref(),
         )
     }
+
+    fn filter_by_url_patterns(&self, results: Vec<SearchResult>) -> Vec<SearchResult> {
+        match &self.whitelisted_url_patterns {
+            Some(patterns) => filter_results_by_patterns(results, patterns),
+            None => results,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, FromForm)]
@@ -40,6 +204,18 @@ pub struct SearchResult {
     pub title: String,
     pub url: String,
     pub content: String,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub modified_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_depth: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub infobox: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
 }
 
 impl std::fmt::Display for SearchResult {
@@ -64,13 +240,52 @@ pub struct SearxSearchResult {
     pub score: Option<f64>,
     pub title: String,
     pub url: String,
+    #[serde(default)]
+    pub anchor_text: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearxInfoboxAttribute {
+    pub label: String,
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearxInfobox {
+    pub infobox: String,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub attributes: Vec<SearxInfoboxAttribute>,
+}
+
+impl SearxInfobox {
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![self.infobox.clone()];
+        if let Some(content) = &self.content {
+            if !content.trim().is_empty() {
+                lines.push(content.trim().to_string());
+            }
+        }
+        for attribute in &self.attributes {
+            let value = match &attribute.value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            lines.push(format!("- {}: {}", attribute.label, value));
+        }
+        lines.join("\n")
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearxResponse {
     pub answers: Vec<String>,
     pub corrections: Vec<String>,
-    pub infoboxes: Vec<String>,
+    #[serde(default)]
+    pub infoboxes: Vec<SearxInfobox>,
     pub number_of_results: f64,
     pub query: String,
     pub results: Vec<SearxSearchResult>,
@@ -84,30 +299,30 @@ pub enum SearchError {
     InvalidSearxUrl { host: String, port: u16 },
     #[error("Searx returned error: {0}")]
     SearxError(String),
+    #[error("Query spell check failed: {0}")]
+    SpellCheckError(LLMError),
 }
 
-async fn single_page_search(
+async fn single_instance_search(
+    instance: &SearxInstance,
     query: &str,
-    searx_host: &str,
-    searx_port: &str,
+    client: &reqwest::Client,
+    safe_search: SearxSafeSearch,
+    language: &str,
     pageno: usize,
-) -> Result<Vec<SearchResult>, SearchError> {
-    let searx_url = format!("http://{}:{}/search", searx_host, searx_port);
-    let client = match reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(60))
-        .build()
-    {
-        Ok(client) => client,
-        Err(e) => return Err(SearchError::RequestError(e)),
-    };
+    results_on_page: usize,
+) -> Result<SearxResponse, SearchError> {
+    let searx_url = format!("{}/search", instance.base_url());
     let response = client
         .get(&searx_url)
         .query(&[
             ("q", query),
             ("format", "json"),
-            ("language", "en"),
+            ("language", language),
             ("engines", "google"),
             ("pageno", pageno.to_string().as_str()),
+            ("results_on_page", results_on_page.to_string().as_str()),
+            ("safesearch", safe_search.searx_value()),
         ])
         .send()
         .await
@@ -118,22 +333,105 @@ async fn single_page_search(
             response.status()
         )));
     }
-    let searx_response = match response
+    response
         .json::<SearxResponse>()
         .await
         .map_err(SearchError::RequestError)
-    {
-        Ok(searx_response) => searx_response,
-        Err(e) => return Err(e),
+}
+
+async fn single_page_search(
+    query: &str,
+    client: &reqwest::Client,
+    searx: &SearxConfig,
+    safe_search: SearxSafeSearch,
+    language: &str,
+    pageno: usize,
+    results_on_page: usize,
+) -> Result<Vec<SearchResult>, SearchError> {
+    let mut last_error = SearchError::SearxError("No searx instances configured".to_string());
+    let mut searx_response = None;
+    for instance in searx.pool.instances_to_try() {
+        let started_at = Instant::now();
+        match single_instance_search(
+            &instance,
+            query,
+            client,
+            safe_search,
+            language,
+            pageno,
+            results_on_page,
+        )
+        .await
+        {
+            Ok(response) => {
+                instance.record_latency(started_at.elapsed());
+                instance.mark_healthy();
+                searx_response = Some(response);
+                break;
+            }
+            Err(e) => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Searx instance {} failed, marking unhealthy: {}",
+                    instance.base_url(),
+                    e
+                );
+                instance.mark_unhealthy();
+                last_error = e;
+            }
+        }
+    }
+    let searx_response = match searx_response {
+        Some(searx_response) => searx_response,
+        None => return Err(last_error),
     };
-    Ok(searx_response
-        .results
-        .into_iter()
-        .map(|result| SearchResult {
-            title: result.title,
-            url: result.url,
-            content: result.content,
+    let infobox_result = if searx_response.infoboxes.is_empty() {
+        None
+    } else {
+        let infobox_content = searx_response
+            .infoboxes
+            .iter()
+            .map(|infobox| infobox.to_text())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Some(SearchResult {
+            title: "Direct Answer".to_string(),
+            url: String::new(),
+            content: infobox_content.clone(),
+            published_at: None,
+            modified_at: None,
+            link_depth: None,
+            infobox: Some(infobox_content),
+            anchor_text: None,
+            score: None,
         })
+    };
+    Ok(infobox_result
+        .into_iter()
+        .chain(searx_response.results.into_iter().filter_map(|result| {
+            let url = match validate_and_normalize_search_result_url(&result.url) {
+                Some(url) => url,
+                None => {
+                    tracing::warn!(
+                        request_id = %current_request_id(),
+                        "Skipping search result with invalid URL: {}",
+                        result.url
+                    );
+                    return None;
+                }
+            };
+            Some(SearchResult {
+                title: decode_html_entities(&result.title),
+                url,
+                content: decode_html_entities(&result.content),
+                published_at: None,
+                modified_at: None,
+                link_depth: None,
+                infobox: None,
+                anchor_text: result.anchor_text,
+                score: result.score,
+            })
+        }))
         .collect())
 }
 
@@ -142,35 +440,199 @@ pub const SEARX_RESULTS_PER_PAGE: usize = 8;
 
 pub async fn search(
     search_input: &SearchInput,
-    searx_host: &str,
-    searx_port: &str,
+    client: &reqwest::Client,
+    searx: &SearxConfig,
 ) -> Result<Vec<SearchResult>, SearchError> {
     let max_results = search_input
         .max_results_to_visit
         .unwrap_or(MAX_RESULTS_TO_VISIT);
-    let num_pages = (max_results + SEARX_RESULTS_PER_PAGE - 1) / SEARX_RESULTS_PER_PAGE;
+    let results_per_page = searx.results_per_page.max(1);
+    let safe_search = search_input
+        .safe_search
+        .unwrap_or(searx.default_safe_search);
+    let language = search_input
+        .language
+        .as_deref()
+        .unwrap_or(&searx.default_language);
+    let num_pages = searx.num_pages(max_results);
+    let corrected_input;
+    let search_input = if search_input.spell_check_query.unwrap_or(false) {
+        match correct_query_spelling(&search_input.query).await {
+            Ok(correction) if correction.was_changed => {
+                corrected_input = SearchInput {
+                    query: correction.corrected,
+                    ..search_input.clone()
+                };
+                &corrected_input
+            }
+            Ok(_) => search_input,
+            Err(e) => return Err(SearchError::SpellCheckError(e)),
+        }
+    } else {
+        search_input
+    };
     let query = search_input.build_google_search_query();
-    let futures: Vec<_> = (1..=num_pages)
-        .map(|pageno| single_page_search(&query, searx_host, searx_port, pageno))
-        .collect();
-    let results = join_all(futures).await;
+    let fetch_page = |pageno| {
+        single_page_search(
+            &query,
+            client,
+            searx,
+            safe_search,
+            language,
+            pageno,
+            results_per_page,
+        )
+    };
+    let num_web_results = |page_results: &[SearchResult]| {
+        page_results
+            .iter()
+            .filter(|result| result.infobox.is_none())
+            .count()
+    };
+    let mut pages = vec![fetch_page(1).await?];
+    if num_pages > 1 && num_web_results(&pages[0]) >= results_per_page {
+        for page_results in join_all((2..=num_pages).map(fetch_page)).await {
+            pages.push(page_results?);
+        }
+    }
     let mut all_results = Vec::new();
-    for page_result in results {
-        match page_result {
-            Ok(page_results) => {
-                for result in page_results {
-                    if all_results.len() >= max_results {
-                        break;
-                    }
-                    all_results.push(result);
-                }
-            }
-            Err(e) => return Err(e),
+    for page_results in pages {
+        let is_last_page = num_web_results(&page_results) < results_per_page;
+        all_results.extend(search_input.filter_by_url_patterns(page_results));
+        all_results = dedup_search_results(all_results);
+        all_results.truncate(max_results);
+        if is_last_page || all_results.len() >= max_results {
+            break;
         }
     }
     Ok(all_results)
 }
 
+pub async fn search_page(
+    search_input: &SearchInput,
+    client: &reqwest::Client,
+    searx: &SearxConfig,
+    pageno: usize,
+) -> Result<Vec<SearchResult>, SearchError> {
+    let safe_search = search_input
+        .safe_search
+        .unwrap_or(searx.default_safe_search);
+    let language = search_input
+        .language
+        .as_deref()
+        .unwrap_or(&searx.default_language);
+    let page_results = single_page_search(
+        &search_input.build_google_search_query(),
+        client,
+        searx,
+        safe_search,
+        language,
+        pageno,
+        searx.results_per_page,
+    )
+    .await?;
+    Ok(dedup_search_results(
+        search_input.filter_by_url_patterns(page_results),
+    ))
+}
+
+pub fn dedup_search_results(results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut seen_urls = HashSet::new();
+    results
+        .into_iter()
+        .filter(|result| seen_urls.insert(normalize_url(&result.url)))
+        .collect()
+}
+
+pub async fn rerank_search_results(
+    query: &str,
+    results: Vec<SearchResult>,
+    max_results: usize,
+) -> Result<Vec<SearchResult>, LLMError> {
+    let prompt = Prompt::new(
+        RERANK_SEARCH_RESULTS_SYSTEM_PROMPT.to_string(),
+        format!(
+            "# Query:\n{}\n\n# Search results:\n{}",
+            query,
+            display_search_results_with_indices(&results)
+        ),
+    );
+    let completion = default_completion(&prompt, LLMCallStage::Reranking).await?;
+    let ranking: Vec<usize> =
+        match parse_json_response_with_retry(&completion, &prompt, DEFAULT_PARSE_JSON_MAX_ATTEMPTS)
+            .await
+        {
+            Ok(ranking) => ranking,
+            Err(e) => return Err(LLMError::Other(e.to_string())),
+        };
+    let mut ranked_indices = Vec::new();
+    for idx in ranking {
+        if idx < results.len() && !ranked_indices.contains(&idx) {
+            ranked_indices.push(idx);
+        }
+    }
+    for idx in 0..results.len() {
+        if !ranked_indices.contains(&idx) {
+            ranked_indices.push(idx);
+        }
+    }
+    let mut results = results.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(ranked_indices
+        .into_iter()
+        .take(max_results)
+        .filter_map(|idx| results[idx].take())
+        .collect())
+}
+
+pub const DEFAULT_BATCH_SEARCH_MAX_CONCURRENCY: usize = 5;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BatchSearchInput {
+    pub queries: Vec<SearchInput>,
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum BatchSearchResult {
+    Success {
+        query: String,
+        results: Vec<SearchResult>,
+    },
+    Error {
+        query: String,
+        error: String,
+    },
+}
+
+pub async fn batch_search(
+    batch_input: &BatchSearchInput,
+    client: &reqwest::Client,
+    searx: &SearxConfig,
+) -> Vec<BatchSearchResult> {
+    let max_concurrency = batch_input
+        .max_concurrency
+        .unwrap_or(DEFAULT_BATCH_SEARCH_MAX_CONCURRENCY)
+        .max(1);
+    stream::iter(batch_input.queries.clone())
+        .map(|search_input| async move {
+            match search(&search_input, client, searx).await {
+                Ok(results) => BatchSearchResult::Success {
+                    query: search_input.query,
+                    results,
+                },
+                Err(e) => BatchSearchResult::Error {
+                    query: search_input.query,
+                    error: e.to_string(),
+                },
+            }
+        })
+        .buffer_unordered(max_concurrency)
+        .collect::<Vec<_>>()
+        .await
+}
+
 pub fn build_google_search_query(
     query: &str,
     whitelisted_base_urls: Option<&Vec<String>>,
@@ -192,3 +654,52 @@ pub fn build_google_search_query(
     }
     parts.join(" ")
 }
+
+fn matches_base_url(url: &Url, base_url: &str) -> bool {
+    let base_url = base_url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let (base_host, base_path) = base_url.split_once('/').unwrap_or((base_url, ""));
+    let base_host = base_host.trim_start_matches("www.").to_lowercase();
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches("www.").to_lowercase(),
+        None => return false,
+    };
+    let host_matches = host == base_host || host.ends_with(&format!(".{}", base_host));
+    let base_path = base_path.trim_end_matches('/');
+    host_matches
+        && (base_path.is_empty()
+            || url
+                .path()
+                .trim_start_matches('/')
+                .strip_prefix(base_path)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+}
+
+pub fn is_url_allowed(
+    url: &str,
+    whitelisted_base_urls: Option<&Vec<String>>,
+    blacklisted_base_urls: Option<&Vec<String>>,
+) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    if let Some(whitelist) = whitelisted_base_urls {
+        if !whitelist
+            .iter()
+            .any(|base_url| matches_base_url(&url, base_url))
+        {
+            return false;
+        }
+    }
+    if let Some(blacklist) = blacklisted_base_urls {
+        if blacklist
+            .iter()
+            .any(|base_url| matches_base_url(&url, base_url))
+        {
+            return false;
+        }
+    }
+    true
+}
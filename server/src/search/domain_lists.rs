@@ -0,0 +1,124 @@
+use std::sync::{Arc, RwLock};
+
+const DOMAIN_ALLOWLIST_FILE_ENV: &str = "DOMAIN_ALLOWLIST_FILE";
+const DOMAIN_BLOCKLIST_FILE_ENV: &str = "DOMAIN_BLOCKLIST_FILE";
+
+#[derive(Debug, Default)]
+pub struct GlobalDomainLists {
+    allowlist: RwLock<Arc<Vec<String>>>,
+    blocklist: RwLock<Arc<Vec<String>>>,
+}
+
+impl GlobalDomainLists {
+    pub fn from_env() -> Self {
+        let lists = Self::default();
+        lists.reload();
+        lists
+    }
+
+    pub fn allowlist(&self) -> Arc<Vec<String>> {
+        match self.allowlist.read() {
+            Ok(allowlist) => allowlist.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub fn blocklist(&self) -> Arc<Vec<String>> {
+        match self.blocklist.read() {
+            Ok(blocklist) => blocklist.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub fn reload(&self) {
+        if let Some(allowlist) = load_domain_list(DOMAIN_ALLOWLIST_FILE_ENV) {
+            swap_list(&self.allowlist, allowlist);
+        }
+        if let Some(blocklist) = load_domain_list(DOMAIN_BLOCKLIST_FILE_ENV) {
+            swap_list(&self.blocklist, blocklist);
+        }
+    }
+}
+
+fn swap_list(list: &RwLock<Arc<Vec<String>>>, domains: Vec<String>) {
+    let domains = Arc::new(domains);
+    match list.write() {
+        Ok(mut list) => *list = domains,
+        Err(poisoned) => *poisoned.into_inner() = domains,
+    }
+}
+
+fn load_domain_list(env_var: &str) -> Option<Vec<String>> {
+    let path = std::env::var(env_var).ok()?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            let domains = parse_domain_list(&contents);
+            tracing::info!(
+                "Loaded {} domains from {} ({})",
+                domains.len(),
+                path,
+                env_var
+            );
+            Some(domains)
+        }
+        Err(e) => {
+            tracing::error!("Failed to read {} ({}): {}", path, env_var, e);
+            None
+        }
+    }
+}
+
+fn parse_domain_list(contents: &str) -> Vec<String> {
+    let mut domains: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let domain = line.trim();
+        if domain.is_empty() || domain.starts_with('#') {
+            continue;
+        }
+        let domain = domain.to_lowercase();
+        if !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    domains
+}
+
+pub fn merge_domain_lists(
+    request_list: Option<&Vec<String>>,
+    global_list: &[String],
+) -> Option<Vec<String>> {
+    if global_list.is_empty() {
+        return request_list.cloned();
+    }
+    let mut merged = request_list.cloned().unwrap_or_default();
+    for domain in global_list {
+        if !merged
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(domain))
+        {
+            merged.push(domain.clone());
+        }
+    }
+    Some(merged)
+}
+
+#[cfg(unix)]
+pub fn spawn_domain_list_reload_on_sighup(lists: Arc<GlobalDomainLists>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::error!("Failed to install SIGHUP handler for domain lists: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            tracing::info!("Received SIGHUP, reloading domain lists");
+            lists.reload();
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn spawn_domain_list_reload_on_sighup(_lists: Arc<GlobalDomainLists>) {}
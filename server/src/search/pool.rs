@@ -0,0 +1,189 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_SEARX_HOST: &str = "localhost";
+const DEFAULT_SEARX_PORT: &str = "8096";
+pub const SEARX_UNHEALTHY_DURATION: Duration = Duration::from_secs(30);
+pub const SEARX_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+#[derive(Debug)]
+pub struct SearxInstance {
+    pub host: String,
+    pub port: String,
+    unhealthy_since: Mutex<Option<Instant>>,
+    latency_ms: AtomicU64,
+}
+
+impl SearxInstance {
+    pub fn new(host: &str, port: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            port: port.to_string(),
+            unhealthy_since: Mutex::new(None),
+            latency_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}:{}", self.host, self.port)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        match *self.unhealthy_since.lock().unwrap() {
+            Some(unhealthy_since) => unhealthy_since.elapsed() >= SEARX_UNHEALTHY_DURATION,
+            None => true,
+        }
+    }
+
+    fn is_marked_unhealthy(&self) -> bool {
+        self.unhealthy_since.lock().unwrap().is_some()
+    }
+
+    pub fn mark_unhealthy(&self) {
+        *self.unhealthy_since.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn mark_healthy(&self) {
+        *self.unhealthy_since.lock().unwrap() = None;
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        let latency_ms = latency.as_millis() as f64;
+        let _ = self
+            .latency_ms
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |previous_ms| {
+                if previous_ms == 0 {
+                    return Some(latency_ms.max(1.0) as u64);
+                }
+                let smoothed_ms =
+                    LATENCY_SMOOTHING * latency_ms + (1.0 - LATENCY_SMOOTHING) * previous_ms as f64;
+                Some(smoothed_ms.max(1.0) as u64)
+            });
+    }
+
+    pub fn latency_ms(&self) -> u64 {
+        self.latency_ms.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolStrategy {
+    #[default]
+    RoundRobin,
+    LeastLatency,
+}
+
+impl PoolStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "round_robin" => Some(PoolStrategy::RoundRobin),
+            "least_latency" => Some(PoolStrategy::LeastLatency),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SearxPool {
+    pub instances: Vec<Arc<SearxInstance>>,
+    pub strategy: PoolStrategy,
+    next_instance: AtomicUsize,
+}
+
+fn parse_searx_instances(value: &str) -> Vec<Arc<SearxInstance>> {
+    value
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (host, port) = entry
+                .rsplit_once(':')
+                .unwrap_or((entry, DEFAULT_SEARX_PORT));
+            Arc::new(SearxInstance::new(host, port))
+        })
+        .collect()
+}
+
+impl SearxPool {
+    pub fn new(instances: Vec<Arc<SearxInstance>>, strategy: PoolStrategy) -> Self {
+        Self {
+            instances,
+            strategy,
+            next_instance: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let mut instances = std::env::var("SEARX_INSTANCES")
+            .map(|value| parse_searx_instances(&value))
+            .unwrap_or_default();
+        if instances.is_empty() {
+            let host =
+                std::env::var("SEARX_HOST").unwrap_or_else(|_| DEFAULT_SEARX_HOST.to_string());
+            let port =
+                std::env::var("SEARX_PORT").unwrap_or_else(|_| DEFAULT_SEARX_PORT.to_string());
+            instances.push(Arc::new(SearxInstance::new(&host, &port)));
+        }
+        let strategy = std::env::var("SEARX_POOL_STRATEGY")
+            .ok()
+            .and_then(|value| PoolStrategy::parse(&value))
+            .unwrap_or_default();
+        Self::new(instances, strategy)
+    }
+
+    pub fn instances_to_try(&self) -> Vec<Arc<SearxInstance>> {
+        let mut instances = self.instances.clone();
+        match self.strategy {
+            PoolStrategy::RoundRobin => {
+                if !instances.is_empty() {
+                    let start = self.next_instance.fetch_add(1, Ordering::Relaxed);
+                    instances.rotate_left(start % self.instances.len());
+                }
+            }
+            PoolStrategy::LeastLatency => {
+                instances.sort_by_key(|instance| instance.latency_ms());
+            }
+        }
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = instances
+            .into_iter()
+            .partition(|instance| instance.is_healthy());
+        if healthy.is_empty() {
+            return unhealthy;
+        }
+        healthy
+    }
+
+    pub async fn probe_unhealthy_instances(&self, client: &reqwest::Client) {
+        for instance in self
+            .instances
+            .iter()
+            .filter(|instance| instance.is_marked_unhealthy())
+        {
+            let healthz_url = format!("{}/healthz", instance.base_url());
+            match client
+                .get(&healthz_url)
+                .timeout(Duration::from_secs(5))
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => {
+                    tracing::info!("Searx instance {} is healthy again", instance.base_url());
+                    instance.mark_healthy();
+                }
+                _ => instance.mark_unhealthy(),
+            }
+        }
+    }
+}
+
+pub fn spawn_searx_health_checks(pool: Arc<SearxPool>, client: reqwest::Client) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SEARX_HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            pool.probe_unhealthy_instances(&client).await;
+        }
+    });
+}
@@ -0,0 +1,107 @@
+use crate::agent_search::AgentSearchResult;
+use crate::llm::{default_completion, LLMError};
+use crate::prompts::{Prompt, SEARCH_DIFF_SYSTEM_PROMPT};
+use crate::search::SearchResult;
+use crate::usage::LLMCallStage;
+use crate::utils::normalize_url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchDiffInput {
+    pub result_a: AgentSearchResult,
+    pub result_b: AgentSearchResult,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchDiff {
+    pub added_sources: Vec<SearchResult>,
+    pub removed_sources: Vec<SearchResult>,
+    pub analysis_diff: String,
+}
+
+#[derive(Error, Debug)]
+pub enum SearchDiffError {
+    #[error("LLM error: {0}")]
+    LLMError(#[from] LLMError),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+#[derive(Serialize)]
+struct SourceSummary<'a> {
+    title: &'a str,
+    url: &'a str,
+}
+
+fn visited_url_set(result: &AgentSearchResult) -> HashSet<String> {
+    result
+        .raw_analysis
+        .visited_results
+        .iter()
+        .map(|source| normalize_url(&source.url))
+        .collect()
+}
+
+fn sources_not_in(result: &AgentSearchResult, urls: &HashSet<String>) -> Vec<SearchResult> {
+    let mut seen = HashSet::new();
+    result
+        .raw_analysis
+        .visited_results
+        .iter()
+        .filter(|source| {
+            let url = normalize_url(&source.url);
+            !urls.contains(&url) && seen.insert(url)
+        })
+        .cloned()
+        .collect()
+}
+
+fn display_sources(result: &AgentSearchResult) -> Result<String, serde_json::Error> {
+    let sources = result
+        .raw_analysis
+        .visited_results
+        .iter()
+        .map(|source| SourceSummary {
+            title: &source.title,
+            url: &source.url,
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string_pretty(&sources)
+}
+
+pub async fn diff_search_results(input: &SearchDiffInput) -> Result<SearchDiff, SearchDiffError> {
+    let urls_a = visited_url_set(&input.result_a);
+    let urls_b = visited_url_set(&input.result_b);
+    let added_sources = sources_not_in(&input.result_b, &urls_a);
+    let removed_sources = sources_not_in(&input.result_a, &urls_b);
+    let content_a = &input.result_a.raw_analysis.content;
+    let content_b = &input.result_b.raw_analysis.content;
+    let analysis_diff = if content_a.trim() == content_b.trim() {
+        "No factual differences: the two analyses are identical.".to_string()
+    } else {
+        let query = input
+            .result_a
+            .queries_executed
+            .first()
+            .or(input.result_b.queries_executed.first())
+            .map(|record| record.query.clone())
+            .unwrap_or_default();
+        let user_prompt = format!(
+            "# Query\n{}\n\n# Analysis A\n{}\n\n# Sources used by A\n{}\n\n# Analysis B\n{}\n\n# Sources used by B\n{}",
+            query,
+            content_a,
+            display_sources(&input.result_a)?,
+            content_b,
+            display_sources(&input.result_b)?
+        );
+        let prompt = Prompt::new(SEARCH_DIFF_SYSTEM_PROMPT.to_string(), user_prompt);
+        default_completion(&prompt, LLMCallStage::Aggregation).await?
+    };
+    Ok(SearchDiff {
+        added_sources,
+        removed_sources,
+        analysis_diff,
+    })
+}
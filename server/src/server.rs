@@ -1,13 +1,50 @@
+use crate::agent_search::{AgentSearchError, AgentSearchInput, AgentSearchResult};
+use crate::grpc_server::GrpcServerError;
+use crate::handlers::diff::handle_search_diff;
+use crate::handlers::metrics::handle_metrics;
+use crate::handlers::openapi::{check_openapi_spec_routes, handle_openapi_spec, handle_swagger_ui};
+use crate::handlers::site_search::handle_site_search;
 use crate::handlers::v1::agent_search::handle_agent_search;
+use crate::handlers::v1::agent_search_ws::handle_agent_search_ws;
+use crate::handlers::v1::batch_search::handle_batch_search;
+use crate::handlers::v1::estimate_cost::handle_estimate_cost;
 use crate::handlers::v1::scrape_site::handle_scrape_site;
 use crate::handlers::v1::search::handle_search;
+use crate::index::{LocalPageIndex, PageCache};
+use crate::llm::cache::CompletionCache;
+use crate::llm::circuit_breaker::CircuitBreakers;
+use crate::llm::{
+    set_default_circuit_breakers, set_default_completion_cache, set_default_llm_client_pool,
+    LLMClientPool,
+};
+use crate::search::{
+    spawn_domain_list_reload_on_sighup, spawn_searx_health_checks, GlobalDomainLists, SearxConfig,
+};
+use crate::server::compression::ResponseCompressionFairing;
+use crate::server::envelope::ResponseEnvelopeFairing;
+use crate::utils::duration_from_env_ms;
+use crate::webpage_parse::{
+    load_user_agents, ssrf_safe_redirect_policy, DomainRateLimiter, SsrfSafeResolver,
+};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
 use rocket::routes;
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+pub mod compression;
+pub mod envelope;
 
 #[derive(Debug)]
 pub enum ServerError {
     Launch(rocket::Error),
     Configuration(String),
     Environment(std::env::VarError),
+    Grpc(GrpcServerError),
 }
 
 impl std::error::Error for ServerError {}
@@ -18,35 +55,216 @@ impl std::fmt::Display for ServerError {
             ServerError::Launch(e) => write!(f, "Server launch error: {}", e),
             ServerError::Configuration(e) => write!(f, "Configuration error: {}", e),
             ServerError::Environment(e) => write!(f, "Environment variable error: {}", e),
+            ServerError::Grpc(e) => write!(f, "gRPC server error: {}", e),
         }
     }
 }
 
+const DEFAULT_WEBPAGE_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_WEBPAGE_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub struct SharedHttpClient(pub reqwest::Client);
+
+impl SharedHttpClient {
+    pub fn new() -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .connection_verbose(false)
+            .tcp_keepalive(Duration::from_secs(60))
+            .pool_max_idle_per_host(20)
+            .connect_timeout(duration_from_env_ms(
+                "WEBPAGE_CONNECT_TIMEOUT_MS",
+                DEFAULT_WEBPAGE_CONNECT_TIMEOUT,
+            ))
+            .read_timeout(duration_from_env_ms(
+                "WEBPAGE_READ_TIMEOUT_MS",
+                DEFAULT_WEBPAGE_READ_TIMEOUT,
+            ))
+            .gzip(true)
+            .redirect(ssrf_safe_redirect_policy())
+            .dns_resolver(Arc::new(SsrfSafeResolver))
+            .build()?;
+        Ok(Self(client))
+    }
+}
+
+const DEFAULT_REQUEST_DEDUP_WINDOW_SECS: u64 = 30;
+
+type InFlightResult = Result<AgentSearchResult, Arc<str>>;
+type InFlightSender = Arc<broadcast::Sender<InFlightResult>>;
+
+enum InFlightRole {
+    Leader(InFlightSender),
+    Follower(broadcast::Receiver<InFlightResult>),
+}
+
+struct InFlightGuard {
+    requests: Arc<DashMap<u64, InFlightSender>>,
+    key: u64,
+    sender: InFlightSender,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.requests.remove_if(&self.key, |_, in_flight| {
+            Arc::ptr_eq(in_flight, &self.sender)
+        });
+    }
+}
+
+#[derive(Debug)]
+pub struct InFlightRequests {
+    requests: Arc<DashMap<u64, InFlightSender>>,
+    window: Duration,
+}
+
+impl InFlightRequests {
+    pub fn from_env() -> Self {
+        let window_secs = std::env::var("REQUEST_DEDUP_WINDOW_SECS")
+            .ok()
+            .and_then(|window_secs| window_secs.parse().ok())
+            .unwrap_or(DEFAULT_REQUEST_DEDUP_WINDOW_SECS);
+        Self {
+            requests: Arc::new(DashMap::new()),
+            window: Duration::from_secs(window_secs),
+        }
+    }
+
+    pub fn request_key(search_input: &AgentSearchInput) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        format!("{:?}", search_input).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub async fn deduplicate(
+        &self,
+        key: u64,
+        search: impl Future<Output = Result<AgentSearchResult, AgentSearchError>>,
+    ) -> Result<AgentSearchResult, AgentSearchError> {
+        if self.window.is_zero() {
+            return search.await;
+        }
+        let role = match self.requests.entry(key) {
+            Entry::Occupied(entry) => InFlightRole::Follower(entry.get().subscribe()),
+            Entry::Vacant(entry) => {
+                let (sender, _) = broadcast::channel(1);
+                let sender = Arc::new(sender);
+                entry.insert(sender.clone());
+                InFlightRole::Leader(sender)
+            }
+        };
+        let sender = match role {
+            InFlightRole::Leader(sender) => sender,
+            InFlightRole::Follower(mut receiver) => {
+                return match receiver.recv().await {
+                    Ok(Ok(mut result)) => {
+                        result.served_from_cache = true;
+                        Ok(result)
+                    }
+                    Ok(Err(message)) => {
+                        Err(AgentSearchError::InFlightSearchError(message.to_string()))
+                    }
+                    Err(_) => search.await,
+                };
+            }
+        };
+        let requests = self.requests.clone();
+        let expiring_sender = Arc::downgrade(&sender);
+        let window = self.window;
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            requests.remove_if(&key, |_, sender| {
+                std::ptr::eq(Arc::as_ptr(sender), expiring_sender.as_ptr())
+            });
+        });
+        let guard = InFlightGuard {
+            requests: self.requests.clone(),
+            key,
+            sender: sender.clone(),
+        };
+        let result = search.await;
+        drop(guard);
+        let _ = sender.send(match &result {
+            Ok(result) => Ok(result.clone()),
+            Err(e) => Err(Arc::from(e.to_string())),
+        });
+        result
+    }
+}
+
 pub struct ServerState {
-    pub searx_host: String,
-    pub searx_port: String,
+    pub searx: SearxConfig,
+    pub user_agents: Vec<String>,
+    pub domain_rate_limiter: Arc<DomainRateLimiter>,
+    pub http_client: SharedHttpClient,
+    pub page_index: Arc<RwLock<LocalPageIndex>>,
+    pub page_cache: Arc<PageCache>,
+    pub completion_cache: Arc<Mutex<CompletionCache>>,
+    pub llm_clients: Arc<LLMClientPool>,
+    pub circuit_breakers: Arc<CircuitBreakers>,
+    pub in_flight_requests: Arc<InFlightRequests>,
+    pub domain_lists: Arc<GlobalDomainLists>,
 }
 
-pub fn create_server() -> rocket::Rocket<rocket::Build> {
-    let searx_host = std::env::var("SEARX_HOST").unwrap_or_else(|_| "localhost".to_string());
-    let searx_port = std::env::var("SEARX_PORT").unwrap_or_else(|_| "8096".to_string());
+pub fn create_server_state(http_client: SharedHttpClient) -> ServerState {
+    let llm_clients = Arc::new(LLMClientPool::new());
+    set_default_llm_client_pool(llm_clients.clone());
+    let completion_cache = Arc::new(Mutex::new(CompletionCache::from_env()));
+    set_default_completion_cache(completion_cache.clone());
+    let circuit_breakers = Arc::new(CircuitBreakers::new());
+    set_default_circuit_breakers(circuit_breakers.clone());
+    let searx = SearxConfig::from_env();
+    if searx.pool.instances.len() > 1 {
+        spawn_searx_health_checks(searx.pool.clone(), searx.client.clone());
+    }
+    let domain_lists = Arc::new(GlobalDomainLists::from_env());
+    spawn_domain_list_reload_on_sighup(domain_lists.clone());
+    ServerState {
+        searx,
+        user_agents: load_user_agents(),
+        domain_rate_limiter: Arc::new(DomainRateLimiter::from_env()),
+        http_client,
+        page_index: Arc::new(RwLock::new(LocalPageIndex::new())),
+        page_cache: Arc::new(PageCache::from_env()),
+        completion_cache,
+        llm_clients,
+        circuit_breakers,
+        in_flight_requests: Arc::new(InFlightRequests::from_env()),
+        domain_lists,
+    }
+}
 
-    rocket::build()
-        .manage(ServerState {
-            searx_host: searx_host,
-            searx_port: searx_port,
-        })
+pub fn create_server(state: Arc<ServerState>) -> rocket::Rocket<rocket::Build> {
+    let rocket = rocket::build()
+        .manage(state)
+        .attach(ResponseEnvelopeFairing)
+        .attach(ResponseCompressionFairing::from_env())
         .mount(
             "/v1",
-            routes![handle_search, handle_agent_search, handle_scrape_site],
+            routes![
+                handle_search,
+                handle_batch_search,
+                handle_agent_search,
+                handle_estimate_cost,
+                handle_agent_search_ws,
+                handle_scrape_site,
+                handle_search_diff,
+                handle_site_search
+            ],
         )
+        .mount(
+            "/",
+            routes![handle_openapi_spec, handle_swagger_ui, handle_metrics],
+        );
+    check_openapi_spec_routes(&rocket);
+    rocket
 }
 
 pub async fn run_server(rocket: rocket::Rocket<rocket::Build>) -> Result<(), ServerError> {
     match rocket.launch().await {
         Ok(_) => Ok(()),
         Err(e) => {
-            eprintln!("Failed to launch rocket server: {}", e);
+            tracing::error!("Failed to launch rocket server: {}", e);
             Err(ServerError::Launch(e))
         }
     }
@@ -0,0 +1,104 @@
+use crate::request_context::RequestContext;
+use async_compression::tokio::bufread::GzipEncoder;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::{Request, Response};
+use std::io::Cursor;
+use tokio::io::AsyncReadExt;
+
+const DEFAULT_COMPRESS_THRESHOLD_BYTES: usize = 1024;
+const UNCOMPRESSED_PATHS: [&str; 1] = ["/metrics"];
+
+pub struct ResponseCompressionFairing {
+    threshold_bytes: usize,
+}
+
+impl ResponseCompressionFairing {
+    pub fn new(threshold_bytes: usize) -> Self {
+        Self { threshold_bytes }
+    }
+
+    pub fn from_env() -> Self {
+        let threshold_bytes = std::env::var("COMPRESS_THRESHOLD_BYTES")
+            .ok()
+            .and_then(|bytes| bytes.parse().ok())
+            .unwrap_or(DEFAULT_COMPRESS_THRESHOLD_BYTES);
+        Self::new(threshold_bytes)
+    }
+}
+
+fn accepts_gzip(request: &Request<'_>) -> bool {
+    request
+        .headers()
+        .get("Accept-Encoding")
+        .flat_map(|accept_encoding| accept_encoding.split(','))
+        .any(|coding| {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let rejected = parts.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            !rejected && (name.eq_ignore_ascii_case("gzip") || name == "*")
+        })
+}
+
+async fn gzip(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzipEncoder::new(body);
+    let mut compressed = Vec::new();
+    encoder.read_to_end(&mut compressed).await?;
+    Ok(compressed)
+}
+
+#[rocket::async_trait]
+impl Fairing for ResponseCompressionFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if response.status() == Status::SwitchingProtocols
+            || response.headers().contains("Content-Encoding")
+            || UNCOMPRESSED_PATHS.contains(&request.uri().path().as_str())
+            || !accepts_gzip(request)
+        {
+            return;
+        }
+        let context = request.local_cache(|| RequestContext::from_request(request));
+        let body = match response.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!(
+                    request_id = %context.request_id,
+                    "Failed to read response body for compression: {}",
+                    e
+                );
+                return;
+            }
+        };
+        if body.len() < self.threshold_bytes {
+            response.set_sized_body(body.len(), Cursor::new(body));
+            return;
+        }
+        let compressed = match gzip(&body).await {
+            Ok(compressed) => compressed,
+            Err(e) => {
+                tracing::error!(
+                    request_id = %context.request_id,
+                    "Failed to compress response body: {}",
+                    e
+                );
+                response.set_sized_body(body.len(), Cursor::new(body));
+                return;
+            }
+        };
+        response.set_header(Header::new("Content-Encoding", "gzip"));
+        response.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+        response.set_sized_body(compressed.len(), Cursor::new(compressed));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::request_context::{RequestContext, REQUEST_ID_HEADER};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::{Data, Request, Response};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::io::Cursor;
+
+pub const API_VERSION: &str = "1.0";
+pub const ENVELOPE_MEDIA_TYPE: &str = "application/vnd.webbrowser+json";
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct EnvelopeMeta {
+    pub queries_executed: Vec<String>,
+    pub pages_visited: usize,
+    pub llm_calls: u64,
+}
+
+impl EnvelopeMeta {
+    fn from_data(data: &Value) -> Self {
+        let queries_executed = data
+            .get("queries_executed")
+            .and_then(Value::as_array)
+            .map(|queries| {
+                queries
+                    .iter()
+                    .filter_map(|record| record.get("query").and_then(Value::as_str))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let pages_visited = data
+            .pointer("/raw_analysis/visited_results")
+            .and_then(Value::as_array)
+            .map(Vec::len)
+            .unwrap_or(0);
+        let llm_calls = data
+            .pointer("/usage/calls_by_stage")
+            .and_then(Value::as_object)
+            .map(|stages| {
+                stages
+                    .values()
+                    .filter_map(|stage| stage.get("calls").and_then(Value::as_u64))
+                    .sum()
+            })
+            .unwrap_or(0);
+        Self {
+            queries_executed,
+            pages_visited,
+            llm_calls,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ResponseEnvelope {
+    pub api_version: &'static str,
+    pub request_id: String,
+    pub elapsed_ms: u128,
+    pub data: Box<RawValue>,
+    pub meta: EnvelopeMeta,
+}
+
+pub struct ResponseEnvelopeFairing;
+
+fn wants_envelope(request: &Request<'_>) -> bool {
+    request
+        .headers()
+        .get("Accept")
+        .any(|accept| accept.contains(ENVELOPE_MEDIA_TYPE))
+}
+
+#[rocket::async_trait]
+impl Fairing for ResponseEnvelopeFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response envelope",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let request: &Request<'_> = request;
+        request.local_cache(|| RequestContext::from_request(request));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let context = request.local_cache(|| RequestContext::from_request(request));
+        response.set_raw_header(REQUEST_ID_HEADER, context.request_id.clone());
+        if !wants_envelope(request) || response.content_type() != Some(ContentType::JSON) {
+            return;
+        }
+        let body = match response.body_mut().to_string().await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!(
+                    request_id = %context.request_id,
+                    "Failed to read response body for envelope: {}",
+                    e
+                );
+                return;
+            }
+        };
+        let (data, meta) = match (
+            RawValue::from_string(body.clone()),
+            serde_json::from_str::<Value>(&body),
+        ) {
+            (Ok(data), Ok(value)) => (data, EnvelopeMeta::from_data(&value)),
+            _ => {
+                response.set_sized_body(body.len(), Cursor::new(body));
+                return;
+            }
+        };
+        let envelope = ResponseEnvelope {
+            api_version: API_VERSION,
+            request_id: context.request_id.clone(),
+            elapsed_ms: context.received_at.elapsed().as_millis(),
+            data,
+            meta,
+        };
+        let body = match serde_json::to_string(&envelope) {
+            Ok(envelope_body) => envelope_body,
+            Err(e) => {
+                tracing::error!(
+                    request_id = %context.request_id,
+                    "Failed to serialize response envelope: {}",
+                    e
+                );
+                response.set_sized_body(body.len(), Cursor::new(body));
+                return;
+            }
+        };
+        response.set_header(ContentType::new("application", "vnd.webbrowser+json"));
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::search::SearchResult;
+use crate::utils::normalize_url;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+
+tokio::task_local! {
+    static SEARCH_SESSION: SearchSession;
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchProgress {
+    pub url_visited: String,
+    pub analysis_so_far: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchSession {
+    progress: Option<UnboundedSender<SearchProgress>>,
+    cancelled: Arc<AtomicBool>,
+    skipped_urls: Arc<Mutex<HashSet<String>>>,
+    seeded_results: Option<Arc<Vec<SearchResult>>>,
+}
+
+impl SearchSession {
+    pub fn new(progress: UnboundedSender<SearchProgress>) -> Self {
+        Self {
+            progress: Some(progress),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_seeded_results(mut self, seeded_results: Vec<SearchResult>) -> Self {
+        self.seeded_results = Some(Arc::new(seeded_results));
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn skip_url(&self, url: &str) {
+        self.skipped_urls.lock().unwrap().insert(normalize_url(url));
+    }
+
+    pub fn is_url_skipped(&self, url: &str) -> bool {
+        self.skipped_urls
+            .lock()
+            .unwrap()
+            .contains(&normalize_url(url))
+    }
+
+    fn report_progress(&self, url_visited: &str, analysis_so_far: &str) {
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(SearchProgress {
+                url_visited: url_visited.to_string(),
+                analysis_so_far: analysis_so_far.to_string(),
+            });
+        }
+    }
+}
+
+pub async fn with_search_session<F: Future>(session: SearchSession, future: F) -> F::Output {
+    SEARCH_SESSION.scope(session, future).await
+}
+
+pub fn current_search_session() -> Option<SearchSession> {
+    SEARCH_SESSION.try_with(|session| session.clone()).ok()
+}
+
+pub fn report_progress(url_visited: &str, analysis_so_far: &str) {
+    let _ =
+        SEARCH_SESSION.try_with(|session| session.report_progress(url_visited, analysis_so_far));
+}
+
+pub fn seeded_search_results() -> Option<Vec<SearchResult>> {
+    SEARCH_SESSION
+        .try_with(|session| {
+            session
+                .seeded_results
+                .as_ref()
+                .map(|results| results.to_vec())
+        })
+        .ok()
+        .flatten()
+}
+
+pub fn search_cancelled() -> bool {
+    SEARCH_SESSION
+        .try_with(|session| session.is_cancelled())
+        .unwrap_or(false)
+}
+
+pub fn is_url_skipped(url: &str) -> bool {
+    SEARCH_SESSION
+        .try_with(|session| session.is_url_skipped(url))
+        .unwrap_or(false)
+}
@@ -0,0 +1,271 @@
+use crate::llm::CompletionWithUsage;
+use crate::request_context::{current_request_context, with_request_context};
+use crate::session::{current_search_session, with_search_session};
+use rocket::FromForm;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+tokio::task_local! {
+    static USAGE_TRACKER: UsageTracker;
+    static STAGE_TEMPERATURES: StageTemperatures;
+    static QUERY_LLM_CALLS: LLMCallCounter;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LLMCallStage {
+    QuerySynthesis,
+    QueryTranslation,
+    QueryCorrection,
+    ResultSelection,
+    Reranking,
+    DependencyTree,
+    Extraction,
+    SufficiencyCheck,
+    Aggregation,
+    Deduplication,
+    Formatting,
+    FollowUpQueries,
+    JsonRetry,
+}
+
+impl LLMCallStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LLMCallStage::QuerySynthesis => "query_synthesis",
+            LLMCallStage::QueryTranslation => "query_translation",
+            LLMCallStage::QueryCorrection => "query_correction",
+            LLMCallStage::ResultSelection => "result_selection",
+            LLMCallStage::Reranking => "reranking",
+            LLMCallStage::DependencyTree => "dependency_tree",
+            LLMCallStage::Extraction => "extraction",
+            LLMCallStage::SufficiencyCheck => "sufficiency_check",
+            LLMCallStage::Aggregation => "aggregation",
+            LLMCallStage::Deduplication => "deduplication",
+            LLMCallStage::Formatting => "formatting",
+            LLMCallStage::FollowUpQueries => "follow_up_queries",
+            LLMCallStage::JsonRetry => "json_retry",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, FromForm)]
+pub struct StageTemperatures {
+    #[serde(default)]
+    pub query_synthesis: Option<f64>,
+    #[serde(default)]
+    pub page_selection: Option<f64>,
+    #[serde(default)]
+    pub page_extraction: Option<f64>,
+    #[serde(default)]
+    pub aggregation: Option<f64>,
+    #[serde(default)]
+    pub result_formatting: Option<f64>,
+}
+
+impl StageTemperatures {
+    pub fn for_stage(&self, stage: LLMCallStage) -> Option<f64> {
+        match stage {
+            LLMCallStage::QuerySynthesis
+            | LLMCallStage::QueryTranslation
+            | LLMCallStage::QueryCorrection
+            | LLMCallStage::FollowUpQueries => self.query_synthesis,
+            LLMCallStage::ResultSelection
+            | LLMCallStage::Reranking
+            | LLMCallStage::DependencyTree => self.page_selection,
+            LLMCallStage::Extraction => self.page_extraction,
+            LLMCallStage::Aggregation | LLMCallStage::Deduplication => self.aggregation,
+            LLMCallStage::Formatting => self.result_formatting,
+            LLMCallStage::SufficiencyCheck | LLMCallStage::JsonRetry => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UsageStage {
+    pub calls: u32,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UsageSummary {
+    pub total_prompt_tokens: u32,
+    pub total_completion_tokens: u32,
+    pub calls_by_stage: HashMap<String, UsageStage>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LLMCallCounter {
+    calls: Arc<AtomicU32>,
+    max_calls: Option<u32>,
+    budget_reached: Arc<AtomicBool>,
+}
+
+impl LLMCallCounter {
+    pub fn new(max_calls: Option<u32>) -> Self {
+        Self {
+            max_calls,
+            ..Self::default()
+        }
+    }
+
+    pub fn increment(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn calls(&self) -> u32 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        match self.max_calls {
+            Some(max_calls) => self.calls() >= max_calls,
+            None => false,
+        }
+    }
+
+    pub fn stop_if_exhausted(&self) -> bool {
+        if !self.is_exhausted() {
+            return false;
+        }
+        self.budget_reached.store(true, Ordering::Relaxed);
+        true
+    }
+
+    pub fn budget_reached(&self) -> bool {
+        self.budget_reached.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    summary: Arc<Mutex<UsageSummary>>,
+    rate_limit_delays_ms: Arc<AtomicU64>,
+    llm_calls: LLMCallCounter,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_llm_calls(max_llm_calls: Option<u32>) -> Self {
+        Self {
+            llm_calls: LLMCallCounter::new(max_llm_calls),
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&self, stage: LLMCallStage, completion: &CompletionWithUsage) {
+        self.llm_calls.increment();
+        let mut summary = self.summary.lock().unwrap();
+        summary.total_prompt_tokens += completion.prompt_tokens;
+        summary.total_completion_tokens += completion.completion_tokens;
+        let stage_usage = summary
+            .calls_by_stage
+            .entry(stage.as_str().to_string())
+            .or_default();
+        stage_usage.calls += 1;
+        stage_usage.prompt_tokens += completion.prompt_tokens;
+        stage_usage.completion_tokens += completion.completion_tokens;
+    }
+
+    pub fn summary(&self) -> UsageSummary {
+        self.summary.lock().unwrap().clone()
+    }
+
+    pub fn record_rate_limit_delay(&self, delay: Duration) {
+        self.rate_limit_delays_ms
+            .fetch_add(delay.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn rate_limit_delays_ms(&self) -> u64 {
+        self.rate_limit_delays_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn truncated_due_to_llm_budget(&self) -> bool {
+        self.llm_calls.budget_reached()
+    }
+}
+
+pub async fn track_usage<F: Future>(tracker: UsageTracker, future: F) -> F::Output {
+    USAGE_TRACKER.scope(tracker, future).await
+}
+
+pub async fn with_stage_temperatures<F: Future>(
+    temperatures: StageTemperatures,
+    future: F,
+) -> F::Output {
+    STAGE_TEMPERATURES.scope(temperatures, future).await
+}
+
+pub async fn count_query_llm_calls<F: Future>(counter: LLMCallCounter, future: F) -> F::Output {
+    QUERY_LLM_CALLS.scope(counter, future).await
+}
+
+/// Carries the usage tracker, stage temperatures, per-query LLM call counter, search session, and request context into a spawned task.
+pub fn with_current_search_context<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let tracker = USAGE_TRACKER.try_with(|tracker| tracker.clone()).ok();
+    let temperatures = STAGE_TEMPERATURES
+        .try_with(|temperatures| temperatures.clone())
+        .ok();
+    let query_llm_calls = QUERY_LLM_CALLS.try_with(|counter| counter.clone()).ok();
+    let session = current_search_session();
+    let request_context = current_request_context();
+    async move {
+        let future = async move {
+            match tracker {
+                Some(tracker) => track_usage(tracker, future).await,
+                None => future.await,
+            }
+        };
+        let future = async move {
+            match temperatures {
+                Some(temperatures) => with_stage_temperatures(temperatures, future).await,
+                None => future.await,
+            }
+        };
+        let future = async move {
+            match query_llm_calls {
+                Some(counter) => count_query_llm_calls(counter, future).await,
+                None => future.await,
+            }
+        };
+        let future = async move {
+            match session {
+                Some(session) => with_search_session(session, future).await,
+                None => future.await,
+            }
+        };
+        match request_context {
+            Some(context) => with_request_context(context, future).await,
+            None => future.await,
+        }
+    }
+}
+
+pub fn stage_temperature(stage: LLMCallStage) -> Option<f64> {
+    STAGE_TEMPERATURES
+        .try_with(|temperatures| temperatures.for_stage(stage))
+        .ok()
+        .flatten()
+}
+
+pub fn record_usage(stage: LLMCallStage, completion: &CompletionWithUsage) {
+    let _ = USAGE_TRACKER.try_with(|tracker| tracker.record(stage, completion));
+    let _ = QUERY_LLM_CALLS.try_with(|counter| counter.increment());
+}
+
+pub fn stop_for_llm_budget() -> bool {
+    USAGE_TRACKER
+        .try_with(|tracker| tracker.llm_calls.stop_if_exhausted())
+        .unwrap_or(false)
+}
+
+pub fn record_rate_limit_delay(delay: Duration) {
+    let _ = USAGE_TRACKER.try_with(|tracker| tracker.record_rate_limit_delay(delay));
+}
@@ -1,8 +1,17 @@
+use crate::llm::{default_completion_with_messages, CompletionResult, Message, Role};
+use crate::prompts::{Prompt, INVALID_JSON_CORRECTION_PROMPT};
+use crate::request_context::current_request_id;
 use crate::search::SearchResult;
+use crate::usage::LLMCallStage;
+use glob::Pattern;
 use regex::Regex;
 use serde::de::DeserializeOwned;
 use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::OnceLock;
+use std::time::Duration;
 use thiserror::Error;
+use url::{Host, Url};
 
 #[derive(Error, Debug)]
 pub struct ParseMarkdownCodeBlockError {
@@ -36,29 +45,145 @@ impl Display for ParseJsonError {
     }
 }
 
+const MARKDOWN_CODE_FENCE: &str = "```";
+
+struct MarkdownCodeBlock {
+    language: String,
+    content: String,
+    terminated: bool,
+}
+
+fn code_fence_language(info: &str) -> Option<&str> {
+    let language = info.trim_end();
+    language
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '+'))
+        .then_some(language)
+}
+
+fn parse_markdown_code_blocks(content: &str) -> Vec<MarkdownCodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<MarkdownCodeBlock> = None;
+    let mut depth = 0;
+    for line in content.lines() {
+        let block = match current.as_mut() {
+            Some(block) => block,
+            None => {
+                let language = line.find(MARKDOWN_CODE_FENCE).and_then(|fence_start| {
+                    code_fence_language(&line[fence_start + MARKDOWN_CODE_FENCE.len()..])
+                });
+                if let Some(language) = language {
+                    current = Some(MarkdownCodeBlock {
+                        language: language.to_string(),
+                        content: String::new(),
+                        terminated: false,
+                    });
+                    depth = 1;
+                }
+                continue;
+            }
+        };
+        let fence_language = line
+            .trim_start()
+            .strip_prefix(MARKDOWN_CODE_FENCE)
+            .and_then(code_fence_language);
+        if let Some(fence_language) = fence_language {
+            if fence_language.is_empty() {
+                depth -= 1;
+            } else {
+                depth += 1;
+            }
+            if depth == 0 {
+                if let Some(mut block) = current.take() {
+                    block.content = block.content.trim().to_string();
+                    block.terminated = true;
+                    blocks.push(block);
+                }
+                continue;
+            }
+        }
+        block.content.push_str(line);
+        block.content.push('\n');
+    }
+    if let Some(mut block) = current {
+        block.content = block.content.trim().to_string();
+        blocks.push(block);
+    }
+    blocks
+}
+
+fn balanced_json_end(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + c.len_utf8());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn extract_last_json_substring(content: &str) -> Option<String> {
+    let mut last_json = None;
+    let mut offset = 0;
+    while let Some(start) = content[offset..].find(['{', '[']) {
+        let start = offset + start;
+        match balanced_json_end(&content[start..]) {
+            Some(end) => {
+                last_json = Some(content[start..start + end].to_string());
+                offset = start + end;
+            }
+            None => offset = start + 1,
+        }
+    }
+    last_json
+}
+
 pub fn parse_markdown_code_block(
     content: &str,
     language: Option<&str>,
 ) -> Result<String, ParseMarkdownCodeBlockError> {
-    let re = Regex::new(r"```(\w*)\n([\s\S]*?)\n```").unwrap();
-    let mut valid_results = Vec::new();
-    for cap in re.captures_iter(content) {
-        let block_language = cap.get(1).map_or("", |m| m.as_str());
-        let parsed_content = cap.get(2).map_or("", |m| m.as_str()).trim();
-        if language.is_none() {
-            return Ok(parsed_content.to_string());
+    let blocks = parse_markdown_code_blocks(content);
+    let parsed_content = match language {
+        None => blocks.into_iter().next().map(|block| block.content),
+        Some(language) => {
+            let (terminated, unterminated): (Vec<_>, Vec<_>) = blocks
+                .into_iter()
+                .filter(|block| block.language == language)
+                .partition(|block| block.terminated);
+            terminated
+                .into_iter()
+                .last()
+                .or(unterminated.into_iter().last())
+                .map(|block| block.content)
+                .or_else(|| extract_last_json_substring(content))
         }
-        if block_language == language.unwrap() {
-            valid_results.push(parsed_content.to_string());
-        }
-    }
-    if valid_results.is_empty() {
-        return Err(ParseMarkdownCodeBlockError {
+    };
+    match parsed_content {
+        Some(parsed_content) => Ok(parsed_content),
+        None => Err(ParseMarkdownCodeBlockError {
             message: "No matching markdown code blocks found in response".to_string(),
             original_response: content.to_string(),
-        });
+        }),
     }
-    Ok(valid_results.last().unwrap().to_string())
 }
 
 pub fn display_content_preview(content: &str) -> String {
@@ -70,24 +195,334 @@ pub fn display_content_preview(content: &str) -> String {
     format!("{}...", preview)
 }
 
+pub fn duration_from_env_ms(name: &str, default: Duration) -> Duration {
+    std::env::var(name)
+        .ok()
+        .and_then(|millis| millis.parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(default)
+}
+
+pub fn new_uuid_v4() -> String {
+    let id = rand::random::<u128>();
+    let id = (id & !(0xf << 76)) | (0x4 << 76);
+    let id = (id & !(0x3 << 62)) | (0x2 << 62);
+    let hex = format!("{:032x}", id);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 3 <= bytes.len() {
+            let high = (bytes[i + 1] as char).to_digit(16);
+            let low = (bytes[i + 2] as char).to_digit(16);
+            if let (Some(high), Some(low)) = (high, low) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+const MAX_HTML_ENTITY_LEN: usize = 10;
+
+fn decode_html_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code_point = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse::<u32>().ok()?,
+        };
+        return char::from_u32(code_point).filter(|c| *c != '\0');
+    }
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        "ndash" => Some('–'),
+        "mdash" => Some('—'),
+        "hellip" => Some('…'),
+        "lsquo" => Some('‘'),
+        "rsquo" => Some('’'),
+        "ldquo" => Some('“'),
+        "rdquo" => Some('”'),
+        "copy" => Some('©'),
+        "reg" => Some('®'),
+        "trade" => Some('™'),
+        _ => None,
+    }
+}
+
+pub fn decode_html_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= MAX_HTML_ENTITY_LEN)
+            .and_then(|end| Some((end, decode_html_entity(&rest[1..end + 1])?)));
+        match entity {
+            Some((end, c)) => {
+                decoded.push(c);
+                rest = &rest[end + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+static TRUSTED_DOMAINS: phf::Set<&'static str> = phf::phf_set! {
+    "wikipedia.org",
+    "britannica.com",
+    "reuters.com",
+    "apnews.com",
+    "bbc.co.uk",
+    "bbc.com",
+    "nytimes.com",
+    "theguardian.com",
+    "economist.com",
+    "nature.com",
+    "science.org",
+    "sciencedirect.com",
+    "springer.com",
+    "wiley.com",
+    "cell.com",
+    "thelancet.com",
+    "nejm.org",
+    "bmj.com",
+    "arxiv.org",
+    "pubmed.ncbi.nlm.nih.gov",
+    "ncbi.nlm.nih.gov",
+    "nih.gov",
+    "who.int",
+    "acm.org",
+    "ieee.org",
+    "docs.python.org",
+    "doc.rust-lang.org",
+    "developer.mozilla.org",
+    "github.com",
+    "stackoverflow.com",
+};
+
+static SCIENCE_DOMAINS: phf::Set<&'static str> = phf::phf_set! {
+    "nature.com",
+    "science.org",
+    "sciencedirect.com",
+    "springer.com",
+    "wiley.com",
+    "cell.com",
+    "thelancet.com",
+    "nejm.org",
+    "bmj.com",
+    "arxiv.org",
+    "pubmed.ncbi.nlm.nih.gov",
+    "ncbi.nlm.nih.gov",
+    "nih.gov",
+    "who.int",
+    "acm.org",
+    "ieee.org",
+};
+
+static NEWS_DOMAINS: phf::Set<&'static str> = phf::phf_set! {
+    "reuters.com",
+    "apnews.com",
+    "bbc.co.uk",
+    "bbc.com",
+    "nytimes.com",
+    "theguardian.com",
+    "economist.com",
+};
+
+static IT_DOMAINS: phf::Set<&'static str> = phf::phf_set! {
+    "docs.python.org",
+    "doc.rust-lang.org",
+    "developer.mozilla.org",
+    "github.com",
+    "stackoverflow.com",
+    "arxiv.org",
+    "acm.org",
+    "ieee.org",
+};
+
+fn category_domains(category: &str) -> Option<&'static phf::Set<&'static str>> {
+    match category.to_lowercase().as_str() {
+        "science" => Some(&SCIENCE_DOMAINS),
+        "news" => Some(&NEWS_DOMAINS),
+        "it" => Some(&IT_DOMAINS),
+        _ => None,
+    }
+}
+
+fn tld_credibility_score(labels: &[&str]) -> f32 {
+    let institutional = labels
+        .iter()
+        .rev()
+        .take(2)
+        .any(|label| matches!(*label, "gov" | "edu" | "mil" | "ac"));
+    if institutional {
+        return 0.9;
+    }
+    match labels.last().copied() {
+        Some("org") | Some("int") => 0.7,
+        Some("com") | Some("net") => 0.5,
+        Some("xyz") | Some("top") | Some("click") | Some("info") => 0.3,
+        _ => 0.5,
+    }
+}
+
+fn matching_domain(labels: &[&str], domains: &phf::Set<&'static str>) -> Option<usize> {
+    (0..labels.len().saturating_sub(1)).find(|&i| domains.contains(labels[i..].join(".").as_str()))
+}
+
+pub fn source_credibility_score(url: &str, category: Option<&str>) -> f32 {
+    let host = match Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_lowercase))
+    {
+        Some(host) => host,
+        None => return 0.0,
+    };
+    let labels = host
+        .trim_start_matches("www.")
+        .split('.')
+        .filter(|label| !label.is_empty())
+        .collect::<Vec<_>>();
+    let mut score = tld_credibility_score(&labels);
+    let mut registered_domain_start = labels.len().saturating_sub(2);
+    if let Some(i) = matching_domain(&labels, &TRUSTED_DOMAINS) {
+        score = score.max(0.9);
+        registered_domain_start = i;
+        let in_category = category
+            .and_then(category_domains)
+            .map(|domains| matching_domain(&labels, domains).is_some())
+            .unwrap_or(false);
+        if in_category {
+            score = 1.0;
+        }
+    }
+    let subdomain_depth = registered_domain_start.saturating_sub(1);
+    score -= (subdomain_depth as f32 * 0.05).min(0.15);
+    score.clamp(0.0, 1.0)
+}
+
+fn url_path_terms(url: &str) -> Vec<String> {
+    let parsed_url = match Url::parse(url) {
+        Ok(parsed_url) => parsed_url,
+        Err(_) => return Vec::new(),
+    };
+    percent_decode(parsed_url.path())
+        .split(['/', '-'])
+        .map(|term| term.trim().to_string())
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+pub fn build_result_preview(result: &SearchResult) -> String {
+    let mut preview = format!("Title: {} ({})", result.title, result.url);
+    let path_terms = url_path_terms(&result.url);
+    if !path_terms.is_empty() {
+        preview.push_str(&format!("\nURL path: {}", path_terms.join(" ")));
+    }
+    if let Some(anchor_text) = result
+        .anchor_text
+        .as_deref()
+        .filter(|anchor_text| !anchor_text.trim().is_empty())
+    {
+        preview.push_str(&format!("\nAnchor text: {}", anchor_text.trim()));
+    }
+    preview.push_str(&format!(
+        "\nContent preview: {}",
+        display_content_preview(&result.content)
+    ));
+    preview
+}
+
 pub fn display_search_results_with_indices(results: &[SearchResult]) -> String {
     results
         .iter()
         .enumerate()
         .map(|(i, r)| {
+            let mut dates = String::new();
+            if let Some(published_at) = &r.published_at {
+                dates.push_str(&format!("\nPublished: {}", published_at));
+            }
+            if let Some(modified_at) = &r.modified_at {
+                dates.push_str(&format!("\nLast modified: {}", modified_at));
+            }
+            let direct_answer = if r.infobox.is_some() {
+                "[DIRECT ANSWER] "
+            } else {
+                ""
+            };
             format!(
-                "[{}] Title: {} ({})\nContent preview: {}",
+                "[{}] {}{}{}\nSource credibility: {:.2}",
                 i,
-                r.title,
-                r.url,
-                display_content_preview(&r.content)
+                direct_answer,
+                build_result_preview(r),
+                dates,
+                source_credibility_score(&r.url, None)
             )
         })
         .collect::<Vec<_>>()
         .join("\n\n")
 }
 
-pub fn parse_json_response<T: DeserializeOwned>(completion: &str) -> Result<T, ParseJsonError> {
+pub fn parse_json_response<T: DeserializeOwned>(
+    completion: &CompletionResult,
+) -> Result<T, ParseJsonError> {
+    let completion = match completion {
+        CompletionResult::ToolCall { arguments, .. } => {
+            match serde_json::from_value(arguments.clone()) {
+                Ok(parsed) => return Ok(parsed),
+                Err(e) => match arguments.as_str() {
+                    Some(arguments) => arguments,
+                    None => {
+                        return Err(ParseJsonError {
+                            message: e.to_string(),
+                            original_response: arguments.to_string(),
+                        })
+                    }
+                },
+            }
+        }
+        CompletionResult::Text(text) => text.as_str(),
+    };
+    parse_json_code_block(completion)
+}
+
+pub fn parse_json_response_with_schema<T: DeserializeOwned>(
+    completion: &CompletionResult,
+) -> Result<T, ParseJsonError> {
+    match completion {
+        CompletionResult::ToolCall { .. } => parse_json_response(completion),
+        CompletionResult::Text(text) => parse_json_response_or_raw(text),
+    }
+}
+
+fn parse_json_code_block<T: DeserializeOwned>(completion: &str) -> Result<T, ParseJsonError> {
     let response = match parse_markdown_code_block(completion, Some("json")) {
         Ok(response) => response,
         Err(e) => {
@@ -106,6 +541,277 @@ pub fn parse_json_response<T: DeserializeOwned>(completion: &str) -> Result<T, P
     }
 }
 
+pub const DEFAULT_PARSE_JSON_MAX_ATTEMPTS: u32 = 3;
+
+fn parse_json_response_or_raw<T: DeserializeOwned>(completion: &str) -> Result<T, ParseJsonError> {
+    match parse_json_code_block(completion) {
+        Ok(parsed) => Ok(parsed),
+        Err(e) => match serde_json::from_str(completion.trim()) {
+            Ok(parsed) => Ok(parsed),
+            Err(_) => Err(e),
+        },
+    }
+}
+
+pub async fn parse_json_response_with_retry<T: DeserializeOwned>(
+    completion: &str,
+    prompt: &Prompt,
+    max_attempts: u32,
+) -> Result<T, ParseJsonError> {
+    let mut completion = completion.to_string();
+    let mut attempts = 1;
+    loop {
+        let error = match parse_json_response_or_raw(&completion) {
+            Ok(parsed) => return Ok(parsed),
+            Err(e) => e,
+        };
+        if attempts >= max_attempts {
+            return Err(error);
+        }
+        attempts += 1;
+        tracing::warn!(
+            request_id = %current_request_id(),
+            "Retrying malformed JSON response (attempt {}/{}): {}",
+            attempts,
+            max_attempts,
+            error.message
+        );
+        let mut messages = prompt.build_messages();
+        messages.push(Message {
+            role: Role::Assistant,
+            content: completion.clone(),
+        });
+        messages.push(Message {
+            role: Role::User,
+            content: INVALID_JSON_CORRECTION_PROMPT.to_string(),
+        });
+        completion = match default_completion_with_messages(messages, LLMCallStage::JsonRetry).await
+        {
+            Ok(completion) => completion,
+            Err(e) => {
+                return Err(ParseJsonError {
+                    message: format!("{}. Retry failed: {}", error.message, e),
+                    original_response: completion,
+                })
+            }
+        };
+    }
+}
+
+const TRACKING_QUERY_PARAMS: [&str; 4] = ["fbclid", "gclid", "ref", "source"];
+
+fn is_tracking_query_param(key: &str) -> bool {
+    key.starts_with("utm_") || TRACKING_QUERY_PARAMS.contains(&key)
+}
+
+fn decode_unreserved_percent_encodings(url: &str) -> String {
+    let bytes = url.as_bytes();
+    let mut decoded = String::with_capacity(url.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 3 <= bytes.len() {
+            let high = (bytes[i + 1] as char).to_digit(16);
+            let low = (bytes[i + 2] as char).to_digit(16);
+            if let (Some(high), Some(low)) = (high, low) {
+                let byte = (high * 16 + low) as u8;
+                if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                    decoded.push(byte as char);
+                    i += 3;
+                    continue;
+                }
+            }
+        }
+        decoded.push(bytes[i] as char);
+        i += 1;
+    }
+    decoded
+}
+
+pub fn normalize_url(url: &str) -> String {
+    let mut parsed_url = match Url::parse(url.trim()) {
+        Ok(parsed_url) => parsed_url,
+        Err(_) => return url.to_string(),
+    };
+    if let Some(host) = parsed_url.host_str() {
+        if let Some(stripped_host) = host.strip_prefix("www.") {
+            let stripped_host = stripped_host.to_string();
+            if parsed_url.set_host(Some(&stripped_host)).is_err() {
+                return url.to_string();
+            }
+        }
+    }
+    let path = parsed_url.path().trim_end_matches('/').to_string();
+    parsed_url.set_path(&path);
+    let mut query_pairs = parsed_url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_query_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    query_pairs.sort();
+    if query_pairs.is_empty() {
+        parsed_url.set_query(None);
+    } else {
+        parsed_url
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(query_pairs);
+    }
+    parsed_url.set_fragment(None);
+    decode_unreserved_percent_encodings(parsed_url.as_str())
+}
+
+const REJECTED_URL_SCHEMES: [&str; 3] = ["javascript:", "data:", "mailto:"];
+
+fn is_private_ipv4(ip: &Ipv4Addr) -> bool {
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.octets()[0] == 0
+        || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+}
+
+fn is_private_ipv6(ip: &Ipv6Addr) -> bool {
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_private_ipv4(&ipv4);
+    }
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || (ip.segments()[0] & 0xfe00) == 0xfc00
+        || (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+pub fn is_private_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => is_private_ipv6(ip),
+    }
+}
+
+pub fn is_private_host(host: &Host<&str>) -> bool {
+    match host {
+        Host::Domain(domain) => {
+            let domain = domain.trim_end_matches('.').to_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Host::Ipv4(ip) => is_private_ipv4(ip),
+        Host::Ipv6(ip) => is_private_ipv6(ip),
+    }
+}
+
+pub fn validate_and_normalize_search_result_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    let lowercase_url = url.to_lowercase();
+    if url.is_empty()
+        || REJECTED_URL_SCHEMES
+            .iter()
+            .any(|scheme| lowercase_url.starts_with(scheme))
+    {
+        return None;
+    }
+    let url = if lowercase_url.starts_with("http://") || lowercase_url.starts_with("https://") {
+        url.to_string()
+    } else if let Some(url) = url.strip_prefix("//") {
+        format!("https://{}", url)
+    } else if url.starts_with('/') || url.contains("://") {
+        return None;
+    } else {
+        format!("https://{}", url)
+    };
+    let parsed_url = match Url::parse(&url) {
+        Ok(parsed_url) => parsed_url,
+        Err(_) => return None,
+    };
+    match parsed_url.host() {
+        Some(host) if !is_private_host(&host) => Some(parsed_url.to_string()),
+        _ => None,
+    }
+}
+
+fn url_without_scheme(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => url,
+    }
+}
+
+pub fn filter_results_by_patterns(
+    results: Vec<SearchResult>,
+    patterns: &[String],
+) -> Vec<SearchResult> {
+    if patterns.is_empty() {
+        return results;
+    }
+    let patterns = patterns
+        .iter()
+        .filter_map(|pattern| match Pattern::new(pattern) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Ignoring invalid URL pattern {}: {}",
+                    pattern,
+                    e
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    results
+        .into_iter()
+        .filter(|result| {
+            result.infobox.is_some()
+                || patterns.iter().any(|pattern| {
+                    pattern.matches(&result.url) || pattern.matches(url_without_scheme(&result.url))
+                })
+        })
+        .collect()
+}
+
+static MARKDOWN_LINK_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+fn markdown_link_pattern() -> &'static Regex {
+    MARKDOWN_LINK_PATTERN.get_or_init(|| Regex::new(r"\[[^\]]*\]\((https?://[^\s)]+)\)").unwrap())
+}
+
+pub fn extract_urls_from_markdown(content: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for captures in markdown_link_pattern().captures_iter(content) {
+        let url = captures[1].to_string();
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+pub fn truncate_at_sentence(text: &str, max_chars: usize) -> &str {
+    let limit = match text.char_indices().nth(max_chars) {
+        Some((limit, _)) => limit,
+        None => return text,
+    };
+    let candidate = &text[..limit];
+    let mut chars = candidate.char_indices().peekable();
+    let mut sentence_end = None;
+    while let Some((i, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') {
+            if let Some((_, next)) = chars.peek() {
+                if next.is_whitespace() {
+                    sentence_end = Some(i + c.len_utf8());
+                }
+            }
+        }
+    }
+    if let Some(sentence_end) = sentence_end {
+        return &text[..sentence_end];
+    }
+    match candidate.rfind(char::is_whitespace) {
+        Some(word_end) if word_end > 0 => candidate[..word_end].trim_end(),
+        _ => candidate,
+    }
+}
+
 pub fn enforce_n_sequential_newlines(text: &str, n: usize) -> String {
     let mut result = String::with_capacity(text.len());
     let mut newline_count = 0;
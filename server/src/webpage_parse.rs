@@ -1,10 +1,31 @@
 use thiserror::Error;
 
-use crate::utils::enforce_n_sequential_newlines;
+use crate::request_context::current_request_id;
+use crate::utils::{
+    enforce_n_sequential_newlines, is_private_host, is_private_ip, truncate_at_sentence,
+};
+use crate::webpage_parse::streaming::read_body_streaming;
 
 use ammonia::Builder;
+use dashmap::DashMap;
+use rand::seq::SliceRandom;
 use reqwest;
+use scraper::{ElementRef, Html, Selector};
 use std::collections::HashSet;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
+use url::{Host, Url};
+
+pub mod academic;
+pub mod github;
+pub mod streaming;
+pub mod wikipedia;
+
+pub use academic::{extract_academic_metadata, AcademicPaper};
+pub use github::{extract_github_repo, fetch_github_repo_metadata, GitHubRepoMetadata};
+pub use wikipedia::{extract_wikipedia_infobox, format_wikipedia_infobox};
 
 #[derive(Error, Debug)]
 pub enum WebpageParseError {
@@ -14,12 +35,26 @@ pub enum WebpageParseError {
     DomParseError(#[from] DomParseError),
     #[error("Failed to clean webpage: {0}")]
     SemanticParseError(#[from] SemanticParseError),
+    #[error("Skipped webpage fetch, falling back to snippet: {0}")]
+    FetchSkipped(String),
+    #[error("Unsupported content type: {0}")]
+    UnsupportedContentType(String),
+    #[error("Blocked by a bot challenge: {0}")]
+    BotChallenge(String),
+    #[error("Skipped large or binary content at {0}: {1}")]
+    SkippedLargeOrBinaryContent(String, String),
+    #[error("Blocked request to {url}: resolves to private address {resolved_ip}")]
+    SSRFBlocked { url: String, resolved_ip: String },
+    #[error("Invalid user agent: {0}")]
+    InvalidUserAgent(String),
 }
 
 #[derive(Error, Debug)]
 pub enum DomParseError {
     #[error("Failed to parse webpage")]
     ParseError(String),
+    #[error("Unsupported content type: {0}")]
+    UnsupportedContentType(String),
 }
 
 #[derive(Error, Debug)]
@@ -32,30 +67,447 @@ pub enum SemanticParseError {
 pub struct ParsedWebpage {
     pub original_content: String,
     pub content: String,
+    pub is_snippet: bool,
+    pub published_at: Option<String>,
+    pub modified_at: Option<String>,
+    pub image_urls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Html,
+    PlainText,
+    Json,
+    Csv,
+    Pdf,
+}
+
+impl ContentType {
+    pub fn from_header(content_type: Option<&str>) -> Self {
+        let mime_type = content_type
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_lowercase())
+            .unwrap_or_default();
+        match mime_type.as_str() {
+            "text/html" | "application/xhtml+xml" => ContentType::Html,
+            "text/plain" => ContentType::PlainText,
+            "application/json" => ContentType::Json,
+            mime_type if mime_type.ends_with("+json") => ContentType::Json,
+            "text/csv" | "application/csv" => ContentType::Csv,
+            "application/pdf" => ContentType::Pdf,
+            _ => ContentType::Html,
+        }
+    }
 }
 
 const MAX_RETRIES: u32 = 3;
 
-pub async fn visit_and_parse_webpage(url: &str) -> Result<ParsedWebpage, WebpageParseError> {
+const DEFAULT_MAX_FETCH_BYTES: u64 = 10 * 1024 * 1024;
+
+const HEAD_PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn max_fetch_bytes() -> u64 {
+    std::env::var("MAX_FETCH_BYTES")
+        .ok()
+        .and_then(|bytes| bytes.parse().ok())
+        .unwrap_or(DEFAULT_MAX_FETCH_BYTES)
+}
+
+fn head_preflight_enabled() -> bool {
+    std::env::var("HEAD_PREFLIGHT_ENABLED")
+        .ok()
+        .and_then(|enabled| enabled.parse().ok())
+        .unwrap_or(true)
+}
+
+fn is_text_like_mime_type(mime_type: &str) -> bool {
+    mime_type.starts_with("text/")
+        || mime_type.ends_with("+xml")
+        || mime_type.ends_with("+json")
+        || matches!(
+            mime_type,
+            "application/pdf" | "application/json" | "application/xml" | "application/csv"
+        )
+}
+
+async fn resolve_host_addresses(url: &Url) -> Vec<IpAddr> {
+    match url.host() {
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(Host::Domain(domain)) => {
+            let port = url.port_or_known_default().unwrap_or(443);
+            match tokio::net::lookup_host((domain, port)).await {
+                Ok(addresses) => addresses.map(|address| address.ip()).collect(),
+                Err(_) => Vec::new(),
+            }
+        }
+        None => Vec::new(),
+    }
+}
+
+const MAX_REDIRECTS: usize = 10;
+
+pub fn ssrf_safe_redirect_policy() -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        let is_private = attempt
+            .url()
+            .host()
+            .is_some_and(|host| is_private_host(&host));
+        if is_private {
+            let url = attempt.url().to_string();
+            tracing::warn!(
+                request_id = %current_request_id(),
+                "Blocked redirect to private address {}",
+                url
+            );
+            return attempt.error(format!("redirect to private address {}", url));
+        }
+        attempt.follow()
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct SsrfSafeResolver;
+
+impl reqwest::dns::Resolve for SsrfSafeResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addresses = tokio::net::lookup_host((host, 0))
+                .await?
+                .collect::<Vec<_>>();
+            if let Some(address) = addresses
+                .iter()
+                .find(|address| is_private_ip(&address.ip()))
+            {
+                tracing::warn!(
+                    request_id = %current_request_id(),
+                    "Blocked connection to {}: resolves to private address {}",
+                    host,
+                    address.ip()
+                );
+                return Err(
+                    format!("{} resolves to private address {}", host, address.ip()).into(),
+                );
+            }
+            let addresses: reqwest::dns::Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+async fn check_ssrf(url: &str) -> Result<(), WebpageParseError> {
+    let parsed_url = match Url::parse(url) {
+        Ok(parsed_url) => parsed_url,
+        Err(_) => return Ok(()),
+    };
+    let addresses = resolve_host_addresses(&parsed_url).await;
+    match addresses.iter().find(|address| is_private_ip(address)) {
+        Some(resolved_ip) => {
+            tracing::warn!(
+                request_id = %current_request_id(),
+                "Blocked request to {}: resolves to private address {}",
+                url,
+                resolved_ip
+            );
+            Err(WebpageParseError::SSRFBlocked {
+                url: url.to_string(),
+                resolved_ip: resolved_ip.to_string(),
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+async fn head_preflight(
+    url: &str,
+    client: &reqwest::Client,
+    user_agent: &str,
+) -> Result<(), WebpageParseError> {
+    let response = match client
+        .head(url)
+        .header("user-agent", user_agent)
+        .timeout(HEAD_PREFLIGHT_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => response,
+        _ => return Ok(()),
+    };
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    if let Some(content_type) = &content_type {
+        let mime_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        if !is_text_like_mime_type(&mime_type) {
+            return Err(WebpageParseError::SkippedLargeOrBinaryContent(
+                url.to_string(),
+                mime_type,
+            ));
+        }
+    }
+    let content_length = response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if content_length.is_some_and(|content_length| content_length > max_fetch_bytes()) {
+        return Err(WebpageParseError::SkippedLargeOrBinaryContent(
+            url.to_string(),
+            content_type.unwrap_or_else(|| "unknown".to_string()),
+        ));
+    }
+    Ok(())
+}
+
+const DEFAULT_USER_AGENTS: [&str; 10] = [
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/127.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.36 Edg/128.0.0.0",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.36 Edg/128.0.0.0",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:130.0) Gecko/20100101 Firefox/130.0",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 14.6; rv:130.0) Gecko/20100101 Firefox/130.0",
+    "Mozilla/5.0 (X11; Linux x86_64; rv:129.0) Gecko/20100101 Firefox/129.0",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.6 Safari/605.1.15",
+    "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Mobile Safari/537.36",
+];
+
+const ACCEPT_LANGUAGES: [&str; 5] = [
+    "en-US,en;q=0.9",
+    "en-GB,en;q=0.9",
+    "en-US,en;q=0.8",
+    "en-CA,en;q=0.9,fr-CA;q=0.8",
+    "en-AU,en;q=0.9",
+];
+
+pub fn load_user_agents() -> Vec<String> {
+    let user_agents = std::env::var("USER_AGENTS")
+        .map(|value| {
+            value
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if user_agents.is_empty() {
+        return DEFAULT_USER_AGENTS
+            .iter()
+            .map(|ua| ua.to_string())
+            .collect();
+    }
+    user_agents
+}
+
+struct ClientHints {
+    sec_ch_ua: Option<String>,
+    sec_ch_ua_mobile: &'static str,
+    sec_ch_ua_platform: &'static str,
+}
+
+fn client_hints_for_user_agent(user_agent: &str) -> ClientHints {
+    let sec_ch_ua_platform = if user_agent.contains("Android") {
+        "\"Android\""
+    } else if user_agent.contains("iPhone") || user_agent.contains("iPad") {
+        "\"iOS\""
+    } else if user_agent.contains("Windows") {
+        "\"Windows\""
+    } else if user_agent.contains("Macintosh") {
+        "\"macOS\""
+    } else if user_agent.contains("Linux") {
+        "\"Linux\""
+    } else {
+        "\"Unknown\""
+    };
+    let sec_ch_ua_mobile = if user_agent.contains("Mobile") {
+        "?1"
+    } else {
+        "?0"
+    };
+    let chrome_version = user_agent
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix("Chrome/"))
+        .and_then(|version| version.split('.').next());
+    let sec_ch_ua = chrome_version.map(|version| {
+        let brand = if user_agent.contains("Edg/") {
+            "Microsoft Edge"
+        } else {
+            "Google Chrome"
+        };
+        format!(
+            "\"Chromium\";v=\"{version}\", \"Not;A=Brand\";v=\"24\", \"{brand}\";v=\"{version}\""
+        )
+    });
+    ClientHints {
+        sec_ch_ua,
+        sec_ch_ua_mobile,
+        sec_ch_ua_platform,
+    }
+}
+
+const MIN_USER_AGENT_LENGTH: usize = 10;
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+pub fn validate_user_agent(user_agent: &str) -> Result<(), WebpageParseError> {
+    let length = user_agent.chars().count();
+    if !(MIN_USER_AGENT_LENGTH..=MAX_USER_AGENT_LENGTH).contains(&length) {
+        return Err(WebpageParseError::InvalidUserAgent(format!(
+            "must be between {} and {} characters, got {}",
+            MIN_USER_AGENT_LENGTH, MAX_USER_AGENT_LENGTH, length
+        )));
+    }
+    if user_agent.contains(['\r', '\n']) {
+        return Err(WebpageParseError::InvalidUserAgent(
+            "must not contain newlines".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+const DEFAULT_DOMAIN_FETCH_DELAY_MS: u64 = 500;
+
+const DOMAIN_RATE_LIMITER_PRUNE_THRESHOLD: usize = 1000;
+
+const SECOND_LEVEL_LABELS: [&str; 7] = ["co", "com", "org", "net", "ac", "gov", "edu"];
+
+pub struct DomainRateLimiter {
+    last_fetch: DashMap<String, Instant>,
+    per_domain_delay: Duration,
+}
+
+impl DomainRateLimiter {
+    pub fn new(per_domain_delay: Duration) -> Self {
+        Self {
+            last_fetch: DashMap::new(),
+            per_domain_delay,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let delay_ms = std::env::var("DOMAIN_FETCH_DELAY_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(DEFAULT_DOMAIN_FETCH_DELAY_MS);
+        Self::new(Duration::from_millis(delay_ms))
+    }
+
+    pub async fn wait(&self, url: &str) {
+        let domain = match registered_domain(url) {
+            Some(domain) => domain,
+            None => return,
+        };
+        let now = Instant::now();
+        if self.last_fetch.len() >= DOMAIN_RATE_LIMITER_PRUNE_THRESHOLD {
+            self.last_fetch
+                .retain(|_, last_fetch| *last_fetch + self.per_domain_delay > now);
+        }
+        let mut scheduled_at = now;
+        self.last_fetch
+            .entry(domain)
+            .and_modify(|last_fetch| {
+                let next_allowed = *last_fetch + self.per_domain_delay;
+                if next_allowed > now {
+                    scheduled_at = next_allowed;
+                }
+                *last_fetch = scheduled_at;
+            })
+            .or_insert(now);
+        if scheduled_at > now {
+            tokio::time::sleep(scheduled_at - now).await;
+        }
+    }
+}
+
+fn registered_domain(url: &str) -> Option<String> {
+    let parsed_url = Url::parse(url).ok()?;
+    let host = parsed_url.host_str()?.to_lowercase();
+    let labels: Vec<&str> = host.split('.').collect();
+    if labels.len() <= 2 || host.parse::<std::net::IpAddr>().is_ok() {
+        return Some(host);
+    }
+    let num_labels = if SECOND_LEVEL_LABELS.contains(&labels[labels.len() - 2]) {
+        3
+    } else {
+        2
+    };
+    Some(labels[labels.len() - num_labels..].join("."))
+}
+
+const BOT_CHALLENGE_TITLES: [&str; 3] = ["just a moment", "checking your browser", "access denied"];
+
+fn is_bot_challenge_page(html: &str) -> bool {
+    let title_selector = match Selector::parse("title") {
+        Ok(selector) => selector,
+        Err(_) => return false,
+    };
+    Html::parse_document(html)
+        .select(&title_selector)
+        .next()
+        .map(|title| title.text().collect::<String>().to_lowercase())
+        .is_some_and(|title| {
+            BOT_CHALLENGE_TITLES
+                .iter()
+                .any(|challenge_title| title.contains(challenge_title))
+        })
+}
+
+pub async fn visit_and_parse_webpage(
+    url: &str,
+    client: &reqwest::Client,
+    user_agents: &[String],
+    user_agent_override: Option<&str>,
+    rate_limiter: &DomainRateLimiter,
+    dom_parse_config: &DomParseConfig,
+) -> Result<ParsedWebpage, WebpageParseError> {
+    let user_agent = match user_agent_override {
+        Some(user_agent) => {
+            validate_user_agent(user_agent)?;
+            user_agent
+        }
+        None => user_agents
+            .choose(&mut rand::thread_rng())
+            .map(|ua| ua.as_str())
+            .unwrap_or(DEFAULT_USER_AGENTS[0]),
+    };
+    let accept_language = ACCEPT_LANGUAGES
+        .choose(&mut rand::thread_rng())
+        .unwrap_or(&ACCEPT_LANGUAGES[0]);
+    let client_hints = client_hints_for_user_agent(user_agent);
+    check_ssrf(url).await?;
+    if head_preflight_enabled() {
+        head_preflight(url, client, user_agent).await?;
+    }
     let mut attempts = 0;
     let response = loop {
-        let client = reqwest::Client::builder()
-            .gzip(true)
-            .build()
-            .map_err(WebpageParseError::FetchError)?;
-        match client.get(url)
+        rate_limiter.wait(url).await;
+        let mut request = client.get(url)
             .header("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7")
-            .header("Accept-Language", "en-US,en;q=0.9")
-            .header("priority", "u=0, i")
-            .header("sec-ch-ua", "\"Chromium\";v=\"128\", \"Not;A=Brand\";v=\"24\", \"Google Chrome\";v=\"128\"")
-            .header("sec-ch-ua-mobile", "?0")
-            .header("sec-ch-ua-platform", "\"macOS\"")
+            .header("Accept-Language", *accept_language)
+            .header("priority", "u=0, i");
+        if let Some(sec_ch_ua) = &client_hints.sec_ch_ua {
+            request = request
+                .header("sec-ch-ua", sec_ch_ua)
+                .header("sec-ch-ua-mobile", client_hints.sec_ch_ua_mobile)
+                .header("sec-ch-ua-platform", client_hints.sec_ch_ua_platform);
+        }
+        match request
             .header("sec-fetch-dest", "document")
             .header("sec-fetch-mode", "navigate")
             .header("sec-fetch-site", "none")
             .header("sec-fetch-user", "?1")
             .header("upgrade-insecure-requests", "1")
-            .header("user-agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/128.0.0.0 Safari/537.36")
+            .header("user-agent", user_agent)
             .header("Accept-Encoding", "gzip")
             .send()
             .await
@@ -70,18 +522,78 @@ pub async fn visit_and_parse_webpage(url: &str) -> Result<ParsedWebpage, Webpage
             }
         }
     };
-    let webpage_text = match response.text().await {
-        Ok(text) => text,
-        Err(e) => return Err(WebpageParseError::FetchError(e)),
+    let last_modified = response
+        .headers()
+        .get(reqwest::header::LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let content_type = ContentType::from_header(
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok()),
+    );
+    let is_challenge_response = response
+        .headers()
+        .get("cf-mitigated")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("challenge"));
+    if is_challenge_response {
+        tracing::warn!(
+            request_id = %current_request_id(),
+            "Warning: bot challenge detected at {}",
+            url
+        );
+        return Err(WebpageParseError::BotChallenge(url.to_string()));
+    }
+    if content_type == ContentType::Pdf {
+        return Err(WebpageParseError::UnsupportedContentType(
+            "application/pdf".to_string(),
+        ));
+    }
+    let max_streamed_chars = match content_type {
+        ContentType::Html | ContentType::PlainText => dom_parse_config.max_content_chars,
+        ContentType::Json | ContentType::Csv | ContentType::Pdf => None,
     };
-    let dom_text = match dom_parse_webpage(&webpage_text) {
-        Ok(text) => text,
-        Err(e) => return Err(WebpageParseError::DomParseError(e)),
-    };
-    let trimmed_text = dom_text.content.trim();
+    let webpage_text = read_body_streaming(
+        response,
+        content_type == ContentType::Html,
+        max_streamed_chars,
+        max_fetch_bytes() as usize,
+    )
+    .await?;
+    if content_type == ContentType::Html && is_bot_challenge_page(&webpage_text) {
+        tracing::warn!(
+            request_id = %current_request_id(),
+            "Warning: bot challenge detected at {}",
+            url
+        );
+        return Err(WebpageParseError::BotChallenge(url.to_string()));
+    }
+    let mut dom_text =
+        match parse_webpage_content(&webpage_text, url, content_type, dom_parse_config) {
+            Ok(text) => text,
+            Err(e) => return Err(WebpageParseError::DomParseError(e)),
+        };
+    if let Some(metadata) = fetch_github_repo_metadata(url, client).await {
+        dom_text.content = format!(
+            "{}{}{}",
+            metadata, PARSED_CONTENT_SECTION_DIVIDER, dom_text.content
+        );
+    }
+    let mut trimmed_text = dom_text.content.trim();
+    if content_type != ContentType::Html {
+        if let Some(max_content_chars) = dom_parse_config.max_content_chars {
+            trimmed_text = truncate_content(url, trimmed_text, max_content_chars);
+        }
+    }
     Ok(ParsedWebpage {
         original_content: dom_text.original_content,
         content: trimmed_text.to_string(),
+        is_snippet: false,
+        published_at: dom_text.published_at,
+        modified_at: dom_text.modified_at.or(last_modified),
+        image_urls: dom_text.image_urls,
     })
 }
 
@@ -127,10 +639,446 @@ const BLACKLISTED_TAGS: [&str; 27] = [
     "center",
 ];
 
-fn dom_parse_webpage(webpage_text: &str) -> Result<ParsedWebpage, DomParseError> {
-    let clean_html = Builder::new()
-        .rm_tags(BLACKLISTED_TAGS)
-        .generic_attributes(HashSet::from_iter(WHITELISTED_ATTRIBUTES))
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomParseConfig {
+    pub strip_tags: Vec<String>,
+    pub keep_attributes: Vec<String>,
+    pub max_content_chars: Option<usize>,
+    pub vision_enabled: bool,
+    pub user_agent: Option<String>,
+    pub max_content_age_days: Option<u32>,
+    pub min_content_chars: Option<usize>,
+}
+
+impl Default for DomParseConfig {
+    fn default() -> Self {
+        Self {
+            strip_tags: BLACKLISTED_TAGS.iter().map(|tag| tag.to_string()).collect(),
+            keep_attributes: WHITELISTED_ATTRIBUTES
+                .iter()
+                .map(|attribute| attribute.to_string())
+                .collect(),
+            max_content_chars: None,
+            vision_enabled: false,
+            user_agent: None,
+            max_content_age_days: None,
+            min_content_chars: None,
+        }
+    }
+}
+
+impl DomParseConfig {
+    pub fn parse_options(&self) -> Self {
+        Self {
+            max_content_age_days: None,
+            min_content_chars: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn from_overrides(
+        strip_tags: Option<&Vec<String>>,
+        keep_attributes: Option<&Vec<String>>,
+    ) -> Self {
+        let default_config = Self::default();
+        Self {
+            strip_tags: strip_tags.cloned().unwrap_or(default_config.strip_tags),
+            keep_attributes: keep_attributes
+                .cloned()
+                .unwrap_or(default_config.keep_attributes),
+            max_content_chars: None,
+            vision_enabled: false,
+            user_agent: None,
+            max_content_age_days: None,
+            min_content_chars: None,
+        }
+    }
+}
+
+const MAX_TABLE_COLUMNS: usize = 20;
+const MAX_TABLE_ROWS: usize = 200;
+const PARSED_CONTENT_SECTION_DIVIDER: &str = "\n\n---\n\n";
+
+fn element_text(element: &ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn table_to_markdown(table: &ElementRef) -> Option<String> {
+    let row_selector = Selector::parse("tr").unwrap();
+    let rows: Vec<Vec<String>> = table
+        .select(&row_selector)
+        .filter(|row| {
+            row.ancestors()
+                .find(|node| {
+                    node.value()
+                        .as_element()
+                        .is_some_and(|element| element.name() == "table")
+                })
+                .is_some_and(|node| node.id() == table.id())
+        })
+        .map(|row| {
+            row.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                .map(|cell| element_text(&cell).replace('|', "\\|"))
+                .collect::<Vec<_>>()
+        })
+        .filter(|cells| cells.iter().any(|cell| !cell.is_empty()))
+        .collect();
+    rows_to_markdown(&rows)
+}
+
+fn rows_to_markdown(rows: &[Vec<String>]) -> Option<String> {
+    if rows.is_empty() {
+        return None;
+    }
+    let num_rows = rows.len();
+    let num_columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let kept_columns = num_columns.min(MAX_TABLE_COLUMNS);
+    let mut lines = Vec::new();
+    for (i, row) in rows.iter().take(MAX_TABLE_ROWS).enumerate() {
+        let cells = (0..kept_columns)
+            .map(|j| row.get(j).map_or("", |cell| cell.as_str()))
+            .collect::<Vec<_>>();
+        lines.push(format!("| {} |", cells.join(" | ")));
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(kept_columns)));
+        }
+    }
+    if num_rows > MAX_TABLE_ROWS {
+        lines.push(format!(
+            "\n(Table truncated: showing the first {} of {} rows)",
+            MAX_TABLE_ROWS, num_rows
+        ));
+    }
+    if num_columns > MAX_TABLE_COLUMNS {
+        lines.push(format!(
+            "\n(Table truncated: showing the first {} of {} columns)",
+            MAX_TABLE_COLUMNS, num_columns
+        ));
+    }
+    Some(lines.join("\n"))
+}
+
+pub fn extract_tables(document: &Html) -> Vec<String> {
+    let table_selector = Selector::parse("table").unwrap();
+    document
+        .select(&table_selector)
+        .filter_map(|table| table_to_markdown(&table))
+        .collect()
+}
+
+const MAX_CODE_BLOCK_LENGTH: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub content: String,
+}
+
+impl std::fmt::Display for CodeBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "```{}\n{}\n```",
+            self.language.as_deref().unwrap_or(""),
+            self.content
+        )
+    }
+}
+
+fn code_block_language(element: &ElementRef) -> Option<String> {
+    element.value().classes().find_map(|class| {
+        class
+            .strip_prefix("language-")
+            .or_else(|| class.strip_prefix("lang-"))
+            .filter(|language| !language.is_empty())
+            .map(|language| language.to_string())
+    })
+}
+
+pub fn extract_code_blocks(document: &Html) -> Vec<CodeBlock> {
+    let pre_selector = Selector::parse("pre").unwrap();
+    let code_selector = Selector::parse("code").unwrap();
+    document
+        .select(&pre_selector)
+        .filter_map(|pre| {
+            let code = pre.select(&code_selector).next();
+            let language = code
+                .as_ref()
+                .and_then(code_block_language)
+                .or_else(|| code_block_language(&pre));
+            let text = code.unwrap_or(pre).text().collect::<String>();
+            let text = text.trim_matches('\n').trim_end();
+            if text.trim().is_empty() {
+                return None;
+            }
+            let content = if text.chars().count() > MAX_CODE_BLOCK_LENGTH {
+                format!(
+                    "{}\n// ... (truncated)",
+                    text.chars().take(MAX_CODE_BLOCK_LENGTH).collect::<String>()
+                )
+            } else {
+                text.to_string()
+            };
+            Some(CodeBlock { language, content })
+        })
+        .collect()
+}
+
+fn first_attribute_value(document: &Html, selector: &str, attribute: &str) -> Option<String> {
+    let selector = Selector::parse(selector).unwrap();
+    document
+        .select(&selector)
+        .filter_map(|element| element.value().attr(attribute))
+        .map(|value| value.trim())
+        .find(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+
+const NAVIGATION_LINK_ANCESTORS: [&str; 4] = ["nav", "header", "footer", "aside"];
+const MAX_IMAGE_URLS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct PageLink {
+    pub url: String,
+    pub text: String,
+}
+
+pub fn extract_links(html: &str, page_url: &str) -> Vec<PageLink> {
+    let page_url = match Url::parse(page_url) {
+        Ok(page_url) => page_url,
+        Err(_) => return Vec::new(),
+    };
+    let document = Html::parse_document(html);
+    let link_selector = Selector::parse("a[href]").unwrap();
+    let mut seen_urls = HashSet::new();
+    seen_urls.insert(page_url.to_string());
+    document
+        .select(&link_selector)
+        .filter(|link| {
+            !link.ancestors().any(|node| {
+                node.value()
+                    .as_element()
+                    .is_some_and(|element| NAVIGATION_LINK_ANCESTORS.contains(&element.name()))
+            })
+        })
+        .filter_map(|link| {
+            let mut url = page_url.join(link.value().attr("href")?).ok()?;
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return None;
+            }
+            url.set_fragment(None);
+            if !seen_urls.insert(url.to_string()) {
+                return None;
+            }
+            Some(PageLink {
+                url: url.to_string(),
+                text: element_text(&link),
+            })
+        })
+        .collect()
+}
+
+pub fn extract_image_urls(document: &Html, base_url: &str) -> Vec<String> {
+    let base_url = match Url::parse(base_url) {
+        Ok(base_url) => base_url,
+        Err(_) => return Vec::new(),
+    };
+    let image_selector = Selector::parse("img[src], picture source[srcset]").unwrap();
+    let mut seen_urls = HashSet::new();
+    document
+        .select(&image_selector)
+        .filter_map(|image| {
+            let src = match image.value().attr("src") {
+                Some(src) => src,
+                None => image
+                    .value()
+                    .attr("srcset")?
+                    .split(',')
+                    .next()?
+                    .split_whitespace()
+                    .next()?,
+            };
+            let url = base_url.join(src.trim()).ok()?;
+            if url.scheme() != "https" || !seen_urls.insert(url.to_string()) {
+                return None;
+            }
+            Some(url.to_string())
+        })
+        .take(MAX_IMAGE_URLS)
+        .collect()
+}
+
+pub fn extract_metadata_dates(document: &Html) -> (Option<String>, Option<String>) {
+    let published_at = first_attribute_value(
+        document,
+        r#"meta[property="article:published_time"]"#,
+        "content",
+    )
+    .or_else(|| first_attribute_value(document, r#"meta[name="date"]"#, "content"))
+    .or_else(|| first_attribute_value(document, "time[datetime]", "datetime"));
+    let modified_at = first_attribute_value(
+        document,
+        r#"meta[property="article:modified_time"]"#,
+        "content",
+    )
+    .or_else(|| first_attribute_value(document, r#"meta[property="og:updated_time"]"#, "content"));
+    (published_at, modified_at)
+}
+
+fn parse_metadata_date(date: &str) -> Option<Date> {
+    let date = date.trim().get(..10)?;
+    Date::parse(date, format_description!("[year]-[month]-[day]")).ok()
+}
+
+pub fn latest_metadata_date(published_at: Option<&str>, modified_at: Option<&str>) -> Option<Date> {
+    [published_at, modified_at]
+        .into_iter()
+        .flatten()
+        .filter_map(parse_metadata_date)
+        .max()
+}
+
+pub fn is_stale_date(date: Date, max_content_age_days: u32) -> bool {
+    (OffsetDateTime::now_utc().date() - date).whole_days() > max_content_age_days as i64
+}
+
+fn parse_csv_rows(csv_text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = csv_text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.into_iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| {
+                    cell.split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                        .replace('|', "\\|")
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|cells| cells.iter().any(|cell| !cell.is_empty()))
+        .collect()
+}
+
+fn truncate_content<'a>(url: &str, text: &'a str, max_content_chars: usize) -> &'a str {
+    let num_chars = text.chars().count();
+    if num_chars <= max_content_chars {
+        return text;
+    }
+    let truncated_text = truncate_at_sentence(text, max_content_chars);
+    let kept_chars = truncated_text.chars().count();
+    tracing::debug!(
+        request_id = %current_request_id(),
+        "Truncated {} at char {} (kept {:.1}% of {} chars)",
+        url,
+        kept_chars,
+        kept_chars as f64 / num_chars as f64 * 100.0,
+        num_chars
+    );
+    truncated_text
+}
+
+fn within_char_budget(sections: Vec<String>, max_chars: Option<usize>) -> Vec<String> {
+    let max_chars = match max_chars {
+        Some(max_chars) => max_chars,
+        None => return sections,
+    };
+    let mut num_chars = 0;
+    sections
+        .into_iter()
+        .take_while(|section| {
+            num_chars += section.chars().count();
+            num_chars <= max_chars
+        })
+        .collect()
+}
+
+fn parse_webpage_content(
+    webpage_text: &str,
+    url: &str,
+    content_type: ContentType,
+    dom_parse_config: &DomParseConfig,
+) -> Result<ParsedWebpage, DomParseError> {
+    let content = match content_type {
+        ContentType::Html => return dom_parse_webpage(webpage_text, url, dom_parse_config),
+        ContentType::Pdf => {
+            return Err(DomParseError::UnsupportedContentType(
+                "application/pdf".to_string(),
+            ))
+        }
+        ContentType::PlainText => enforce_n_sequential_newlines(webpage_text, 2),
+        ContentType::Json => match serde_json::from_str::<serde_json::Value>(webpage_text) {
+            Ok(value) => match serde_json::to_string_pretty(&value) {
+                Ok(pretty) => pretty,
+                Err(e) => return Err(DomParseError::ParseError(e.to_string())),
+            },
+            Err(_) => webpage_text.to_string(),
+        },
+        ContentType::Csv => rows_to_markdown(&parse_csv_rows(webpage_text)).unwrap_or_default(),
+    };
+    Ok(ParsedWebpage {
+        original_content: webpage_text.to_string(),
+        content,
+        is_snippet: false,
+        published_at: None,
+        modified_at: None,
+        image_urls: Vec::new(),
+    })
+}
+
+fn dom_parse_webpage(
+    webpage_text: &str,
+    url: &str,
+    config: &DomParseConfig,
+) -> Result<ParsedWebpage, DomParseError> {
+    let document = Html::parse_document(webpage_text);
+    let (published_at, modified_at) = extract_metadata_dates(&document);
+    let academic_paper = extract_academic_metadata(&document, url);
+    let infobox = extract_wikipedia_infobox(&document);
+    let github_repo = extract_github_repo(url, &document);
+    let tables = extract_tables(&document);
+    let code_blocks = extract_code_blocks(&document);
+    let image_urls = extract_image_urls(&document, url);
+    let mut html_cleaner = Builder::new();
+    if config.keep_attributes.iter().any(|name| name == "rel") {
+        html_cleaner.link_rel(None);
+    }
+    let clean_html = html_cleaner
+        .rm_tags(config.strip_tags.iter().map(String::as_str))
+        .generic_attributes(HashSet::from_iter(
+            config.keep_attributes.iter().map(String::as_str),
+        ))
         .attribute_filter(|element, attribute, value| match (element, attribute) {
             ("div", "src") => None,
             ("img", "src") => None,
@@ -148,8 +1096,33 @@ fn dom_parse_webpage(webpage_text: &str) -> Result<ParsedWebpage, DomParseError>
         .collect::<Vec<&str>>()
         .join("\n");
     let clean_html = enforce_n_sequential_newlines(&clean_html, 2);
+    let clean_html = github_repo.unwrap_or(clean_html);
+    let clean_html = match config.max_content_chars {
+        Some(max_content_chars) => truncate_content(url, clean_html.trim(), max_content_chars),
+        None => clean_html.as_str(),
+    };
+    let structured_sections = within_char_budget(
+        tables
+            .into_iter()
+            .chain(code_blocks.iter().map(|code_block| code_block.to_string()))
+            .collect(),
+        config.max_content_chars,
+    );
+    let content = academic_paper
+        .as_ref()
+        .map(|paper| paper.to_string())
+        .into_iter()
+        .chain(infobox.as_ref().map(format_wikipedia_infobox))
+        .chain(std::iter::once(clean_html.to_string()))
+        .chain(structured_sections)
+        .collect::<Vec<_>>()
+        .join(PARSED_CONTENT_SECTION_DIVIDER);
     Ok(ParsedWebpage {
         original_content: webpage_text.to_string(),
-        content: clean_html,
+        content,
+        is_snippet: false,
+        published_at,
+        modified_at,
+        image_urls,
     })
 }
@@ -0,0 +1,218 @@
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AcademicPaper {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub abstract_text: String,
+    pub doi: Option<String>,
+    pub published: Option<String>,
+    pub venue: Option<String>,
+}
+
+impl std::fmt::Display for AcademicPaper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Paper:")?;
+        writeln!(f, "Title: {}", self.title)?;
+        if !self.authors.is_empty() {
+            writeln!(f, "Authors: {}", self.authors.join(", "))?;
+        }
+        if let Some(published) = &self.published {
+            writeln!(f, "Published: {}", published)?;
+        }
+        if let Some(venue) = &self.venue {
+            writeln!(f, "Venue: {}", venue)?;
+        }
+        if let Some(doi) = &self.doi {
+            writeln!(f, "DOI: {}", doi)?;
+        }
+        write!(f, "Abstract: {}", self.abstract_text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AcademicSite {
+    Arxiv,
+    GoogleScholar,
+    Doi,
+    PubMed,
+}
+
+impl AcademicSite {
+    fn from_url(url: &str) -> Option<Self> {
+        let host = Url::parse(url).ok()?.host_str()?.to_lowercase();
+        let matches = |domain: &str| host == domain || host.ends_with(&format!(".{}", domain));
+        if matches("arxiv.org") {
+            Some(AcademicSite::Arxiv)
+        } else if matches("scholar.google.com") {
+            Some(AcademicSite::GoogleScholar)
+        } else if matches("doi.org") {
+            Some(AcademicSite::Doi)
+        } else if matches("ncbi.nlm.nih.gov") {
+            Some(AcademicSite::PubMed)
+        } else {
+            None
+        }
+    }
+
+    fn title_selectors(&self) -> &'static [&'static str] {
+        match self {
+            AcademicSite::Arxiv => &["h1.title"],
+            AcademicSite::GoogleScholar => &["#gsc_oci_title"],
+            AcademicSite::Doi => &[],
+            AcademicSite::PubMed => &["h1.heading-title"],
+        }
+    }
+
+    fn abstract_selectors(&self) -> &'static [&'static str] {
+        match self {
+            AcademicSite::Arxiv => &["blockquote.abstract"],
+            AcademicSite::GoogleScholar => &["#gsh_ab", ".gsh_csp"],
+            AcademicSite::Doi => &["section.abstract", "div.abstract", "#abstract"],
+            AcademicSite::PubMed => &["#eng-abstract", "div.abstract-content", "#abstract"],
+        }
+    }
+
+    fn default_venue(&self) -> Option<&'static str> {
+        match self {
+            AcademicSite::Arxiv => Some("arXiv"),
+            _ => None,
+        }
+    }
+}
+
+fn meta_values(document: &Html, name: &str) -> Vec<String> {
+    let selector = Selector::parse(&format!(r#"meta[name="{}"]"#, name)).unwrap();
+    document
+        .select(&selector)
+        .filter_map(|element| element.value().attr("content"))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn first_meta_value(document: &Html, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| meta_values(document, name).into_iter().next())
+}
+
+fn first_selector_text(document: &Html, selectors: &[&str]) -> Option<String> {
+    selectors.iter().find_map(|selector| {
+        let selector = Selector::parse(selector).unwrap();
+        document
+            .select(&selector)
+            .map(|element| {
+                element
+                    .text()
+                    .collect::<String>()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .find(|text| !text.is_empty())
+    })
+}
+
+fn scholar_field(document: &Html, field_name: &str) -> Option<String> {
+    let row_selector = Selector::parse("#gsc_oci_table .gs_scl").unwrap();
+    let field_selector = Selector::parse(".gsc_oci_field").unwrap();
+    let value_selector = Selector::parse(".gsc_oci_value").unwrap();
+    document.select(&row_selector).find_map(|row| {
+        let field = row
+            .select(&field_selector)
+            .next()?
+            .text()
+            .collect::<String>();
+        if !field.trim().eq_ignore_ascii_case(field_name) {
+            return None;
+        }
+        let value = row
+            .select(&value_selector)
+            .next()?
+            .text()
+            .collect::<String>();
+        Some(value.trim().to_string()).filter(|value| !value.is_empty())
+    })
+}
+
+fn doi_from_url(url: &str) -> Option<String> {
+    let parsed_url = Url::parse(url).ok()?;
+    let path = parsed_url.path().trim_start_matches('/');
+    if path.starts_with("10.") {
+        Some(path.to_string())
+    } else {
+        None
+    }
+}
+
+pub fn extract_academic_metadata(document: &Html, url: &str) -> Option<AcademicPaper> {
+    let site = AcademicSite::from_url(url)?;
+    let title = first_meta_value(document, &["citation_title", "dc.title", "DC.title"])
+        .or_else(|| first_selector_text(document, site.title_selectors()))
+        .map(|title| title.trim_start_matches("Title:").trim().to_string())?;
+    let mut authors = meta_values(document, "citation_author");
+    if authors.is_empty() {
+        authors = first_meta_value(document, &["citation_authors"])
+            .or_else(|| scholar_field(document, "Authors"))
+            .map(|authors| {
+                authors
+                    .split([';', ','])
+                    .map(|author| author.trim().to_string())
+                    .filter(|author| !author.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+    let abstract_text = first_meta_value(document, &["citation_abstract"])
+        .or_else(|| first_selector_text(document, site.abstract_selectors()))
+        .or_else(|| scholar_field(document, "Description"))
+        .or_else(|| first_meta_value(document, &["description", "dc.description"]))
+        .map(|abstract_text| {
+            abstract_text
+                .trim_start_matches("Abstract:")
+                .trim_start_matches("Abstract")
+                .trim()
+                .to_string()
+        })
+        .unwrap_or_default();
+    if authors.is_empty() && abstract_text.is_empty() {
+        return None;
+    }
+    let doi = first_meta_value(document, &["citation_doi", "dc.identifier"])
+        .map(|doi| doi.trim_start_matches("doi:").to_string())
+        .or_else(|| match site {
+            AcademicSite::Doi => doi_from_url(url),
+            _ => None,
+        });
+    let published = first_meta_value(
+        document,
+        &[
+            "citation_publication_date",
+            "citation_date",
+            "citation_online_date",
+        ],
+    )
+    .or_else(|| scholar_field(document, "Publication date"));
+    let venue = first_meta_value(
+        document,
+        &[
+            "citation_journal_title",
+            "citation_conference_title",
+            "citation_publisher",
+        ],
+    )
+    .or_else(|| scholar_field(document, "Journal"))
+    .or_else(|| scholar_field(document, "Conference"))
+    .or_else(|| site.default_venue().map(|venue| venue.to_string()));
+    Some(AcademicPaper {
+        title,
+        authors,
+        abstract_text,
+        doi,
+        published,
+        venue,
+    })
+}
@@ -0,0 +1,206 @@
+use crate::request_context::current_request_id;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+
+const GITHUB_API_BASE_URL: &str = "https://api.github.com";
+const GITHUB_API_TIMEOUT: Duration = Duration::from_secs(10);
+const GITHUB_API_USER_AGENT: &str = "web-browser";
+const RESERVED_GITHUB_OWNERS: [&str; 16] = [
+    "about",
+    "collections",
+    "customer-stories",
+    "enterprise",
+    "events",
+    "explore",
+    "features",
+    "login",
+    "marketplace",
+    "orgs",
+    "pricing",
+    "search",
+    "settings",
+    "sponsors",
+    "topics",
+    "trending",
+];
+const README_BLOCK_SELECTOR: &str =
+    "#readme h1, #readme h2, #readme h3, #readme h4, #readme h5, #readme h6, #readme p, #readme li, #readme pre";
+const LANGUAGE_SELECTORS: [&str; 2] = [
+    r#"[itemprop="programmingLanguage"]"#,
+    r#"a[href*="search?l="] span.text-bold"#,
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GitHubRepoMetadata {
+    pub full_name: String,
+    #[serde(default)]
+    pub stargazers_count: u64,
+    #[serde(default)]
+    pub forks_count: u64,
+    #[serde(default)]
+    pub topics: Vec<String>,
+}
+
+impl std::fmt::Display for GitHubRepoMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "GitHub repository metadata: {}", self.full_name)?;
+        writeln!(f, "Stars: {}", self.stargazers_count)?;
+        write!(f, "Forks: {}", self.forks_count)?;
+        if !self.topics.is_empty() {
+            write!(f, "\nTopics: {}", self.topics.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_github_repo_url(url: &str) -> Option<(String, String)> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    if host != "github.com" && host != "www.github.com" {
+        return None;
+    }
+    let segments = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    let (owner, repo) = match segments.as_slice() {
+        [owner, repo] => (*owner, repo.trim_end_matches(".git")),
+        _ => return None,
+    };
+    if repo.is_empty() || RESERVED_GITHUB_OWNERS.contains(&owner.to_lowercase().as_str()) {
+        return None;
+    }
+    Some((owner.to_string(), repo.to_string()))
+}
+
+fn element_text(element: ElementRef) -> String {
+    element.text().collect::<Vec<_>>().join(" ")
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn extract_readme(document: &Html) -> Option<String> {
+    let readme_selector = Selector::parse("#readme").unwrap();
+    let block_selector = Selector::parse(README_BLOCK_SELECTOR).unwrap();
+    let paragraph_selector = Selector::parse("p").unwrap();
+    let readme = document.select(&readme_selector).next()?;
+    let blocks = document
+        .select(&block_selector)
+        .filter(|block| {
+            block.value().name() != "li" || block.select(&paragraph_selector).next().is_none()
+        })
+        .filter_map(|block| {
+            let text = if block.value().name() == "pre" {
+                block.text().collect::<String>().trim().to_string()
+            } else {
+                normalize_whitespace(&element_text(block))
+            };
+            match block.value().name() {
+                _ if text.is_empty() => None,
+                "li" => Some(format!("- {}", text)),
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Some(format!("## {}", text)),
+                "pre" => Some(format!("```\n{}\n```", text)),
+                _ => Some(text),
+            }
+        })
+        .collect::<Vec<_>>();
+    let readme_text = if blocks.is_empty() {
+        normalize_whitespace(&element_text(readme))
+    } else {
+        blocks.join("\n\n")
+    };
+    if readme_text.is_empty() {
+        return None;
+    }
+    Some(readme_text)
+}
+
+fn extract_primary_language(document: &Html) -> Option<String> {
+    LANGUAGE_SELECTORS.iter().find_map(|selector| {
+        let selector = Selector::parse(selector).unwrap();
+        document
+            .select(&selector)
+            .map(|element| normalize_whitespace(&element_text(element)))
+            .find(|language| !language.is_empty())
+    })
+}
+
+pub fn extract_github_repo(url: &str, document: &Html) -> Option<String> {
+    let (owner, repo) = parse_github_repo_url(url)?;
+    let description_selector = Selector::parse(r#"meta[property="og:description"]"#).unwrap();
+    let description = document
+        .select(&description_selector)
+        .filter_map(|element| element.value().attr("content"))
+        .map(normalize_whitespace)
+        .find(|description| !description.is_empty());
+    let readme = extract_readme(document);
+    let language = extract_primary_language(document);
+    if description.is_none() && readme.is_none() {
+        return None;
+    }
+    let mut lines = vec![format!("GitHub repository: {}/{}", owner, repo)];
+    if let Some(description) = description {
+        lines.push(format!("Description: {}", description));
+    }
+    if let Some(language) = language {
+        lines.push(format!("Primary language: {}", language));
+    }
+    if let Some(readme) = readme {
+        lines.push(format!("\nREADME:\n{}", readme));
+    }
+    Some(lines.join("\n"))
+}
+
+pub async fn fetch_github_repo_metadata(
+    url: &str,
+    client: &reqwest::Client,
+) -> Option<GitHubRepoMetadata> {
+    let (owner, repo) = parse_github_repo_url(url)?;
+    let response = client
+        .get(format!("{}/repos/{}/{}", GITHUB_API_BASE_URL, owner, repo))
+        .timeout(GITHUB_API_TIMEOUT)
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", GITHUB_API_USER_AGENT)
+        .send()
+        .await;
+    let response = match response {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            tracing::warn!(
+                request_id = %current_request_id(),
+                "GitHub API returned status {} for {}/{}",
+                response.status(),
+                owner,
+                repo
+            );
+            return None;
+        }
+        Err(e) => {
+            tracing::warn!(
+                request_id = %current_request_id(),
+                "Failed to fetch GitHub metadata for {}/{}: {}",
+                owner,
+                repo,
+                e
+            );
+            return None;
+        }
+    };
+    match response.json::<GitHubRepoMetadata>().await {
+        Ok(metadata) => Some(metadata),
+        Err(e) => {
+            tracing::warn!(
+                request_id = %current_request_id(),
+                "Failed to parse GitHub metadata for {}/{}: {}",
+                owner,
+                repo,
+                e
+            );
+            None
+        }
+    }
+}
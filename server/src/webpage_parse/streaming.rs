@@ -0,0 +1,387 @@
+use crate::webpage_parse::{DomParseError, WebpageParseError};
+use bytes::Bytes;
+use encoding_rs::{Decoder, Encoding, UTF_8};
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{
+    BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+const CHUNK_CHANNEL_CAPACITY: usize = 4;
+
+const DROPPED_CONTENT_TAGS: [&str; 8] = [
+    "script", "style", "noscript", "svg", "iframe", "canvas", "template", "object",
+];
+
+const STRUCTURED_CONTENT_TAGS: [&str; 2] = ["table", "pre"];
+
+fn raw_kind(tag_name: &str) -> Option<RawKind> {
+    match tag_name {
+        "script" => Some(RawKind::ScriptData),
+        "style" | "iframe" | "noscript" | "noembed" | "noframes" | "xmp" => Some(RawKind::Rawtext),
+        "title" | "textarea" => Some(RawKind::Rcdata),
+        _ => None,
+    }
+}
+
+fn is_json_ld_script(tag: &Tag) -> bool {
+    tag.attrs.iter().any(|attr| {
+        attr.name.local.as_ref() == "type"
+            && attr
+                .value
+                .trim()
+                .eq_ignore_ascii_case("application/ld+json")
+    })
+}
+
+fn escape_html(text: &str, escape_quotes: bool, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if escape_quotes => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+struct HtmlReducer {
+    html: RefCell<String>,
+    max_text_chars: Option<usize>,
+    text_chars: Cell<usize>,
+    structured_chars: Cell<usize>,
+    structured_depth: Cell<usize>,
+    dropped_tag: RefCell<Option<(String, usize)>>,
+    in_json_ld: Cell<bool>,
+}
+
+impl HtmlReducer {
+    fn new(max_text_chars: Option<usize>) -> Self {
+        Self {
+            html: RefCell::new(String::new()),
+            max_text_chars,
+            text_chars: Cell::new(0),
+            structured_chars: Cell::new(0),
+            structured_depth: Cell::new(0),
+            dropped_tag: RefCell::new(None),
+            in_json_ld: Cell::new(false),
+        }
+    }
+
+    fn is_text_full(&self) -> bool {
+        self.max_text_chars
+            .is_some_and(|max_text_chars| self.text_chars.get() >= max_text_chars)
+    }
+
+    fn is_full(&self) -> bool {
+        self.max_text_chars
+            .is_some_and(|max_text_chars| self.structured_chars.get() >= max_text_chars)
+    }
+
+    fn is_writing(&self) -> bool {
+        !self.is_text_full() || self.structured_depth.get() > 0
+    }
+
+    fn write_tag(&self, tag: &Tag) {
+        let mut html = self.html.borrow_mut();
+        match tag.kind {
+            TagKind::StartTag => {
+                html.push('<');
+                html.push_str(&tag.name);
+                for attr in &tag.attrs {
+                    let name = attr.name.local.as_ref();
+                    if name == "style" || name.starts_with("on") {
+                        continue;
+                    }
+                    html.push(' ');
+                    html.push_str(name);
+                    html.push_str("=\"");
+                    escape_html(&attr.value, true, &mut html);
+                    html.push('"');
+                }
+                if tag.self_closing {
+                    html.push_str(" /");
+                }
+                html.push('>');
+            }
+            TagKind::EndTag => {
+                html.push_str("</");
+                html.push_str(&tag.name);
+                html.push('>');
+            }
+        }
+    }
+
+    fn process_tag(&self, tag: Tag) -> TokenSinkResult<()> {
+        let name = tag.name.as_ref();
+        let raw_kind = match tag.kind {
+            TagKind::StartTag => raw_kind(name),
+            TagKind::EndTag => None,
+        };
+        {
+            let mut dropped_tag = self.dropped_tag.borrow_mut();
+            if let Some((dropped_name, depth)) = dropped_tag.as_mut() {
+                if dropped_name == name {
+                    match tag.kind {
+                        TagKind::StartTag if !tag.self_closing => *depth += 1,
+                        TagKind::StartTag => {}
+                        TagKind::EndTag => *depth -= 1,
+                    }
+                    if *depth == 0 {
+                        *dropped_tag = None;
+                    }
+                }
+                return match raw_kind {
+                    Some(kind) => TokenSinkResult::RawData(kind),
+                    None => TokenSinkResult::Continue,
+                };
+            }
+            if tag.kind == TagKind::StartTag
+                && !tag.self_closing
+                && DROPPED_CONTENT_TAGS.contains(&name)
+                && !(name == "script" && is_json_ld_script(&tag))
+            {
+                *dropped_tag = Some((name.to_string(), 1));
+                return match raw_kind {
+                    Some(kind) => TokenSinkResult::RawData(kind),
+                    None => TokenSinkResult::Continue,
+                };
+            }
+        }
+        let is_structured = STRUCTURED_CONTENT_TAGS.contains(&name);
+        let structured_depth = self.structured_depth.get();
+        match tag.kind {
+            TagKind::StartTag if is_structured && !tag.self_closing => {
+                self.structured_depth.set(structured_depth + 1)
+            }
+            _ => {}
+        }
+        if self.is_writing() {
+            if name == "script" {
+                self.in_json_ld
+                    .set(tag.kind == TagKind::StartTag && !tag.self_closing);
+            }
+            self.write_tag(&tag);
+        }
+        if tag.kind == TagKind::EndTag && is_structured {
+            self.structured_depth
+                .set(structured_depth.saturating_sub(1));
+        }
+        match raw_kind {
+            Some(kind) if !tag.self_closing => TokenSinkResult::RawData(kind),
+            _ => TokenSinkResult::Continue,
+        }
+    }
+
+    fn process_text(&self, text: &StrTendril) {
+        if self.dropped_tag.borrow().is_some() || !self.is_writing() {
+            return;
+        }
+        let mut html = self.html.borrow_mut();
+        if self.in_json_ld.get() {
+            html.push_str(text);
+            return;
+        }
+        escape_html(text, false, &mut html);
+        let num_chars = text.trim().chars().count();
+        if self.is_text_full() {
+            self.structured_chars
+                .set(self.structured_chars.get() + num_chars);
+        } else {
+            self.text_chars.set(self.text_chars.get() + num_chars);
+        }
+    }
+}
+
+impl TokenSink for HtmlReducer {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::TagToken(tag) => return self.process_tag(tag),
+            Token::CharacterTokens(text) => self.process_text(&text),
+            Token::DoctypeToken(_)
+            | Token::CommentToken(_)
+            | Token::NullCharacterToken
+            | Token::EOFToken
+            | Token::ParseError(_) => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+enum PageBuffer {
+    Html {
+        tokenizer: Box<Tokenizer<HtmlReducer>>,
+        input: BufferQueue,
+    },
+    Text {
+        text: String,
+        num_chars: usize,
+    },
+}
+
+pub struct StreamingPageReader {
+    decoder: Decoder,
+    buffer: PageBuffer,
+    max_content_chars: Option<usize>,
+    max_bytes: usize,
+}
+
+impl StreamingPageReader {
+    pub fn new(
+        content_type: Option<&str>,
+        is_html: bool,
+        max_content_chars: Option<usize>,
+        max_bytes: usize,
+    ) -> Self {
+        let encoding = content_type
+            .and_then(|content_type| {
+                content_type.split(';').find_map(|param| {
+                    let (key, value) = param.split_once('=')?;
+                    if key.trim().eq_ignore_ascii_case("charset") {
+                        Encoding::for_label(value.trim().trim_matches('"').as_bytes())
+                    } else {
+                        None
+                    }
+                })
+            })
+            .unwrap_or(UTF_8);
+        let buffer = if is_html {
+            PageBuffer::Html {
+                tokenizer: Box::new(Tokenizer::new(
+                    HtmlReducer::new(max_content_chars),
+                    TokenizerOpts::default(),
+                )),
+                input: BufferQueue::default(),
+            }
+        } else {
+            PageBuffer::Text {
+                text: String::new(),
+                num_chars: 0,
+            }
+        };
+        Self {
+            decoder: encoding.new_decoder(),
+            buffer,
+            max_content_chars,
+            max_bytes,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        match &self.buffer {
+            PageBuffer::Html { tokenizer, .. } => {
+                tokenizer.sink.html.borrow().len() >= self.max_bytes || tokenizer.sink.is_full()
+            }
+            PageBuffer::Text { text, num_chars } => {
+                text.len() >= self.max_bytes
+                    || self
+                        .max_content_chars
+                        .is_some_and(|max_content_chars| *num_chars >= max_content_chars)
+            }
+        }
+    }
+
+    fn decode(&mut self, chunk: &[u8], last: bool) -> String {
+        let capacity = self
+            .decoder
+            .max_utf8_buffer_length(chunk.len())
+            .unwrap_or(chunk.len() * 3);
+        let mut decoded = String::with_capacity(capacity);
+        let _ = self.decoder.decode_to_string(chunk, &mut decoded, last);
+        decoded
+    }
+
+    fn feed(&mut self, decoded: String) {
+        match &mut self.buffer {
+            PageBuffer::Html { tokenizer, input } => {
+                input.push_back(StrTendril::from(decoded));
+                let _ = tokenizer.feed(input);
+            }
+            PageBuffer::Text { text, num_chars } => {
+                *num_chars += decoded.chars().count();
+                text.push_str(&decoded);
+            }
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        if self.is_full() {
+            return;
+        }
+        let decoded = self.decode(chunk, false);
+        self.feed(decoded);
+    }
+
+    pub fn finish(mut self) -> String {
+        if !self.is_full() {
+            let decoded = self.decode(&[], true);
+            self.feed(decoded);
+        }
+        match self.buffer {
+            PageBuffer::Html { tokenizer, input } => {
+                let _ = tokenizer.feed(&input);
+                tokenizer.end();
+                tokenizer.sink.html.into_inner()
+            }
+            PageBuffer::Text { text, .. } => text,
+        }
+    }
+}
+
+pub async fn read_body_streaming(
+    mut response: reqwest::Response,
+    is_html: bool,
+    max_content_chars: Option<usize>,
+    max_bytes: usize,
+) -> Result<String, WebpageParseError> {
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let (chunks_tx, mut chunks_rx) = mpsc::channel::<Bytes>(CHUNK_CHANNEL_CAPACITY);
+    let is_full = Arc::new(AtomicBool::new(false));
+    let reader_is_full = is_full.clone();
+    let reader = tokio::task::spawn_blocking(move || {
+        let mut reader = StreamingPageReader::new(
+            content_type.as_deref(),
+            is_html,
+            max_content_chars,
+            max_bytes,
+        );
+        while let Some(chunk) = chunks_rx.blocking_recv() {
+            reader.push(&chunk);
+            if reader.is_full() {
+                reader_is_full.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+        reader.finish()
+    });
+    while !is_full.load(Ordering::Relaxed) {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                if chunks_tx.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => return Err(WebpageParseError::FetchError(e)),
+        }
+    }
+    drop(chunks_tx);
+    drop(response);
+    match reader.await {
+        Ok(body) => Ok(body),
+        Err(e) => Err(WebpageParseError::DomParseError(DomParseError::ParseError(
+            e.to_string(),
+        ))),
+    }
+}
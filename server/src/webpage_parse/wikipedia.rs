@@ -0,0 +1,83 @@
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::HashMap;
+
+const SKIPPED_INFOBOX_TAGS: [&str; 3] = ["sup", "style", "script"];
+
+fn is_wikipedia(document: &Html) -> bool {
+    let site_name_selector = Selector::parse(r#"meta[property="og:site_name"]"#).unwrap();
+    document
+        .select(&site_name_selector)
+        .filter_map(|element| element.value().attr("content"))
+        .any(|site_name| site_name.contains("Wikipedia"))
+}
+
+fn collect_cell_text(element: ElementRef, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(child_text) => text.push_str(child_text),
+            Node::Element(child_element) => {
+                if SKIPPED_INFOBOX_TAGS.contains(&child_element.name()) {
+                    continue;
+                }
+                if child_element.name() == "br" || child_element.name() == "li" {
+                    text.push(' ');
+                }
+                if let Some(child_element) = ElementRef::wrap(child) {
+                    collect_cell_text(child_element, text);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn infobox_cell_text(cell: ElementRef) -> String {
+    let footnote_marker = Regex::new(r"\[(\d+|[a-z]|note \d+|citation needed)\]").unwrap();
+    let mut text = String::new();
+    collect_cell_text(cell, &mut text);
+    let text = footnote_marker.replace_all(&text, "");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn extract_wikipedia_infobox(document: &Html) -> Option<HashMap<String, String>> {
+    if !is_wikipedia(document) {
+        return None;
+    }
+    let infobox_selector = Selector::parse("table.infobox").unwrap();
+    let row_selector = Selector::parse("tr").unwrap();
+    let infobox = document.select(&infobox_selector).next()?;
+    let mut fields = HashMap::new();
+    for row in infobox.select(&row_selector) {
+        let cells = row.children().filter_map(ElementRef::wrap);
+        let mut key = None;
+        let mut value = None;
+        for cell in cells {
+            match cell.value().name() {
+                "th" if key.is_none() => key = Some(infobox_cell_text(cell)),
+                "td" if value.is_none() => value = Some(infobox_cell_text(cell)),
+                _ => {}
+            }
+        }
+        if let (Some(key), Some(value)) = (key, value) {
+            if !key.is_empty() && !value.is_empty() {
+                fields.entry(key).or_insert(value);
+            }
+        }
+    }
+    if fields.is_empty() {
+        return None;
+    }
+    Some(fields)
+}
+
+pub fn format_wikipedia_infobox(infobox: &HashMap<String, String>) -> String {
+    let mut fields = infobox.iter().collect::<Vec<_>>();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    let lines = fields
+        .into_iter()
+        .map(|(key, value)| format!("{}: {}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("Infobox:\n{}", lines)
+}
@@ -0,0 +1,245 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::future::BoxFuture;
+use ring::digest;
+use rocket::data::{IoHandler, IoStream};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder, Response};
+use rocket::Request;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const WEBSOCKET_VERSION: &str = "13";
+const MAX_MESSAGE_BYTES: usize = 1 << 20;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+pub fn websocket_accept_key(key: &str) -> String {
+    let digest = digest::digest(
+        &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key.trim(), WEBSOCKET_GUID).as_bytes(),
+    );
+    STANDARD.encode(digest.as_ref())
+}
+
+pub struct WebSocket {
+    accept_key: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebSocket {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let is_websocket_upgrade = request
+            .headers()
+            .get_one("Upgrade")
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+        if is_websocket_upgrade
+            && request.headers().get_one("Sec-WebSocket-Version") != Some(WEBSOCKET_VERSION)
+        {
+            return Outcome::Error((
+                Status::UpgradeRequired,
+                "Unsupported WebSocket version, expected 13",
+            ));
+        }
+        match request.headers().get_one("Sec-WebSocket-Key") {
+            Some(key) if is_websocket_upgrade => Outcome::Success(WebSocket {
+                accept_key: websocket_accept_key(key),
+            }),
+            _ => Outcome::Error((Status::BadRequest, "Expected a WebSocket upgrade request")),
+        }
+    }
+}
+
+type WebSocketHandler =
+    Box<dyn FnOnce(WebSocketStream) -> BoxFuture<'static, io::Result<()>> + Send>;
+
+pub struct WebSocketChannel {
+    accept_key: String,
+    handler: WebSocketHandler,
+}
+
+impl WebSocket {
+    pub fn channel<F, Fut>(self, handler: F) -> WebSocketChannel
+    where
+        F: FnOnce(WebSocketStream) -> Fut + Send + 'static,
+        Fut: Future<Output = io::Result<()>> + Send + 'static,
+    {
+        WebSocketChannel {
+            accept_key: self.accept_key,
+            handler: Box::new(move |stream| Box::pin(handler(stream))),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for WebSocketChannel {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .raw_header("Sec-WebSocket-Accept", self.accept_key)
+            .upgrade(
+                "websocket",
+                WebSocketUpgrade {
+                    handler: self.handler,
+                },
+            )
+            .ok()
+    }
+}
+
+struct WebSocketUpgrade {
+    handler: WebSocketHandler,
+}
+
+#[rocket::async_trait]
+impl IoHandler for WebSocketUpgrade {
+    async fn io(self: Pin<Box<Self>>, io: IoStream) -> io::Result<()> {
+        let upgrade = Pin::into_inner(self);
+        let (reader, writer) = tokio::io::split(io);
+        (upgrade.handler)(WebSocketStream {
+            reader: WebSocketReader {
+                reader,
+                partial_message: None,
+            },
+            writer: WebSocketWriter { writer },
+        })
+        .await
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+pub struct WebSocketStream {
+    reader: WebSocketReader,
+    writer: WebSocketWriter,
+}
+
+impl WebSocketStream {
+    pub fn split(self) -> (WebSocketReader, WebSocketWriter) {
+        (self.reader, self.writer)
+    }
+}
+
+pub struct WebSocketReader {
+    reader: ReadHalf<IoStream>,
+    partial_message: Option<(u8, Vec<u8>)>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl WebSocketReader {
+    async fn read_frame(&mut self) -> io::Result<(bool, u8, Vec<u8>)> {
+        let mut header = [0u8; 2];
+        self.reader.read_exact(&mut header).await?;
+        let is_final = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0f;
+        if header[1] & 0x80 == 0 {
+            return Err(invalid_data("Client WebSocket frames must be masked"));
+        }
+        let payload_len = match header[1] & 0x7f {
+            126 => self.reader.read_u16().await? as u64,
+            127 => self.reader.read_u64().await?,
+            payload_len => payload_len as u64,
+        };
+        if payload_len > MAX_MESSAGE_BYTES as u64 {
+            return Err(invalid_data("WebSocket frame is too large"));
+        }
+        let mut mask = [0u8; 4];
+        self.reader.read_exact(&mut mask).await?;
+        let mut payload = vec![0u8; payload_len as usize];
+        self.reader.read_exact(&mut payload).await?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        Ok((is_final, opcode, payload))
+    }
+
+    pub async fn recv(&mut self) -> io::Result<WebSocketMessage> {
+        loop {
+            let (is_final, opcode, payload) = self.read_frame().await?;
+            let (opcode, payload) = match opcode {
+                OPCODE_CLOSE => return Ok(WebSocketMessage::Close),
+                OPCODE_PING => return Ok(WebSocketMessage::Ping(payload)),
+                OPCODE_PONG => return Ok(WebSocketMessage::Pong(payload)),
+                OPCODE_TEXT | OPCODE_BINARY => (opcode, payload),
+                OPCODE_CONTINUATION => match self.partial_message.take() {
+                    Some((opcode, mut message)) => {
+                        message.extend(payload);
+                        (opcode, message)
+                    }
+                    None => return Err(invalid_data("Unexpected WebSocket continuation frame")),
+                },
+                _ => return Err(invalid_data("Unknown WebSocket opcode")),
+            };
+            if payload.len() > MAX_MESSAGE_BYTES {
+                return Err(invalid_data("WebSocket message is too large"));
+            }
+            if !is_final {
+                self.partial_message = Some((opcode, payload));
+                continue;
+            }
+            return match opcode {
+                OPCODE_TEXT => match String::from_utf8(payload) {
+                    Ok(text) => Ok(WebSocketMessage::Text(text)),
+                    Err(_) => Err(invalid_data("WebSocket text message is not valid UTF-8")),
+                },
+                _ => Ok(WebSocketMessage::Binary(payload)),
+            };
+        }
+    }
+}
+
+pub struct WebSocketWriter {
+    writer: WriteHalf<IoStream>,
+}
+
+impl WebSocketWriter {
+    async fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            len if len < 126 => frame.push(len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+        self.writer.write_all(&frame).await?;
+        self.writer.flush().await
+    }
+
+    pub async fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_frame(OPCODE_TEXT, text.as_bytes()).await
+    }
+
+    pub async fn send_pong(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.write_frame(OPCODE_PONG, payload).await
+    }
+
+    pub async fn close(&mut self) -> io::Result<()> {
+        self.write_frame(OPCODE_CLOSE, &[]).await?;
+        self.writer.shutdown().await
+    }
+}